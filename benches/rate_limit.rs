@@ -0,0 +1,90 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use deadpool_redis::redis;
+use forest_gate::{
+    features::onboarding::{
+        now_ms, MemoryLimiter, RateLimitAlgorithm, RateLimitFallback, RateLimiter,
+    },
+    infrastructure::persistence::redis::create_pool,
+};
+
+const ALGORITHMS: [RateLimitAlgorithm; 3] = [
+    RateLimitAlgorithm::SlidingWindow,
+    RateLimitAlgorithm::TokenBucket,
+    RateLimitAlgorithm::Gcra,
+];
+const LIMITS: [u32; 2] = [10, 1_000];
+const WINDOW_MS: u64 = 60_000;
+
+/// Latency of one `hit` per algorithm and limit against the Redis at
+/// `REDIS_URL`, plus the memory a key near its limit takes.
+/// Skipped when `REDIS_URL` is unset or unreachable.
+fn redis_algorithms(c: &mut Criterion) {
+    let Ok(url) = std::env::var("REDIS_URL") else {
+        eprintln!("REDIS_URL not set, skipping the Redis rate limit benchmarks");
+        return;
+    };
+    let rt = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let pool = create_pool(&url).expect("Redis pool");
+    let ping: Result<String, _> = rt.block_on(async {
+        let mut conn = pool.get().await.map_err(|e| e.to_string())?;
+        redis::cmd("PING")
+            .query_async(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())
+    });
+    if let Err(e) = ping {
+        eprintln!("Redis unreachable ({e}), skipping the Redis rate limit benchmarks");
+        return;
+    }
+    let limiter = RateLimiter::new(pool.clone(), RateLimitFallback::Closed);
+
+    let mut group = c.benchmark_group("rate_limit/redis");
+    for algorithm in ALGORITHMS {
+        for limit in LIMITS {
+            let key = format!("rl:bench:{}:{}", algorithm.as_str(), limit);
+            group.bench_with_input(
+                BenchmarkId::new(algorithm.as_str(), limit),
+                &limit,
+                |b, &limit| {
+                    b.to_async(&rt)
+                        .iter(|| limiter.hit(algorithm, &key, limit, WINDOW_MS))
+                },
+            );
+
+            let bytes: Option<i64> = rt.block_on(async {
+                let mut conn = pool.get().await.ok()?;
+                let bytes = redis::cmd("MEMORY")
+                    .arg("USAGE")
+                    .arg(&key)
+                    .query_async(&mut *conn)
+                    .await
+                    .ok();
+                let _: Result<(), _> = redis::cmd("DEL").arg(&key).query_async(&mut *conn).await;
+                bytes
+            });
+            if let Some(bytes) = bytes {
+                println!(
+                    "{} limit {limit}: {bytes} bytes per key",
+                    algorithm.as_str()
+                );
+            }
+        }
+    }
+    group.finish();
+}
+
+/// The in-process fallback over 1 000 keys.
+fn memory_fallback(c: &mut Criterion) {
+    let limiter = MemoryLimiter::default();
+    let keys: Vec<String> = (0..1_000).map(|i| format!("rl:bench:memory:{i}")).collect();
+    let mut i = 0;
+    c.bench_function("rate_limit/memory/gcra", |b| {
+        b.iter(|| {
+            i = (i + 1) % keys.len();
+            limiter.hit(&keys[i], 100, WINDOW_MS, now_ms())
+        })
+    });
+}
+
+criterion_group!(benches, redis_algorithms, memory_fallback);
+criterion_main!(benches);
@@ -0,0 +1,204 @@
+//! Bare-bones OpenID Connect provider for trying federated login locally.
+//!
+//! ```text
+//! cargo run --example mock_idp                                # http://localhost:9000
+//! OIDC_PROVIDERS_PATH=examples/mock_idp.toml cargo run        # forest_gate, provider "mock"
+//! ```
+//!
+//! `/authorize` signs in whoever `login_hint` names (default
+//! `alice@example.com`) without asking anything: never expose it.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::{get, http::header, post, web, App, HttpResponse, HttpServer, Responder};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use ring::{
+    rand::{SecureRandom, SystemRandom},
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+const KID: &str = "mock-idp";
+
+struct Idp {
+    issuer: String,
+    key: EncodingKey,
+    /// uncompressed P-256 point
+    public_key: Vec<u8>,
+    codes: Mutex<HashMap<String, PendingCode>>,
+}
+
+struct PendingCode {
+    client_id: String,
+    redirect_uri: String,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    email: String,
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    client_id: String,
+    redirect_uri: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    login_hint: Option<String>,
+}
+
+#[get("/.well-known/openid-configuration")]
+async fn discovery(idp: web::Data<Arc<Idp>>) -> impl Responder {
+    let issuer = &idp.issuer;
+    HttpResponse::Ok().json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/authorize"),
+        "token_endpoint": format!("{issuer}/token"),
+        "jwks_uri": format!("{issuer}/jwks"),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["ES256"],
+        "code_challenge_methods_supported": ["S256"],
+    }))
+}
+
+#[get("/jwks")]
+async fn jwks(idp: web::Data<Arc<Idp>>) -> impl Responder {
+    let (x, y) = idp.public_key[1..].split_at(32);
+    HttpResponse::Ok().json(json!({
+        "keys": [{
+            "kty": "EC",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(x),
+            "y": URL_SAFE_NO_PAD.encode(y),
+            "kid": KID,
+            "alg": "ES256",
+            "use": "sig",
+        }]
+    }))
+}
+
+#[get("/authorize")]
+async fn authorize(idp: web::Data<Arc<Idp>>, query: web::Query<AuthorizeQuery>) -> impl Responder {
+    let query = query.into_inner();
+    let Ok(mut redirect) = Url::parse(&query.redirect_uri) else {
+        return HttpResponse::BadRequest().body("invalid redirect_uri");
+    };
+
+    let code = random_token();
+    let email = query
+        .login_hint
+        .unwrap_or_else(|| "alice@example.com".into());
+    println!("authorize: {email} for {}", query.client_id);
+    idp.codes.lock().unwrap().insert(
+        code.clone(),
+        PendingCode {
+            client_id: query.client_id,
+            redirect_uri: query.redirect_uri,
+            nonce: query.nonce,
+            code_challenge: query.code_challenge,
+            email,
+        },
+    );
+
+    redirect.query_pairs_mut().append_pair("code", &code);
+    if let Some(state) = &query.state {
+        redirect.query_pairs_mut().append_pair("state", state);
+    }
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, redirect.to_string()))
+        .finish()
+}
+
+#[post("/token")]
+async fn token(
+    idp: web::Data<Arc<Idp>>,
+    form: web::Form<HashMap<String, String>>,
+) -> impl Responder {
+    let invalid = |reason: &str| {
+        HttpResponse::BadRequest()
+            .json(json!({"error": "invalid_grant", "error_description": reason}))
+    };
+    let field = |name: &str| form.get(name).map(String::as_str).unwrap_or_default();
+
+    let Some(pending) = idp.codes.lock().unwrap().remove(field("code")) else {
+        return invalid("unknown or used code");
+    };
+    if pending.client_id != field("client_id") || pending.redirect_uri != field("redirect_uri") {
+        return invalid("client_id or redirect_uri mismatch");
+    }
+    if let Some(challenge) = &pending.code_challenge {
+        let computed = URL_SAFE_NO_PAD.encode(Sha256::digest(field("code_verifier").as_bytes()));
+        if &computed != challenge {
+            return invalid("PKCE verification failed");
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let claims = json!({
+        "iss": idp.issuer,
+        "sub": format!("mock-{}", hex::encode(&Sha256::digest(pending.email.as_bytes())[..8])),
+        "aud": pending.client_id,
+        "iat": now,
+        "exp": now + 300,
+        "nonce": pending.nonce,
+        "email": pending.email,
+        "email_verified": true,
+        "preferred_username": pending.email.split('@').next(),
+    });
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some(KID.into());
+    let id_token = encode(&header, &claims, &idp.key).expect("sign id token");
+
+    HttpResponse::Ok().json(json!({
+        "access_token": random_token(),
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "id_token": id_token,
+    }))
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 24];
+    SystemRandom::new().fill(&mut bytes).expect("random bytes");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let port: u16 = std::env::var("MOCK_IDP_PORT")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(9000);
+
+    let rng = SystemRandom::new();
+    let pkcs8 =
+        EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).expect("generate key");
+    let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+        .expect("parse key");
+    let idp = Arc::new(Idp {
+        issuer: format!("http://localhost:{port}"),
+        key: EncodingKey::from_ec_der(pkcs8.as_ref()),
+        public_key: key_pair.public_key().as_ref().to_vec(),
+        codes: Mutex::new(HashMap::new()),
+    });
+
+    println!("mock identity provider on {}", idp.issuer);
+    HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(idp.clone()))
+            .service(discovery)
+            .service(jwks)
+            .service(authorize)
+            .service(token)
+    })
+    .bind(("127.0.0.1", port))?
+    .run()
+    .await
+}
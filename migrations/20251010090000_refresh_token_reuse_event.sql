@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'refresh_token_reuse';
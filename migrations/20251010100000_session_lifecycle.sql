@@ -0,0 +1,8 @@
+-- Add migration script here
+ALTER TABLE sessions
+  ADD COLUMN IF NOT EXISTS ended_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions (user_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_active_expires_at
+  ON sessions (expires_at)
+  WHERE status = 'active';
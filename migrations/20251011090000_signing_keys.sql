@@ -0,0 +1,18 @@
+-- Key ring for JWT signing. Exactly one key is active (signs new tokens);
+-- retired keys keep verifying until every token they signed has expired.
+CREATE TYPE signing_key_status_enum AS ENUM ('active', 'retired');
+
+CREATE TABLE IF NOT EXISTS signing_keys (
+    kid            TEXT PRIMARY KEY,                 -- RFC 7638 JWK thumbprint
+    algorithm      TEXT NOT NULL DEFAULT 'ES256',
+    private_pkcs8  BYTEA NOT NULL,                   -- PKCS#8 DER, P-256
+    public_x       TEXT NOT NULL,                    -- base64url, JWK "x"
+    public_y       TEXT NOT NULL,                    -- base64url, JWK "y"
+    status         signing_key_status_enum NOT NULL DEFAULT 'active',
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+    retired_at     TIMESTAMPTZ,
+    verify_until   TIMESTAMPTZ                       -- NULL while active
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS uq_signing_keys_single_active
+    ON signing_keys (status) WHERE status = 'active';
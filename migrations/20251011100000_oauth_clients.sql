@@ -0,0 +1,12 @@
+-- Applications that use forest_gate as their OpenID Connect provider.
+-- Public clients (SPA, mobile) have no secret and rely on PKCE alone.
+CREATE TABLE IF NOT EXISTS oauth_clients (
+    id                  BIGSERIAL PRIMARY KEY,
+    client_id           TEXT NOT NULL UNIQUE,
+    client_secret_hash  TEXT,                       -- argon2, NULL for public clients
+    name                TEXT NOT NULL,
+    redirect_uris       TEXT[] NOT NULL DEFAULT '{}',  -- exact match only
+    allowed_scopes      TEXT[] NOT NULL DEFAULT '{openid}',
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
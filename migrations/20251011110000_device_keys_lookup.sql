@@ -0,0 +1,4 @@
+-- DPoP: device keys are looked up by (device, key) on every login with a proof
+CREATE INDEX IF NOT EXISTS idx_device_keys_device_id_public_key
+  ON device_keys (device_id, public_key)
+  WHERE deleted_at IS NULL;
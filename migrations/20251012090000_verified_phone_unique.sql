@@ -0,0 +1,4 @@
+-- A verified phone number identifies exactly one live account (phone login).
+CREATE UNIQUE INDEX ux_users_verified_phone
+  ON users (phone_number)
+  WHERE is_phone_verified AND deleted_at IS NULL;
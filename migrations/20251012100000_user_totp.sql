@@ -0,0 +1,9 @@
+-- TOTP second factor (RFC 6238). One seed per user, sealed with AES-256-GCM.
+CREATE TABLE user_totp (
+  user_id         BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+  secret_sealed   BYTEA NOT NULL,
+  enabled_at      TIMESTAMPTZ,
+  last_used_step  BIGINT,
+  created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at      TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,12 @@
+-- WebAuthn passkeys live in device_keys (operation_usage = 'auth') next to DPoP keys;
+-- only passkeys have a credential id.
+ALTER TABLE device_keys
+  ADD COLUMN IF NOT EXISTS credential_id BYTEA,
+  ADD COLUMN IF NOT EXISTS sign_count    BIGINT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS ux_device_keys_credential_id
+  ON device_keys (credential_id)
+  WHERE credential_id IS NOT NULL;
+
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'passkey_registered';
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'passkey_clone_suspected';
@@ -0,0 +1,18 @@
+-- One row per recovery code (Argon2 PHC string) instead of one code per user;
+-- a code is spent once `used_at` is set.
+DROP TABLE IF EXISTS recovery_codes;
+
+CREATE TABLE recovery_codes (
+  id          BIGSERIAL PRIMARY KEY,
+  user_id     BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  code_hash   TEXT NOT NULL,
+  created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+  used_at     TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS ix_recovery_codes_user_unused
+  ON recovery_codes (user_id)
+  WHERE used_at IS NULL;
+
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'recovery_codes_generated';
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'recovery_code_used';
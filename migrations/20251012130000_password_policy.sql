@@ -0,0 +1,7 @@
+-- Password policy, applied whenever a password is set (sign-up, change, reset).
+ALTER TABLE config
+  ADD COLUMN IF NOT EXISTS password_min_length           INT     NOT NULL DEFAULT 8,
+  ADD COLUMN IF NOT EXISTS password_min_char_classes     INT     NOT NULL DEFAULT 1,
+  ADD COLUMN IF NOT EXISTS password_min_strength         INT     NOT NULL DEFAULT 2,
+  ADD COLUMN IF NOT EXISTS password_reject_personal_info BOOLEAN NOT NULL DEFAULT true,
+  ADD COLUMN IF NOT EXISTS password_check_breached       BOOLEAN NOT NULL DEFAULT true;
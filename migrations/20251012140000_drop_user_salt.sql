@@ -0,0 +1,2 @@
+-- The salt is part of the PHC string in password_hash; the column only duplicated it.
+ALTER TABLE users DROP COLUMN IF EXISTS salt;
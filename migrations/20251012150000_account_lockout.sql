@@ -0,0 +1,26 @@
+-- Progressive login backoff and temporary lockouts. Live counters and locks
+-- sit in Redis; every lockout is also recorded here.
+ALTER TABLE config
+  ADD COLUMN IF NOT EXISTS login_backoff_after        INT NOT NULL DEFAULT 3,
+  ADD COLUMN IF NOT EXISTS login_backoff_max_seconds  INT NOT NULL DEFAULT 60,
+  ADD COLUMN IF NOT EXISTS lockout_threshold          INT NOT NULL DEFAULT 10,
+  ADD COLUMN IF NOT EXISTS lockout_ip_threshold       INT NOT NULL DEFAULT 100,
+  ADD COLUMN IF NOT EXISTS lockout_duration_seconds   INT NOT NULL DEFAULT 900;
+
+CREATE TABLE IF NOT EXISTS account_lockouts (
+  id            BIGSERIAL PRIMARY KEY,
+  user_id       BIGINT REFERENCES users(id) ON DELETE CASCADE,
+  ip_bucket     TEXT,
+  failures      INT NOT NULL,
+  locked_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+  locked_until  TIMESTAMPTZ NOT NULL,
+  unlocked_at   TIMESTAMPTZ,
+  -- 'admin' or 'email' when released early; NULL when the lock simply expired
+  unlocked_by   TEXT,
+  CONSTRAINT ck_account_lockouts_target CHECK (user_id IS NOT NULL OR ip_bucket IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS ix_account_lockouts_user ON account_lockouts (user_id, locked_at DESC);
+
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'account_locked';
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'account_unlocked';
@@ -0,0 +1,8 @@
+-- role based access control on roles / users_roles
+
+INSERT INTO roles (name) VALUES ('admin') ON CONFLICT (name) DO NOTHING;
+
+CREATE INDEX IF NOT EXISTS ix_users_roles_role ON users_roles (role_id);
+
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'role_assigned';
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'role_revoked';
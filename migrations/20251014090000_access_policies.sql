@@ -0,0 +1,21 @@
+-- Attribute based access policies, evaluated by PolicyService and cached in
+-- Redis under `access_policies`. Deny beats allow; nothing applying means deny.
+CREATE TYPE policy_effect_enum AS ENUM ('allow', 'deny');
+
+CREATE TABLE IF NOT EXISTS access_policies (
+    id           SERIAL PRIMARY KEY,
+    name         TEXT NOT NULL UNIQUE,
+    description  TEXT,
+    action       TEXT NOT NULL,                      -- "audit_events:read", "audit_events:*" or "*"
+    effect       policy_effect_enum NOT NULL,
+    conditions   JSONB NOT NULL DEFAULT '[]',        -- all must hold, see policies::types::Condition
+    enabled      BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- keeps routes behind require_policy open to admins until narrower policies exist
+INSERT INTO access_policies (name, description, action, effect, conditions)
+VALUES ('admins', 'Admins may do anything a deny policy does not forbid', '*', 'allow',
+        '[{"type": "role_in", "roles": ["admin"]}]')
+ON CONFLICT (name) DO NOTHING;
@@ -0,0 +1,14 @@
+-- Accounts at upstream OpenID Connect providers that sign users in here.
+-- (provider, subject) is the provider's stable user id; emails can change.
+CREATE TABLE IF NOT EXISTS federated_identities (
+    id             BIGSERIAL PRIMARY KEY,
+    user_id        BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider       TEXT NOT NULL,                -- name in OIDC_PROVIDERS_PATH
+    subject        TEXT NOT NULL,                -- `sub` of the provider's ID token
+    email          TEXT,                         -- as last seen at the provider
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_login_at  TIMESTAMPTZ,
+    UNIQUE (provider, subject)
+);
+
+CREATE INDEX IF NOT EXISTS ix_federated_identities_user ON federated_identities (user_id);
@@ -0,0 +1,56 @@
+-- One row per way a user can sign in; replaces users.login_method and
+-- federated_identities. `identifier` is what a login resolves the user by:
+--   password   the user id (one per user)
+--   email_otp  the lowercased email the codes and magic links go to
+--   phone      the verified E.164 number
+--   passkey    the WebAuthn credential id, base64url
+--   federated  the provider's `sub` (provider names the OIDC provider)
+CREATE TYPE identity_kind_enum AS ENUM ('password', 'email_otp', 'phone', 'passkey', 'federated');
+
+CREATE TABLE IF NOT EXISTS user_identities (
+    id            BIGSERIAL PRIMARY KEY,
+    user_id       BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind          identity_kind_enum NOT NULL,
+    provider      TEXT,                        -- federated only
+    identifier    TEXT NOT NULL,
+    email         TEXT,                        -- federated: as last seen at the provider
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at  TIMESTAMPTZ,
+    UNIQUE NULLS NOT DISTINCT (kind, provider, identifier)
+);
+
+CREATE INDEX IF NOT EXISTS ix_user_identities_user ON user_identities (user_id);
+
+-- backfill from what every login flow used so far
+INSERT INTO user_identities (user_id, kind, identifier, created_at)
+SELECT id, 'password', id::text, created_at
+FROM users
+WHERE login_method <> 'with_oidc';
+
+INSERT INTO user_identities (user_id, kind, identifier, created_at)
+SELECT id, 'email_otp', lower(email), created_at
+FROM users
+WHERE is_email_verified
+ON CONFLICT DO NOTHING;
+
+INSERT INTO user_identities (user_id, kind, identifier, created_at)
+SELECT id, 'phone', phone_number, created_at
+FROM users
+WHERE is_phone_verified AND phone_number IS NOT NULL
+ON CONFLICT DO NOTHING;
+
+INSERT INTO user_identities (user_id, kind, identifier, created_at)
+SELECT user_id, 'passkey', translate(encode(credential_id, 'base64'), E'+/=\n', '-_'), created_at
+FROM device_keys
+WHERE credential_id IS NOT NULL AND user_id IS NOT NULL
+  AND revoked_at IS NULL AND deleted_at IS NULL;
+
+INSERT INTO user_identities (user_id, kind, provider, identifier, email, created_at, last_used_at)
+SELECT user_id, 'federated', provider, subject, email, created_at, last_login_at
+FROM federated_identities;
+
+DROP TABLE federated_identities;
+ALTER TABLE users DROP COLUMN login_method;
+
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'identity_linked';
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'identity_unlinked';
@@ -0,0 +1,20 @@
+-- Personal API keys for CLI tools and CI jobs. Only sha256(key) is stored;
+-- `prefix` is the start of the key, enough to recognise it in a list.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id            BIGSERIAL PRIMARY KEY,
+    user_id       BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name          TEXT NOT NULL,
+    prefix        TEXT NOT NULL,
+    key_hash      TEXT NOT NULL UNIQUE,
+    scopes        TEXT[] NOT NULL,             -- 'read' (GET/HEAD), 'write' (everything else)
+    expires_at    TIMESTAMPTZ,                 -- NULL: until revoked
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at  TIMESTAMPTZ,
+    last_used_ip  INET,
+    revoked_at    TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS ix_api_keys_user ON api_keys (user_id);
+
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'api_key_created';
+ALTER TYPE event_type_enum ADD VALUE IF NOT EXISTS 'api_key_revoked';
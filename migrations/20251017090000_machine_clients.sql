@@ -0,0 +1,7 @@
+-- Machine clients: backends that get tokens for themselves through the
+-- client-credentials grant. They authenticate with a secret (argon2, as for
+-- other confidential clients) or, when `jwks` is set, with a private_key_jwt
+-- assertion signed by one of those keys (RFC 7523).
+ALTER TABLE oauth_clients
+    ADD COLUMN IF NOT EXISTS grant_types TEXT[] NOT NULL DEFAULT '{authorization_code,refresh_token}',
+    ADD COLUMN IF NOT EXISTS jwks        JSONB;          -- {"keys": [...]}, public keys only
@@ -0,0 +1,7 @@
+-- Private signing keys are kept sealed with the app's SecretCipher (aad = kid)
+-- instead of as plain PKCS#8. Rows from before are sealed by the app on its
+-- next start, which then clears `private_pkcs8`.
+ALTER TABLE signing_keys ADD COLUMN IF NOT EXISTS private_sealed BYTEA;  -- nonce || ciphertext || tag
+ALTER TABLE signing_keys ALTER COLUMN private_pkcs8 DROP NOT NULL;
+ALTER TABLE signing_keys ADD CONSTRAINT signing_keys_private_present
+    CHECK (private_pkcs8 IS NOT NULL OR private_sealed IS NOT NULL);
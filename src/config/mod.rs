@@ -3,6 +3,10 @@ mod redis_settings;
 mod cloudflare_settings;
 pub mod email_settings;
 pub mod traits;
+mod rate_limit_settings;
+mod oidc_settings;
 
 pub use db_settings::*;
-pub use redis_settings::*;
\ No newline at end of file
+pub use redis_settings::*;
+pub use rate_limit_settings::*;
+pub use oidc_settings::*;
\ No newline at end of file
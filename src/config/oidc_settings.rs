@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+
+use config::{Config, ConfigError, File};
+use serde::Deserialize;
+
+use crate::config::traits::Env;
+use crate::features::clients::OidcProviderConfig;
+
+/// Upstream identity providers for federated login, read from the TOML file
+/// at `OIDC_PROVIDERS_PATH`; none without it.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OidcSettings {
+    #[serde(default)]
+    pub providers: Vec<OidcProviderConfig>,
+}
+
+impl Env for OidcSettings {
+    fn from_env() -> Result<Self, ConfigError> {
+        dotenvy::dotenv().ok(); // Load .env file if it exists
+
+        let Ok(path) = std::env::var("OIDC_PROVIDERS_PATH") else {
+            return Ok(Self::default());
+        };
+        let mut settings: Self = Config::builder()
+            .add_source(File::with_name(&path))
+            .build()?
+            .try_deserialize()?;
+
+        let mut names = HashSet::new();
+        for provider in &mut settings.providers {
+            let valid = !provider.name.is_empty()
+                && provider
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+            if !valid {
+                return Err(ConfigError::Message(format!(
+                    "identity provider name {:?} must use lowercase letters, digits and `-`",
+                    provider.name
+                )));
+            }
+            if !names.insert(provider.name.clone()) {
+                return Err(ConfigError::Message(format!(
+                    "identity provider {} is configured twice",
+                    provider.name
+                )));
+            }
+
+            // secrets stay out of the file: OIDC_GOOGLE_CLIENT_SECRET, ...
+            let var = format!(
+                "OIDC_{}_CLIENT_SECRET",
+                provider.name.to_uppercase().replace('-', "_")
+            );
+            if let Ok(secret) = std::env::var(var) {
+                provider.client_secret = Some(secret);
+            }
+        }
+        Ok(settings)
+    }
+}
@@ -0,0 +1,60 @@
+use config::{Config, ConfigError, File, FileFormat};
+use serde::Deserialize;
+
+use crate::config::traits::Env;
+use crate::features::onboarding::{RateLimitAlgorithm, RateLimitFallback};
+use crate::infrastructure::middlewares::rate_limit::{RateLimitKey, RateLimitPolicy};
+
+/// Built-in policies, used unless `RATE_LIMITS_PATH` points at another file.
+const DEFAULT_RATE_LIMITS: &str = include_str!("rate_limits.toml");
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitSettings {
+    /// for policies that don't name one
+    #[serde(default)]
+    pub algorithm: RateLimitAlgorithm,
+    /// while Redis is unreachable
+    #[serde(default)]
+    pub fallback: RateLimitFallback,
+    pub policies: Vec<RateLimitPolicy>,
+}
+
+impl Env for RateLimitSettings {
+    fn from_env() -> Result<Self, ConfigError> {
+        dotenvy::dotenv().ok(); // Load .env file if it exists
+
+        let builder = Config::builder();
+        let builder = match std::env::var("RATE_LIMITS_PATH") {
+            Ok(path) => builder.add_source(File::with_name(&path)),
+            Err(_) => builder.add_source(File::from_str(DEFAULT_RATE_LIMITS, FileFormat::Toml)),
+        };
+        let mut settings: Self = builder.build()?.try_deserialize()?;
+
+        for policy in &mut settings.policies {
+            policy.algorithm.get_or_insert(settings.algorithm);
+            if policy.limit == 0 || policy.window_seconds == 0 {
+                return Err(ConfigError::Message(format!(
+                    "rate limit for {} needs a limit and window_seconds above 0",
+                    policy.route
+                )));
+            }
+            let algorithm = policy.algorithm.unwrap_or_default();
+            if !algorithm.supports(policy.limit, policy.window_seconds * 1000) {
+                return Err(ConfigError::Message(format!(
+                    "rate limit for {}: {} can't count {} per {}s, pick a rounder limit",
+                    policy.route,
+                    algorithm.as_str(),
+                    policy.limit,
+                    policy.window_seconds
+                )));
+            }
+            if policy.key == RateLimitKey::Field && policy.field.is_none() {
+                return Err(ConfigError::Message(format!(
+                    "rate limit for {} has key = \"field\" but no field",
+                    policy.route
+                )));
+            }
+        }
+        Ok(settings)
+    }
+}
@@ -2,7 +2,11 @@ use actix_web::{post, web, HttpResponse, Result};
 use serde::Serialize;
 use utoipa::ToSchema;
 
-use crate::features::users::types::UserDto;
+use crate::features::{
+    auth::{types::RevocationTarget, AuthService},
+    users::types::UserDto,
+};
+use crate::infrastructure::middlewares::{admin_auth::require_role, auth::AuthenticatedUser};
 
 use super::types::AllUsersDto;
 use super::AdminService;
@@ -19,10 +23,12 @@ struct UsersPage<T> {
     tag = "admin",
     request_body = AllUsersDto,
     responses(
-        (status = 200, description = "List users with filters and pagination", body = UsersPage<UserDto>)
+        (status = 200, description = "List users with filters and pagination", body = UsersPage<UserDto>),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin")
     )
 )]
-#[post("/admin/app-users")]
+#[post("/admin/app-users", wrap = "require_role(\"admin\")")]
 pub async fn users(
     admin_service: web::Data<AdminService>,
     body: Result<web::Json<AllUsersDto>, actix_web::Error>,
@@ -39,3 +45,27 @@ pub async fn users(
 
     Ok(HttpResponse::Ok().json(UsersPage { items, total }))
 }
+
+#[utoipa::path(
+    post,
+    path = "/admin/revocations",
+    tag = "admin",
+    request_body = RevocationTarget,
+    responses(
+        (status = 204, description = "Matching tokens are rejected from now on"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+    )
+)]
+#[post("/admin/revocations", wrap = "require_role(\"admin\")")]
+pub async fn revoke(
+    admin: AuthenticatedUser,
+    auth_service: web::Data<AuthService>,
+    body: web::Json<RevocationTarget>,
+) -> Result<HttpResponse> {
+    let target = body.into_inner();
+    tracing::info!(admin_id = admin.uid, ?target, "revoking tokens");
+
+    auth_service.revoke(target).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -24,7 +24,7 @@ impl AdminService {
             .all(
                 dto.email_verified,
                 dto.phone_number_verified,
-                dto.login_method.as_deref(),
+                dto.identity,
                 dto.limit.unwrap_or(40),
                 dto.offset.unwrap_or(0),
             )
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use time::Date;
 
+use crate::features::identities::IdentityKind;
+
 
 
 #[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
@@ -9,8 +11,8 @@ pub struct AllUsersDto {
     pub email_verified: Option<bool>,
     /// Filter: is phone number verified
     pub phone_number_verified: Option<bool>,
-    /// Filter by login method (stringified, e.g. "Password", "Google", etc.)
-    pub login_method: Option<String>,
+    /// Filter: users who can sign in this way
+    pub identity: Option<IdentityKind>,
     /// Page size (1..=100). Default 20.
     pub limit: Option<i32>,
     /// Offset (>=0). Default 0.
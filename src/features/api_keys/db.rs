@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use sqlx::{prelude::FromRow, types::ipnetwork::IpNetwork};
+
+/// Row of `api_keys`, without the hash.
+#[derive(FromRow, Clone)]
+pub struct ApiKey {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<IpNetwork>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
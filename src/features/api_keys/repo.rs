@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::ipnetwork::IpNetwork, PgPool};
+
+use super::ApiKey;
+
+const COLUMNS: &str = "id, user_id, name, prefix, scopes, expires_at, created_at, \
+                       last_used_at, last_used_ip, revoked_at";
+
+#[derive(Clone)]
+pub struct ApiKeyRepository {
+    pool: PgPool,
+}
+
+impl ApiKeyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        user_id: i64,
+        name: &str,
+        prefix: &str,
+        key_hash: &str,
+        scopes: &[String],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> sqlx::Result<ApiKey> {
+        sqlx::query_as::<_, ApiKey>(&format!(
+            r#"
+            INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING {COLUMNS}
+            "#
+        ))
+        .bind(user_id)
+        .bind(name)
+        .bind(prefix)
+        .bind(key_hash)
+        .bind(scopes)
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// A key that is neither revoked nor expired and whose owner still exists.
+    pub async fn find_active(&self, key_hash: &str) -> sqlx::Result<Option<ApiKey>> {
+        sqlx::query_as::<_, ApiKey>(&format!(
+            r#"
+            SELECT {COLUMNS} FROM api_keys k
+            WHERE key_hash = $1
+              AND revoked_at IS NULL
+              AND (expires_at IS NULL OR expires_at > now())
+              AND EXISTS (SELECT 1 FROM users u WHERE u.id = k.user_id AND u.deleted_at IS NULL)
+            "#
+        ))
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Newest first; `user_id` narrows it to one user.
+    pub async fn list(
+        &self,
+        user_id: Option<i64>,
+        include_inactive: bool,
+        limit: i64,
+    ) -> sqlx::Result<Vec<ApiKey>> {
+        sqlx::query_as::<_, ApiKey>(&format!(
+            r#"
+            SELECT {COLUMNS} FROM api_keys
+            WHERE ($1::bigint IS NULL OR user_id = $1)
+              AND ($2 OR (revoked_at IS NULL AND (expires_at IS NULL OR expires_at > now())))
+            ORDER BY id DESC
+            LIMIT $3
+            "#
+        ))
+        .bind(user_id)
+        .bind(include_inactive)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Revokes a live key, only if it belongs to `user_id` when one is given.
+    /// Returns the owner, `None` if nothing was revoked.
+    pub async fn revoke(&self, id: i64, user_id: Option<i64>) -> sqlx::Result<Option<i64>> {
+        sqlx::query_scalar(
+            r#"
+            UPDATE api_keys SET revoked_at = now()
+            WHERE id = $1 AND ($2::bigint IS NULL OR user_id = $2) AND revoked_at IS NULL
+            RETURNING user_id
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn revoke_all(&self, user_id: i64) -> sqlx::Result<u64> {
+        let result = sqlx::query(
+            "UPDATE api_keys SET revoked_at = now() WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Records a use. Writes at most once a minute per key unless the IP changed,
+    /// so a busy CI job does not turn every request into an UPDATE.
+    pub async fn touch(&self, id: i64, ip: Option<IpNetwork>) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE api_keys SET last_used_at = now(), last_used_ip = COALESCE($2, last_used_ip)
+            WHERE id = $1
+              AND (last_used_at IS NULL
+                   OR last_used_at < now() - interval '1 minute'
+                   OR ($2 IS NOT NULL AND last_used_ip IS DISTINCT FROM $2))
+            "#,
+        )
+        .bind(id)
+        .bind(ip)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use validator::Validate;
+
+use crate::{
+    features::api_keys::{
+        types::{ApiKeyResp, ApiKeysQuery, CreateApiKeyReq, CreatedApiKeyResp},
+        ApiKeyService,
+    },
+    infrastructure::middlewares::{admin_auth::require_role, auth::AuthenticatedUser},
+};
+
+#[utoipa::path(
+    get,
+    path = "/users/me/api-keys",
+    tag = "users",
+    responses(
+        (status = 200, description = "The caller's keys, newest first; never the secrets", body = [ApiKeyResp]),
+        (status = 401, description = "Missing or invalid access token"),
+    )
+)]
+#[get("/users/me/api-keys")]
+pub async fn list_api_keys(
+    user: AuthenticatedUser,
+    api_key_service: web::Data<ApiKeyService>,
+) -> actix_web::Result<impl Responder> {
+    Ok(HttpResponse::Ok().json(api_key_service.list(user.uid).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/api-keys",
+    tag = "users",
+    request_body = CreateApiKeyReq,
+    responses(
+        (status = 201, description = "Key created; `key` is not shown again", body = CreatedApiKeyResp),
+        (status = 400, description = "Invalid name, scopes or expiry"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Called with an API key"),
+    )
+)]
+#[post("/users/me/api-keys")]
+pub async fn create_api_key(
+    user: AuthenticatedUser,
+    payload: web::Json<CreateApiKeyReq>,
+    api_key_service: web::Data<ApiKeyService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    let created = api_key_service.create(user.uid, &payload).await?;
+    Ok(HttpResponse::Created().json(created))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/api-keys/{id}",
+    tag = "users",
+    params(("id" = i64, Path, description = "Key id")),
+    responses(
+        (status = 204, description = "Revoked; the key stops working right away"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 404, description = "No such live key on this account"),
+    )
+)]
+#[delete("/users/me/api-keys/{id}")]
+pub async fn revoke_api_key(
+    user: AuthenticatedUser,
+    path: web::Path<i64>,
+    api_key_service: web::Data<ApiKeyService>,
+) -> actix_web::Result<impl Responder> {
+    api_key_service
+        .revoke(path.into_inner(), Some(user.uid))
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/api-keys",
+    tag = "admin",
+    params(ApiKeysQuery),
+    responses(
+        (status = 200, description = "Keys of every user, newest first", body = [ApiKeyResp]),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+    )
+)]
+#[get("/admin/api-keys", wrap = "require_role(\"admin\")")]
+pub async fn all_api_keys(
+    query: web::Query<ApiKeysQuery>,
+    api_key_service: web::Data<ApiKeyService>,
+) -> actix_web::Result<impl Responder> {
+    Ok(HttpResponse::Ok().json(api_key_service.all(&query).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/api-keys/{id}",
+    tag = "admin",
+    params(("id" = i64, Path, description = "Key id")),
+    responses(
+        (status = 204, description = "Revoked"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "No such live key"),
+    )
+)]
+#[delete("/admin/api-keys/{id}", wrap = "require_role(\"admin\")")]
+pub async fn admin_revoke_api_key(
+    admin: AuthenticatedUser,
+    path: web::Path<i64>,
+    api_key_service: web::Data<ApiKeyService>,
+) -> actix_web::Result<impl Responder> {
+    let id = path.into_inner();
+    tracing::info!(admin_id = admin.uid, api_key_id = id, "revoking api key");
+
+    api_key_service.revoke(id, None).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,123 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use password_hash::rand_core::{OsRng, RngCore};
+use sqlx::{types::ipnetwork::IpNetwork, PgPool};
+use std::net::IpAddr;
+
+use crate::{
+    features::{
+        api_keys::{
+            types::{ApiKeyResp, ApiKeyScope, ApiKeysQuery, CreateApiKeyReq, CreatedApiKeyResp},
+            ApiKey, ApiKeyRepository,
+        },
+        audits::{AuditRepository, EventType, LogLevel},
+        onboarding::sha256_hex,
+    },
+    utils::error::{Error, Result},
+};
+
+/// Every key starts with it, so the auth extractor can tell keys from JWTs
+/// and secret scanners can find leaked ones.
+pub const API_KEY_PREFIX: &str = "fgk_";
+/// `fgk_` + 8 characters of the secret, kept in clear for lists
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// Long-lived, user-managed credentials for CLI tools and CI jobs. The key
+/// itself is shown once; only its sha256 is stored (it is 256 random bits,
+/// so a slow hash would add nothing).
+#[derive(Clone)]
+pub struct ApiKeyService {
+    repo: ApiKeyRepository,
+    audit_repo: AuditRepository,
+}
+
+impl ApiKeyService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            repo: ApiKeyRepository::new(pool.clone()),
+            audit_repo: AuditRepository::new(pool),
+        }
+    }
+
+    pub async fn create(&self, user_id: i64, req: &CreateApiKeyReq) -> Result<CreatedApiKeyResp> {
+        let mut scopes: Vec<String> = req.scopes.iter().map(|s| s.as_str().to_owned()).collect();
+        scopes.sort();
+        scopes.dedup();
+
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let key = format!("{API_KEY_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes));
+
+        let api_key = self
+            .repo
+            .create(
+                user_id,
+                req.name.trim(),
+                &key[..DISPLAY_PREFIX_LEN],
+                &sha256_hex(&key),
+                &scopes,
+                req.expires_in_days.map(|d| Utc::now() + Duration::days(d)),
+            )
+            .await?;
+        self.audit_repo
+            .create(user_id, EventType::ApiKeyCreated, LogLevel::Info, None)
+            .await?;
+        tracing::info!(user_id, api_key_id = api_key.id, ?scopes, "created api key");
+
+        Ok(CreatedApiKeyResp {
+            key,
+            api_key: api_key.into(),
+        })
+    }
+
+    /// The user's keys, revoked and expired ones included.
+    pub async fn list(&self, user_id: i64) -> Result<Vec<ApiKeyResp>> {
+        let keys = self.repo.list(Some(user_id), true, 200).await?;
+        Ok(keys.into_iter().map(ApiKeyResp::from).collect())
+    }
+
+    pub async fn all(&self, query: &ApiKeysQuery) -> Result<Vec<ApiKeyResp>> {
+        let keys = self
+            .repo
+            .list(
+                query.user_id,
+                query.include_inactive.unwrap_or(false),
+                query.limit.unwrap_or(50).clamp(1, 200),
+            )
+            .await?;
+        Ok(keys.into_iter().map(ApiKeyResp::from).collect())
+    }
+
+    /// `owner` limits it to that user's keys; admins pass `None`.
+    pub async fn revoke(&self, id: i64, owner: Option<i64>) -> Result<()> {
+        let user_id = self.repo.revoke(id, owner).await?.ok_or(Error::NotFound)?;
+        self.audit_repo
+            .create(user_id, EventType::ApiKeyRevoked, LogLevel::Info, None)
+            .await?;
+        tracing::info!(user_id, api_key_id = id, "revoked api key");
+        Ok(())
+    }
+
+    /// Resolve a presented key for a request with `method`. Unknown, revoked
+    /// and expired keys are 401; a key without the scope for `method` is 403.
+    pub async fn authenticate(
+        &self,
+        key: &str,
+        method: &actix_web::http::Method,
+        ip: Option<IpAddr>,
+    ) -> Result<ApiKey> {
+        let api_key = self
+            .repo
+            .find_active(&sha256_hex(key))
+            .await?
+            .ok_or(Error::Unauthorized)?;
+
+        let needed = ApiKeyScope::for_method(method);
+        if !api_key.scopes.iter().any(|s| s == needed.as_str()) {
+            return Err(Error::Forbidden);
+        }
+
+        self.repo.touch(api_key.id, ip.map(IpNetwork::from)).await?;
+        Ok(api_key)
+    }
+}
@@ -1,9 +1,11 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{prelude::FromRow, Type};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, Type, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Type, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
 #[sqlx(type_name = "event_type_enum", rename_all = "snake_case")]
 pub enum EventType {
     // Admin related
@@ -11,9 +13,26 @@ pub enum EventType {
 
     // User related
     Login,
+
+    // Security related
+    RefreshTokenReuse,
+    PasskeyRegistered,
+    /// sign counter did not grow: the authenticator may have been cloned
+    PasskeyCloneSuspected,
+    RecoveryCodesGenerated,
+    RecoveryCodeUsed,
+    AccountLocked,
+    AccountUnlocked,
+    RoleAssigned,
+    RoleRevoked,
+    IdentityLinked,
+    IdentityUnlinked,
+    ApiKeyCreated,
+    ApiKeyRevoked,
 }
 
-#[derive(Debug, Serialize, Deserialize, Type, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Type, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
 #[sqlx(type_name = "log_level_enum", rename_all = "snake_case")]
 pub enum LogLevel {
     Debug,
@@ -1,10 +1,17 @@
-use actix_web::{cookie::{Cookie, SameSite}, post, web, HttpResponse, Result};
+use actix_web::{
+    cookie::{Cookie, SameSite},
+    get, post, web, HttpResponse, Result,
+};
 use serde_json::json;
 use time::Duration;
 use uuid::Uuid;
 
 use crate::{
-    features::audits::{types::AuditEvent, AuditService},
+    features::audits::{
+        types::{AuditEvent, AuditEventDto, AuditEventsQuery},
+        AuditService,
+    },
+    infrastructure::middlewares::policy_auth::require_policy,
     utils::error::Error,
 };
 
@@ -24,7 +31,7 @@ pub const COOKIE_TRACKING: &str = "auth-track_interaction";
 )]
 #[post("/audit/init")]
 pub async fn audit_init() -> Result<HttpResponse> {
-    let cookie =Cookie::build(COOKIE_TRACKING, Uuid::new_v4().to_string())
+    let cookie = Cookie::build(COOKIE_TRACKING, Uuid::new_v4().to_string())
         .http_only(true)
         .secure(true)
         .same_site(SameSite::Lax)
@@ -45,7 +52,8 @@ pub async fn audit_init() -> Result<HttpResponse> {
     tag = "audit",
     responses(
         (status = 200, description = "Audit user session"),
-        (status = 400, description = "Bad Request")
+        (status = 400, description = "Bad Request"),
+        (status = 429, description = "Too many batches for this interaction or network")
     )
 )]
 #[post("/audit/batch")]
@@ -70,3 +78,23 @@ pub async fn audit_batch(
 
     Ok(HttpResponse::Ok().finish())
 }
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit-events",
+    tag = "admin",
+    params(AuditEventsQuery),
+    responses(
+        (status = 200, description = "Security events, newest first", body = [AuditEventDto]),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Denied by the access policies"),
+    )
+)]
+#[get("/admin/audit-events", wrap = "require_policy(\"audit_events:read\")")]
+pub async fn audit_events(
+    audit_service: web::Data<AuditService>,
+    query: web::Query<AuditEventsQuery>,
+) -> Result<HttpResponse> {
+    let events = audit_service.events(query.user_id, query.limit).await?;
+    Ok(HttpResponse::Ok().json(events))
+}
@@ -1,8 +1,10 @@
+use crate::features::audits::{types::AuditEventDto, AuditRepository};
 use crate::features::clients::{OpenRouterClient, OrMessage};
 use crate::utils::error::{Error, Result};
 use deadpool_redis::redis::{self, aio::PubSub, AsyncCommands};
 use deadpool_redis::Pool;
 use futures::StreamExt;
+use sqlx::PgPool;
 use std::path::Path;
 use tokio::fs::{self, OpenOptions};
 use tokio::io::AsyncWriteExt;
@@ -10,11 +12,29 @@ use tokio::io::AsyncWriteExt;
 #[derive(Clone)]
 pub struct AuditService {
     redis_pool: Pool,
+    audit_repo: AuditRepository,
 }
 
 impl AuditService {
-    pub fn new(redis_pool: Pool) -> Self {
-        Self { redis_pool }
+    pub fn new(pool: PgPool, redis_pool: Pool) -> Self {
+        Self {
+            redis_pool,
+            audit_repo: AuditRepository::new(pool),
+        }
+    }
+
+    /// Security events from `audit_events`, newest first.
+    pub async fn events(
+        &self,
+        user_id: Option<i64>,
+        limit: Option<i64>,
+    ) -> Result<Vec<AuditEventDto>> {
+        let limit = limit.unwrap_or(50).clamp(1, 200);
+        let events = match user_id {
+            Some(user_id) => self.audit_repo.list_by_user(user_id, limit).await?,
+            None => self.audit_repo.list_recent(limit).await?,
+        };
+        Ok(events.into_iter().map(AuditEventDto::from).collect())
     }
 
     /// Append events and refresh the inactivity timer (60s).
@@ -41,7 +61,7 @@ impl AuditService {
             .arg(60)
             .ignore();
 
-        pipe.query_async(&mut *conn)
+        pipe.query_async::<()>(&mut *conn)
             .await
             .map_err(|e| Error::Unexpected(format!("redis pipeline error: {e}")))?;
 
@@ -54,7 +74,7 @@ impl AuditService {
     pub fn spawn_inactivity_flusher(
         &self,
         redis_url: &str,
-        pg_url: &str, // unused for now
+        pg_url: &str,                        // unused for now
         openrouter_client: OpenRouterClient, // pass owned; moved into task
     ) {
         let redis_url = redis_url.to_owned();
@@ -144,7 +164,9 @@ async fn run_flusher(
             {
                 Ok(s) => s,
                 Err(err) => {
-                    tracing::warn!("summary via OpenRouter failed: {err:?}; falling back to raw list");
+                    tracing::warn!(
+                        "summary via OpenRouter failed: {err:?}; falling back to raw list"
+                    );
                     fallback_summary(&events)
                 }
             };
@@ -185,7 +207,8 @@ async fn summarize_events(
 - Group similar actions; avoid duplicates and noise.
 - Infer the user's goal when clear, but do not invent facts.
 - If the user asked questions, include them as: The user asked: "…".
-- Do NOT include IDs or internal metadata in the prose."#.into(),
+- Do NOT include IDs or internal metadata in the prose."#
+            .into(),
     };
 
     let user_content = format!(
@@ -231,7 +254,11 @@ async fn write_interaction_markdown(
     let events_md = if events.is_empty() {
         "- (no events)".to_string()
     } else {
-        events.iter().map(|e| format!("- {}", e)).collect::<Vec<_>>().join("\n")
+        events
+            .iter()
+            .map(|e| format!("- {}", e))
+            .collect::<Vec<_>>()
+            .join("\n")
     };
 
     let mut block = String::new();
@@ -1,45 +1,45 @@
-// use super::db::{AuditEvent, EventType, LogLevel};
-// use chrono::{DateTime, Utc};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
-// use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
 
-// Same shape as the entity (as requested)
-// #[derive(Debug, Serialize, Deserialize, Clone)]
-// pub struct AuditEventDto {
-//     pub id: Uuid,
-//     pub user_id: i64,
-//     pub event_type: EventType,
-//     pub log_level: LogLevel,
-//     pub session_id: Option<Uuid>,
-//     pub created_at: DateTime<Utc>,
-// }
+use super::db::{self, EventType, LogLevel};
 
-// impl From<AuditEvent> for AuditEventDto {
-//     fn from(e: AuditEvent) -> Self {
-//         Self {
-//             id: e.id,
-//             user_id: e.user_id,
-//             event_type: e.event_type,
-//             log_level: e.log_level,
-//             session_id: e.session_id,
-//             created_at: e.created_at,
-//         }
-//     }
-// }
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEventDto {
+    #[schema(value_type = String)]
+    pub id: Uuid,
+    pub user_id: i64,
+    pub event_type: EventType,
+    pub log_level: LogLevel,
+    #[schema(value_type = Option<String>)]
+    pub session_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<db::AuditEvent> for AuditEventDto {
+    fn from(e: db::AuditEvent) -> Self {
+        Self {
+            id: e.id,
+            user_id: e.user_id,
+            event_type: e.event_type,
+            log_level: e.log_level,
+            session_id: e.session_id,
+            created_at: e.created_at,
+        }
+    }
+}
 
-// impl From<AuditEventDto> for AuditEvent {
-//     fn from(d: AuditEventDto) -> Self {
-//         Self {
-//             id: d.id,
-//             user_id: d.user_id,
-//             event_type: d.event_type,
-//             log_level: d.log_level,
-//             session_id: d.session_id,
-//             created_at: d.created_at,
-//         }
-//     }
-// }
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct AuditEventsQuery {
+    /// only this user's events
+    pub user_id: Option<i64>,
+    /// newest first, 1..=200; default 50
+    pub limit: Option<i64>,
+}
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct AuditEvent {
@@ -0,0 +1,32 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, types::ipnetwork::IpNetwork, Type};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "session_status_enum", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStatus {
+    Active,
+    Expired,
+    Terminated,
+}
+
+#[derive(Debug, FromRow, Clone)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: i64,
+    pub device_id: i64,
+    pub status: SessionStatus,
+    pub ip_address: Option<IpNetwork>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    pub fn is_active(&self) -> bool {
+        self.status == SessionStatus::Active && self.expires_at > Utc::now()
+    }
+}
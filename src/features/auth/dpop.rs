@@ -0,0 +1,159 @@
+use actix_web::HttpRequest;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use deadpool_redis::{redis, Pool};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::utils::{
+    crypto::p256_jwk_thumbprint,
+    error::{Error, Result},
+};
+
+pub const DPOP_HEADER: &str = "DPoP";
+/// How far a proof's `iat` may be from our clock, either way.
+const PROOF_MAX_SKEW_SECONDS: i64 = 60;
+
+/// region Redis prefixes
+/// proof jti -> "1" while the proof could still be accepted (replay guard)
+pub const DPOP_JTI_PREFIX: &str = "auth:dpop:v1:jti:";
+/// endregion Redis prefixes
+
+#[derive(Deserialize)]
+struct ProofClaims {
+    jti: String,
+    htm: String,
+    htu: String,
+    iat: i64,
+    ath: Option<String>,
+}
+
+/// The parts of a request a DPoP proof is checked against.
+pub struct DpopRequest {
+    pub proof: Option<String>,
+    pub method: String,
+    pub url: String,
+}
+
+impl DpopRequest {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let info = req.connection_info();
+        Self {
+            proof: req
+                .headers()
+                .get(DPOP_HEADER)
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_owned),
+            method: req.method().as_str().to_owned(),
+            url: format!("{}://{}{}", info.scheme(), info.host(), req.path()),
+        }
+    }
+}
+
+/// A verified proof: the key that signed it.
+pub struct DpopKey {
+    /// RFC 7638 thumbprint, what `cnf.jkt` carries
+    pub jkt: String,
+    /// uncompressed SEC1 point (0x04 || x || y), what `device_keys.public_key` stores
+    pub public_key: Vec<u8>,
+}
+
+/// RFC 9449 proof validation. Only ES256 device keys are accepted.
+#[derive(Clone)]
+pub struct DpopVerifier {
+    redis_pool: Pool,
+}
+
+impl DpopVerifier {
+    pub fn new(redis_pool: Pool) -> Self {
+        Self { redis_pool }
+    }
+
+    /// Check the `DPoP` header of `req`. With `access_token` the proof must also
+    /// carry its hash (`ath`), as required when presenting a bound token.
+    pub async fn verify(&self, req: &DpopRequest, access_token: Option<&str>) -> Result<DpopKey> {
+        let proof = req.proof.as_deref().ok_or(Error::Unauthorized)?;
+
+        let header = decode_header(proof).map_err(|_| Error::Unauthorized)?;
+        if header.alg != Algorithm::ES256 || header.typ.as_deref() != Some("dpop+jwt") {
+            return Err(Error::Unauthorized);
+        }
+        let (x, y) = match header.jwk.map(|jwk| jwk.algorithm) {
+            Some(AlgorithmParameters::EllipticCurve(ec)) if ec.curve == EllipticCurve::P256 => {
+                (ec.x, ec.y)
+            }
+            _ => return Err(Error::Unauthorized),
+        };
+
+        let key = DecodingKey::from_ec_components(&x, &y).map_err(|_| Error::Unauthorized)?;
+        let mut val = Validation::new(Algorithm::ES256);
+        val.required_spec_claims.clear();
+        val.validate_exp = false;
+        let claims = decode::<ProofClaims>(proof, &key, &val)
+            .map_err(|_| Error::Unauthorized)?
+            .claims;
+
+        if (Utc::now().timestamp() - claims.iat).abs() > PROOF_MAX_SKEW_SECONDS {
+            return Err(Error::Unauthorized);
+        }
+        if !claims.htm.eq_ignore_ascii_case(&req.method) || !same_target(&claims.htu, &req.url) {
+            return Err(Error::Unauthorized);
+        }
+        if let Some(token) = access_token {
+            let expected = URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()));
+            if claims.ath.as_deref() != Some(expected.as_str()) {
+                return Err(Error::Unauthorized);
+            }
+        }
+
+        // each proof is single use; NX fails if the jti was seen inside the window
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let fresh: Option<String> = redis::cmd("SET")
+            .arg(format!("{}{}", DPOP_JTI_PREFIX, claims.jti))
+            .arg("1")
+            .arg("NX")
+            .arg("EX")
+            .arg(PROOF_MAX_SKEW_SECONDS * 2)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        if fresh.is_none() {
+            return Err(Error::Unauthorized);
+        }
+
+        let mut public_key = vec![0x04];
+        public_key.extend(
+            URL_SAFE_NO_PAD
+                .decode(&x)
+                .map_err(|_| Error::Unauthorized)?,
+        );
+        public_key.extend(
+            URL_SAFE_NO_PAD
+                .decode(&y)
+                .map_err(|_| Error::Unauthorized)?,
+        );
+
+        Ok(DpopKey {
+            jkt: p256_jwk_thumbprint(&x, &y),
+            public_key,
+        })
+    }
+}
+
+/// `htu` is compared without query and fragment (RFC 9449 §4.3).
+fn same_target(htu: &str, url: &str) -> bool {
+    let strip = |u: &str| {
+        Url::parse(u).ok().map(|mut u| {
+            u.set_query(None);
+            u.set_fragment(None);
+            u
+        })
+    };
+    matches!((strip(htu), strip(url)), (Some(a), Some(b)) if a == b)
+}
@@ -0,0 +1,14 @@
+mod db;
+mod dpop;
+mod repo;
+mod revocation;
+mod routes;
+mod service;
+pub mod types;
+
+pub(super) use db::*;
+pub use dpop::*;
+pub(super) use repo::*;
+pub use revocation::*;
+pub use routes::*;
+pub use service::*;
@@ -1,110 +1,159 @@
-use sqlx::PgPool;
-use chrono::Utc;
-use crate::models::user::{User, CreateUserDto, LoginMethod};
+use chrono::{DateTime, Utc};
+use sqlx::{types::ipnetwork::IpNetwork, PgPool};
+use uuid::Uuid;
 
-pub struct UserRepository {
+use super::{Session, SessionStatus};
+
+#[derive(Clone)]
+pub struct SessionRepository {
     pool: PgPool,
 }
 
-impl UserRepository {
+impl SessionRepository {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
 
-    pub async fn create(&self, user_dto: CreateUserDto, password_hash: String, salt: Vec<u8>) -> Result<User, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
+    pub async fn create(
+        &self,
+        user_id: i64,
+        device_id: i64,
+        ip_address: Option<IpNetwork>,
+        expires_at: DateTime<Utc>,
+    ) -> sqlx::Result<Session> {
+        sqlx::query_as::<_, Session>(
             r#"
-            INSERT INTO users 
-            (
-                username, 
-                email, 
-                phone_number, 
-                password_hash, 
-                salt, 
-                is_email_verified, 
-                is_phone_verified, 
-                login_method, 
-                created_at, 
-                updated_at
-            )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-            RETURNING 
-                id, 
-                username, 
-                email, 
-                phone_number, 
-                password_hash, 
-                salt, 
-                is_email_verified, 
-                is_phone_verified, 
-                login_method as "login_method: LoginMethod", 
-                created_at, 
-                updated_at, 
-                deleted_at
+            INSERT INTO sessions (user_id, device_id, status, ip_address, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, user_id, device_id, status, ip_address, created_at, expires_at, ended_at
             "#,
-            user_dto.username,
-            user_dto.email,
-            user_dto.phone_number,
-            password_hash,
-            salt,
-            false, // is_email_verified
-            false, // is_phone_verified
-            user_dto.login_method as LoginMethod,
-            Utc::now(),
-            Utc::now()
         )
+        .bind(user_id)
+        .bind(device_id)
+        .bind(SessionStatus::Active)
+        .bind(ip_address)
+        .bind(expires_at)
         .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn find_by_id(&self, id: Uuid) -> sqlx::Result<Option<Session>> {
+        sqlx::query_as::<_, Session>(
+            r#"
+            SELECT id, user_id, device_id, status, ip_address, created_at, expires_at, ended_at
+            FROM sessions
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Push the expiry of an active session forward (on refresh).
+    pub async fn extend(&self, id: Uuid, expires_at: DateTime<Utc>) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE sessions
+            SET expires_at = $2
+            WHERE id = $1 AND status = $3
+            "#,
+        )
+        .bind(id)
+        .bind(expires_at)
+        .bind(SessionStatus::Active)
+        .execute(&self.pool)
         .await?;
 
-        Ok(user)
+        Ok(())
     }
 
-    pub async fn find_by_id(&self, user_id: i64) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
+    /// End an active session on purpose (logout, revocation). Returns false if it was not active.
+    pub async fn terminate(&self, id: Uuid) -> sqlx::Result<bool> {
+        let res = sqlx::query(
             r#"
-            SELECT id, username, email, phone_number, password_hash, salt, is_email_verified, is_phone_verified, login_method as "login_method: LoginMethod", created_at, updated_at, deleted_at
-            FROM users 
-            WHERE id = $1 AND deleted_at IS NULL
+            UPDATE sessions
+            SET status = $2, ended_at = now()
+            WHERE id = $1 AND status = $3
             "#,
-            user_id
         )
-        .fetch_optional(&self.pool)
+        .bind(id)
+        .bind(SessionStatus::Terminated)
+        .bind(SessionStatus::Active)
+        .execute(&self.pool)
         .await?;
 
-        Ok(user)
+        Ok(res.rows_affected() > 0)
     }
 
-    pub async fn find_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
+    /// Terminate every active session of a device. Returns how many were closed.
+    pub async fn terminate_by_device(&self, device_id: i64) -> sqlx::Result<u64> {
+        let res = sqlx::query(
             r#"
-            SELECT id, username, email, phone_number, password_hash, salt, is_email_verified, is_phone_verified, login_method as "login_method: LoginMethod", created_at, updated_at, deleted_at
-            FROM users 
-            WHERE email = $1 AND deleted_at IS NULL
+            UPDATE sessions
+            SET status = $2, ended_at = now()
+            WHERE device_id = $1 AND status = $3
             "#,
-            email
         )
-        .fetch_optional(&self.pool)
+        .bind(device_id)
+        .bind(SessionStatus::Terminated)
+        .bind(SessionStatus::Active)
+        .execute(&self.pool)
         .await?;
 
-        Ok(user)
+        Ok(res.rows_affected())
     }
 
-    pub async fn find_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
+    /// Terminate every active session of a user. Returns how many were closed.
+    pub async fn terminate_by_user(&self, user_id: i64) -> sqlx::Result<u64> {
+        let res = sqlx::query(
             r#"
-            SELECT id, username, email, phone_number, password_hash, salt, is_email_verified, is_phone_verified, login_method as "login_method: LoginMethod", created_at, updated_at, deleted_at
-            FROM users 
-            WHERE username = $1 AND deleted_at IS NULL
+            UPDATE sessions
+            SET status = $2, ended_at = now()
+            WHERE user_id = $1 AND status = $3
             "#,
-            username
         )
-        .fetch_optional(&self.pool)
+        .bind(user_id)
+        .bind(SessionStatus::Terminated)
+        .bind(SessionStatus::Active)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// Terminate every active session of a user except `keep`. Returns the closed ones.
+    pub async fn terminate_others(&self, user_id: i64, keep: Uuid) -> sqlx::Result<Vec<Uuid>> {
+        sqlx::query_scalar(
+            r#"
+            UPDATE sessions
+            SET status = $3, ended_at = now()
+            WHERE user_id = $1 AND id <> $2 AND status = $4
+            RETURNING id
+            "#,
+        )
+        .bind(user_id)
+        .bind(keep)
+        .bind(SessionStatus::Terminated)
+        .bind(SessionStatus::Active)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Mark every active session past its `expires_at` as expired. Returns how many were closed.
+    pub async fn expire_stale(&self) -> sqlx::Result<u64> {
+        let res = sqlx::query(
+            r#"
+            UPDATE sessions
+            SET status = $1, ended_at = expires_at
+            WHERE status = $2 AND expires_at <= now()
+            "#,
+        )
+        .bind(SessionStatus::Expired)
+        .bind(SessionStatus::Active)
+        .execute(&self.pool)
         .await?;
 
-        Ok(user)
+        Ok(res.rows_affected())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,172 @@
+use chrono::Utc;
+use deadpool_redis::{redis, Pool};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    features::system::ConfigService,
+    utils::{
+        error::{Error, Result},
+        token_service::TokenClaims,
+    },
+};
+
+/// region Redis prefixes
+/// access/refresh jti -> "1" until the token's own `exp`
+pub const REVOKED_JTI_PREFIX: &str = "auth:revoked:v1:jti:";
+/// session id -> "1" once the session was terminated (mirrors `sessions.status`)
+pub const ENDED_SESSION_PREFIX: &str = "auth:session:v1:ended:";
+/// family -> "1" once the refresh family has been revoked
+pub const REVOKED_FAMILY_PREFIX: &str = "auth:refresh:v1:revoked:";
+/// device id -> unix ms, every token of the device issued at or before it is dead
+pub const DEVICE_WATERMARK_PREFIX: &str = "auth:revoked:v1:device:";
+/// user id -> unix ms, every token of the user issued at or before it is dead
+pub const USER_WATERMARK_PREFIX: &str = "auth:revoked:v1:user:";
+/// endregion Redis prefixes
+// jti, session, family, device watermark, user watermark
+type RevocationFlags = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+    Option<i64>,
+);
+
+/// Redis-backed denylist consulted on every authenticated request.
+///
+/// Every entry carries a TTL so the store never outgrows the set of tokens
+/// that could still be presented. Watermarks are in milliseconds and compare
+/// against the token's `iat_ms`, so a login right after a revocation (e.g.
+/// after a password reset) is not caught by it just for sharing the second.
+#[derive(Clone)]
+pub struct RevocationStore {
+    redis_pool: Pool,
+    config_service: Arc<ConfigService>,
+}
+
+impl RevocationStore {
+    pub fn new(redis_pool: Pool, config_service: Arc<ConfigService>) -> Self {
+        Self {
+            redis_pool,
+            config_service,
+        }
+    }
+
+    /// Deny a single token until its `exp`. Already expired tokens are a no-op.
+    pub async fn revoke_token(&self, jti: &str, exp: i64) -> Result<()> {
+        let ttl_seconds = exp - Utc::now().timestamp();
+        if ttl_seconds <= 0 {
+            return Ok(());
+        }
+        self.set(format!("{}{}", REVOKED_JTI_PREFIX, jti), "1", ttl_seconds)
+            .await
+    }
+
+    /// Deny every access token of a session. Refresh tokens are stopped by the DB row.
+    pub async fn revoke_session(&self, session_id: Uuid) -> Result<()> {
+        let cfg = self.config_service.get().await?;
+        self.set(
+            format!("{}{}", ENDED_SESSION_PREFIX, session_id),
+            "1",
+            cfg.token_validity_seconds as i64,
+        )
+        .await
+    }
+
+    /// Deny every refresh (and access) token of a rotation family.
+    pub async fn revoke_family(&self, family: &str) -> Result<()> {
+        let ttl_seconds = self.longest_token_lifetime().await?;
+        self.set(
+            format!("{}{}", REVOKED_FAMILY_PREFIX, family),
+            "1",
+            ttl_seconds,
+        )
+        .await
+    }
+
+    /// Deny every token issued to the device up to now.
+    pub async fn revoke_device(&self, device_id: i64) -> Result<()> {
+        let ttl_seconds = self.longest_token_lifetime().await?;
+        self.set(
+            format!("{}{}", DEVICE_WATERMARK_PREFIX, device_id),
+            Utc::now().timestamp_millis(),
+            ttl_seconds,
+        )
+        .await
+    }
+
+    /// Deny every token issued to the user up to now (password change, account lock, ...).
+    pub async fn revoke_user(&self, user_id: i64) -> Result<()> {
+        let ttl_seconds = self.longest_token_lifetime().await?;
+        self.set(
+            format!("{}{}", USER_WATERMARK_PREFIX, user_id),
+            Utc::now().timestamp_millis(),
+            ttl_seconds,
+        )
+        .await
+    }
+
+    /// One MGET covering every way `claims` could have been revoked.
+    pub async fn is_revoked(&self, claims: &TokenClaims) -> Result<bool> {
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let (jti, session, family, device, user): RevocationFlags = redis::cmd("MGET")
+            .arg(format!("{}{}", REVOKED_JTI_PREFIX, claims.jti))
+            .arg(format!("{}{}", ENDED_SESSION_PREFIX, claims.sid))
+            .arg(format!("{}{}", REVOKED_FAMILY_PREFIX, claims.fam))
+            .arg(format!("{}{}", DEVICE_WATERMARK_PREFIX, claims.did))
+            .arg(format!("{}{}", USER_WATERMARK_PREFIX, claims.uid))
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        // tokens minted before `iat_ms` existed only know their second; treat
+        // them as issued at its start so they stay revoked
+        let issued_ms = match claims.iat_ms {
+            0 => claims.iat * 1000,
+            ms => ms,
+        };
+        let below_watermark = |mark: Option<i64>| mark.is_some_and(|ms| issued_ms <= ms);
+
+        Ok(jti.is_some()
+            || session.is_some()
+            || family.is_some()
+            || below_watermark(device)
+            || below_watermark(user))
+    }
+
+    /// Only the per-token denylist; for machine client tokens, which have no
+    /// session, family, device or user.
+    pub async fn is_token_revoked(&self, jti: &str) -> Result<bool> {
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        redis::cmd("EXISTS")
+            .arg(format!("{}{}", REVOKED_JTI_PREFIX, jti))
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Watermarks must outlive every token that was issued before them.
+    async fn longest_token_lifetime(&self) -> Result<i64> {
+        let cfg = self.config_service.get().await?;
+        Ok(cfg
+            .token_validity_seconds
+            .max(cfg.refresh_token_validity_seconds) as i64)
+    }
+
+    async fn set<V>(&self, key: String, value: V, ttl_seconds: i64) -> Result<()>
+    where
+        V: redis::ToRedisArgs,
+    {
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let _: () = redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("EX")
+            .arg(ttl_seconds.max(1))
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        Ok(())
+    }
+}
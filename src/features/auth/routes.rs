@@ -0,0 +1,71 @@
+use actix_web::{post, web, HttpRequest, Responder};
+use std::sync::Arc;
+
+use crate::{
+    features::{
+        auth::{types::RefreshReq, AuthService, DpopRequest},
+        users::{COOKIE_ACCESS_TOKEN, COOKIE_REFRESH_TOKEN},
+    },
+    utils::{error::Error, token_service::TokenService},
+};
+
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    request_body = RefreshReq,
+    responses(
+        (status = 200, description = "Rotated access + refresh token pair"),
+        (status = 401, description = "Invalid, expired or reused refresh token"),
+        (status = 403, description = "Refresh tokens are disabled"),
+    )
+)]
+#[post("/auth/refresh")]
+pub async fn refresh(
+    req: HttpRequest,
+    payload: Option<web::Json<RefreshReq>>,
+    auth_service: web::Data<AuthService>,
+) -> actix_web::Result<impl Responder> {
+    // cookie first (browsers), then body (mobile / CLI clients)
+    let refresh_token = req
+        .cookie(COOKIE_REFRESH_TOKEN)
+        .map(|c| c.value().to_string())
+        .or_else(|| payload.and_then(|p| p.into_inner().refresh_token))
+        .ok_or(Error::Unauthorized)?;
+
+    let (claims, tokens) = auth_service
+        .refresh(&refresh_token, &DpopRequest::from_request(&req))
+        .await?;
+
+    Ok(auth_service
+        .tokens_response(claims.uid, claims.did, tokens)
+        .await?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Session terminated and auth cookies cleared"),
+    )
+)]
+#[post("/auth/logout")]
+pub async fn logout(
+    req: HttpRequest,
+    auth_service: web::Data<AuthService>,
+    token_service: web::Data<Arc<TokenService>>,
+) -> actix_web::Result<impl Responder> {
+    // the access token may already be expired, so fall back to the refresh token
+    for name in [COOKIE_ACCESS_TOKEN, COOKIE_REFRESH_TOKEN] {
+        let Some(cookie) = req.cookie(name) else {
+            continue;
+        };
+        if let Ok(claims) = token_service.verify(cookie.value()).await {
+            auth_service.logout(&claims).await?;
+            break;
+        }
+    }
+
+    Ok(auth_service.logout_response())
+}
@@ -0,0 +1,541 @@
+use actix_web::{http::header, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use deadpool_redis::{redis, Pool};
+use sqlx::{types::ipnetwork::IpNetwork, PgPool};
+use std::{net::IpAddr, sync::Arc};
+use uuid::Uuid;
+
+use crate::{
+    features::{
+        api_keys::ApiKeyRepository,
+        audits::{AuditRepository, EventType, LogLevel},
+        auth::{
+            types::{RevocationTarget, TokensResp},
+            DpopRequest, DpopVerifier, RevocationStore, Session, SessionRepository,
+        },
+        devices::{DeviceKeyRepository, KeyOperation},
+        system::ConfigService,
+        users::{host_cookie, COOKIE_ACCESS_TOKEN, COOKIE_REFRESH_TOKEN, COOKIE_USER_ID},
+    },
+    utils::{
+        error::{Error, Result},
+        token_service::{
+            ClientTokenClaims, IssuedTokens, TokenClaims, TokenGrant, TokenKind, TokenService,
+        },
+    },
+};
+
+/// region Redis prefixes
+/// live (not yet rotated) refresh token -> its family
+pub const REFRESH_JTI_PREFIX: &str = "auth:refresh:v1:jti:";
+/// family -> set of every refresh jti ever issued in it
+pub const REFRESH_FAMILY_PREFIX: &str = "auth:refresh:v1:family:";
+/// endregion Redis prefixes
+
+#[derive(Clone)]
+pub struct AuthService {
+    redis_pool: Pool,
+    token_service: Arc<TokenService>,
+    config_service: Arc<ConfigService>,
+    audit_repo: AuditRepository,
+    session_repo: SessionRepository,
+    revocations: RevocationStore,
+    dpop: DpopVerifier,
+    device_key_repo: DeviceKeyRepository,
+    api_key_repo: ApiKeyRepository,
+}
+
+impl AuthService {
+    pub fn new(
+        pool: PgPool,
+        redis_pool: Pool,
+        token_service: Arc<TokenService>,
+        config_service: Arc<ConfigService>,
+    ) -> Self {
+        Self {
+            revocations: RevocationStore::new(redis_pool.clone(), config_service.clone()),
+            dpop: DpopVerifier::new(redis_pool.clone()),
+            device_key_repo: DeviceKeyRepository::new(pool.clone()),
+            api_key_repo: ApiKeyRepository::new(pool.clone()),
+            redis_pool,
+            token_service,
+            config_service,
+            audit_repo: AuditRepository::new(pool.clone()),
+            session_repo: SessionRepository::new(pool),
+        }
+    }
+
+    /// Open a session and mint a fresh token pair after a successful login.
+    /// This also starts a new refresh rotation family.
+    pub async fn issue(
+        &self,
+        user_id: i64,
+        device_id: i64,
+        ip: Option<IpAddr>,
+        grant: &TokenGrant,
+    ) -> Result<IssuedTokens> {
+        let cfg = self.config_service.get().await?;
+        let lifetime_seconds = if cfg.allow_refresh_tokens {
+            cfg.refresh_token_validity_seconds
+        } else {
+            cfg.token_validity_seconds
+        };
+
+        let session = self
+            .session_repo
+            .create(
+                user_id,
+                device_id,
+                ip.map(IpNetwork::from),
+                Utc::now() + Duration::seconds(lifetime_seconds as i64),
+            )
+            .await
+            .map_err(Error::from)?;
+
+        let tokens = self
+            .token_service
+            .mint_tokens(user_id, device_id, session.id, None, grant)
+            .await?;
+        self.track_refresh(&tokens).await?;
+        Ok(tokens)
+    }
+
+    /// Exchange a refresh token for a new pair and retire the old one.
+    ///
+    /// A refresh token can be used exactly once. Presenting it again means it
+    /// leaked, so the whole family is revoked and a security event is recorded.
+    /// DPoP-bound tokens also need a proof from the same device key.
+    pub async fn refresh(
+        &self,
+        refresh_token: &str,
+        dpop: &DpopRequest,
+    ) -> Result<(TokenClaims, IssuedTokens)> {
+        let cfg = self.config_service.get().await?;
+        if !cfg.allow_refresh_tokens {
+            return Err(Error::Forbidden);
+        }
+
+        let claims = self.token_service.verify(refresh_token).await?;
+        if claims.typ != TokenKind::Refresh {
+            return Err(Error::Unauthorized);
+        }
+        self.check_binding(&claims, dpop, None).await?;
+
+        // a logged out / expired session cannot be revived by an old refresh token
+        match self
+            .session_repo
+            .find_by_id(claims.sid)
+            .await
+            .map_err(Error::from)?
+        {
+            Some(session) if session.is_active() => {}
+            _ => return Err(Error::Unauthorized),
+        }
+
+        if self.revocations.is_revoked(&claims).await? {
+            return Err(Error::Unauthorized);
+        }
+
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+
+        // GETDEL makes the rotation atomic: only one caller can ever consume a jti.
+        let live_family: Option<String> = redis::cmd("GETDEL")
+            .arg(format!("{}{}", REFRESH_JTI_PREFIX, claims.jti))
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        match live_family {
+            Some(family) if family == claims.fam => {}
+            _ => {
+                drop(conn);
+                self.on_refresh_reuse(&claims).await?;
+                return Err(Error::Unauthorized);
+            }
+        }
+        drop(conn);
+
+        let tokens = self
+            .token_service
+            .mint_tokens(
+                claims.uid,
+                claims.did,
+                claims.sid,
+                Some(&claims.fam),
+                &TokenGrant {
+                    scope: claims.scope.clone(),
+                    client_id: claims.cid.clone(),
+                    jkt: claims.cnf.as_ref().map(|c| c.jkt.clone()),
+                },
+            )
+            .await?;
+        self.track_refresh(&tokens).await?;
+
+        if let Some(exp) = tokens
+            .refresh_expires_at
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+        {
+            self.session_repo
+                .extend(claims.sid, exp)
+                .await
+                .map_err(Error::from)?;
+        }
+
+        Ok((claims, tokens))
+    }
+
+    /// Check an access token the way every protected handler needs it:
+    /// valid signature + expiry, an access (not refresh) token, a DPoP proof
+    /// if the token is bound to a key, and nothing in the revocation store
+    /// (token, session, family, device, user) denying it.
+    pub async fn authenticate(
+        &self,
+        access_token: &str,
+        dpop: &DpopRequest,
+    ) -> Result<TokenClaims> {
+        let claims = self.token_service.verify(access_token).await?;
+        if claims.typ != TokenKind::Access {
+            return Err(Error::Unauthorized);
+        }
+        self.check_binding(&claims, dpop, Some(access_token))
+            .await?;
+
+        if self.revocations.is_revoked(&claims).await? {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(claims)
+    }
+
+    /// Verify the DPoP proof of a login and remember its key for the device.
+    /// Returns the thumbprint the new tokens must be bound to.
+    pub async fn bind_device_key(
+        &self,
+        user_id: i64,
+        device_id: i64,
+        dpop: &DpopRequest,
+    ) -> Result<String> {
+        let key = self.dpop.verify(dpop, None).await?;
+
+        match self
+            .device_key_repo
+            .find(device_id, &key.public_key, KeyOperation::Auth)
+            .await
+            .map_err(Error::from)?
+        {
+            Some(existing) if existing.revoked_at.is_some() => return Err(Error::Forbidden),
+            Some(_) => {}
+            None => {
+                self.device_key_repo
+                    .create(device_id, user_id, &key.public_key, KeyOperation::Auth)
+                    .await
+                    .map_err(Error::from)?;
+            }
+        }
+
+        Ok(key.jkt)
+    }
+
+    /// Full state check for introspection: like [`Self::authenticate`] but for
+    /// either token kind, and backed by the session row rather than Redis alone.
+    /// `None` means the token is not usable, for whatever reason.
+    pub async fn introspect(&self, token: &str) -> Result<Option<(TokenClaims, Session)>> {
+        let claims = match self.token_service.verify(token).await {
+            Ok(claims) => claims,
+            Err(Error::Unauthorized) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if self.revocations.is_revoked(&claims).await? {
+            return Ok(None);
+        }
+
+        // a rotated refresh token is no longer live even though it has not expired
+        if claims.typ == TokenKind::Refresh {
+            let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+            let live: bool = redis::cmd("EXISTS")
+                .arg(format!("{}{}", REFRESH_JTI_PREFIX, claims.jti))
+                .query_async(&mut *conn)
+                .await
+                .map_err(Error::from)?;
+            if !live {
+                return Ok(None);
+            }
+        }
+
+        match self
+            .session_repo
+            .find_by_id(claims.sid)
+            .await
+            .map_err(Error::from)?
+        {
+            Some(session) if session.is_active() => Ok(Some((claims, session))),
+            _ => Ok(None),
+        }
+    }
+
+    /// [`Self::introspect`] for machine client tokens: valid and not revoked.
+    pub async fn introspect_client(&self, token: &str) -> Result<Option<ClientTokenClaims>> {
+        let claims = match self.token_service.verify_client(token).await {
+            Ok(claims) if claims.typ == TokenKind::Access => claims,
+            Ok(_) | Err(Error::Unauthorized) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if self.revocations.is_token_revoked(&claims.jti).await? {
+            return Ok(None);
+        }
+        Ok(Some(claims))
+    }
+
+    /// End the session behind `claims` and make its refresh family unusable.
+    pub async fn logout(&self, claims: &TokenClaims) -> Result<()> {
+        self.revoke_session(claims.sid).await?;
+        self.revoke_family(&claims.fam).await
+    }
+
+    /// Admin entry point, see [`RevocationTarget`].
+    pub async fn revoke(&self, target: RevocationTarget) -> Result<()> {
+        match target {
+            RevocationTarget::Token { jti, exp } => self.revoke_token(&jti, exp).await,
+            RevocationTarget::Session { session_id } => self.revoke_session(session_id).await,
+            RevocationTarget::Device { device_id } => self.revoke_device(device_id).await,
+            RevocationTarget::User { user_id } => self.revoke_user(user_id).await,
+        }
+    }
+
+    /// Deny one token. Without `exp` it is remembered for the longest token lifetime.
+    pub async fn revoke_token(&self, jti: &str, exp: Option<i64>) -> Result<()> {
+        let exp = match exp {
+            Some(exp) => exp,
+            None => {
+                let cfg = self.config_service.get().await?;
+                let lifetime = cfg
+                    .token_validity_seconds
+                    .max(cfg.refresh_token_validity_seconds);
+                Utc::now().timestamp() + lifetime as i64
+            }
+        };
+        self.revocations.revoke_token(jti, exp).await
+    }
+
+    /// Terminate a session and deny its access tokens right away.
+    pub async fn revoke_session(&self, session_id: Uuid) -> Result<()> {
+        self.session_repo
+            .terminate(session_id)
+            .await
+            .map_err(Error::from)?;
+        self.revocations.revoke_session(session_id).await
+    }
+
+    /// Sign a device out everywhere (lost phone, device removed by the user).
+    pub async fn revoke_device(&self, device_id: i64) -> Result<()> {
+        self.session_repo
+            .terminate_by_device(device_id)
+            .await
+            .map_err(Error::from)?;
+        self.revocations.revoke_device(device_id).await
+    }
+
+    /// Sign a user out of every device (password change, account lock).
+    /// Their API keys are revoked too.
+    pub async fn revoke_user(&self, user_id: i64) -> Result<()> {
+        self.session_repo
+            .terminate_by_user(user_id)
+            .await
+            .map_err(Error::from)?;
+        self.api_key_repo
+            .revoke_all(user_id)
+            .await
+            .map_err(Error::from)?;
+        self.revocations.revoke_user(user_id).await
+    }
+
+    /// Sign a user out of every session but `keep`, the one they are using
+    /// (password change). Their API keys are revoked too.
+    pub async fn revoke_other_sessions(&self, user_id: i64, keep: Uuid) -> Result<()> {
+        let ended = self
+            .session_repo
+            .terminate_others(user_id, keep)
+            .await
+            .map_err(Error::from)?;
+        self.api_key_repo
+            .revoke_all(user_id)
+            .await
+            .map_err(Error::from)?;
+        for session_id in ended {
+            self.revocations.revoke_session(session_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Start a background worker that marks sessions past `expires_at` as expired.
+    pub fn spawn_session_expirer(&self, every: std::time::Duration) {
+        let session_repo = self.session_repo.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(every);
+            loop {
+                ticker.tick().await;
+                match session_repo.expire_stale().await {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("expired {n} stale sessions"),
+                    Err(e) => tracing::error!("session expirer failed: {e:?}"),
+                }
+            }
+        });
+    }
+
+    /// Build the JSON + `__Host-*` cookies response shared by login and refresh.
+    pub async fn tokens_response(
+        &self,
+        user_id: i64,
+        device_id: i64,
+        tokens: IssuedTokens,
+    ) -> Result<HttpResponse> {
+        let cfg = self.config_service.get().await?;
+        let mut resp = HttpResponse::Ok();
+
+        resp.cookie(host_cookie(
+            COOKIE_USER_ID,
+            user_id.to_string(),
+            cfg.refresh_token_validity_seconds as i64,
+            true,
+        ));
+        resp.cookie(host_cookie(
+            COOKIE_ACCESS_TOKEN,
+            tokens.access_token.clone(),
+            cfg.token_validity_seconds as i64,
+            true,
+        ));
+        if let Some(ref rt) = tokens.refresh_token {
+            resp.cookie(host_cookie(
+                COOKIE_REFRESH_TOKEN,
+                rt.clone(),
+                cfg.refresh_token_validity_seconds as i64,
+                true,
+            ));
+        }
+
+        Ok(resp
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .json(TokensResp {
+                user_id,
+                device_id,
+                access_token: tokens.access_token,
+                access_expires_at: tokens.access_expires_at,
+                refresh_token: tokens.refresh_token,
+                refresh_expires_at: tokens.refresh_expires_at,
+            }))
+    }
+
+    /// Expire every `__Host-*` auth cookie on the client.
+    pub fn logout_response(&self) -> HttpResponse {
+        let mut resp = HttpResponse::Ok();
+        for name in [COOKIE_USER_ID, COOKIE_ACCESS_TOKEN, COOKIE_REFRESH_TOKEN] {
+            let mut cookie = host_cookie(name, String::new(), 0, true);
+            cookie.make_removal();
+            resp.cookie(cookie);
+        }
+        resp.finish()
+    }
+
+    /// Tokens with `cnf` are useless without a fresh proof from the bound key.
+    async fn check_binding(
+        &self,
+        claims: &TokenClaims,
+        dpop: &DpopRequest,
+        access_token: Option<&str>,
+    ) -> Result<()> {
+        let Some(cnf) = &claims.cnf else {
+            return Ok(());
+        };
+        let key = self.dpop.verify(dpop, access_token).await?;
+        if key.jkt != cnf.jkt {
+            return Err(Error::Unauthorized);
+        }
+        Ok(())
+    }
+
+    /// Remember a freshly minted refresh token as the live member of its family.
+    async fn track_refresh(&self, tokens: &IssuedTokens) -> Result<()> {
+        let (Some(jti), Some(exp)) = (&tokens.refresh_jti, tokens.refresh_expires_at) else {
+            return Ok(());
+        };
+        let ttl_seconds = (exp - Utc::now().timestamp()).max(1);
+        let family_key = format!("{}{}", REFRESH_FAMILY_PREFIX, tokens.family);
+
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("SET")
+            .arg(format!("{}{}", REFRESH_JTI_PREFIX, jti))
+            .arg(&tokens.family)
+            .arg("EX")
+            .arg(ttl_seconds)
+            .ignore()
+            .cmd("SADD")
+            .arg(&family_key)
+            .arg(jti)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&family_key)
+            .arg(ttl_seconds)
+            .ignore();
+
+        pipe.query_async::<()>(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        Ok(())
+    }
+
+    /// Reuse detected: kill every token of the family and leave an audit trail.
+    async fn on_refresh_reuse(&self, claims: &TokenClaims) -> Result<()> {
+        self.revoke_family(&claims.fam).await?;
+
+        tracing::warn!(
+            user_id = claims.uid,
+            device_id = claims.did,
+            family = %claims.fam,
+            "refresh token reuse detected, family revoked"
+        );
+
+        self.audit_repo
+            .create(
+                claims.uid,
+                EventType::RefreshTokenReuse,
+                LogLevel::Critical,
+                Some(claims.sid),
+            )
+            .await
+            .map_err(Error::from)?;
+
+        Ok(())
+    }
+
+    /// Flag the family as revoked and drop every live refresh token in it.
+    async fn revoke_family(&self, family: &str) -> Result<()> {
+        self.revocations.revoke_family(family).await?;
+
+        let family_key = format!("{}{}", REFRESH_FAMILY_PREFIX, family);
+
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let members: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(&family_key)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().cmd("DEL").arg(&family_key).ignore();
+        for jti in &members {
+            pipe.cmd("DEL")
+                .arg(format!("{}{}", REFRESH_JTI_PREFIX, jti))
+                .ignore();
+        }
+
+        pipe.query_async::<()>(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RefreshReq {
+    /// Only needed by clients that cannot send the `__Host-refresh_token` cookie.
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokensResp {
+    pub user_id: i64,
+    pub device_id: i64,
+    pub access_token: String,
+    pub access_expires_at: i64,
+    pub refresh_token: Option<String>,
+    pub refresh_expires_at: Option<i64>,
+}
+
+/// What to revoke. Every variant also stops the matching refresh tokens.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum RevocationTarget {
+    /// A single token by `jti`. `exp` bounds how long it is remembered.
+    Token { jti: String, exp: Option<i64> },
+    Session {
+        #[schema(value_type = String)]
+        session_id: Uuid,
+    },
+    Device { device_id: i64 },
+    User { user_id: i64 },
+}
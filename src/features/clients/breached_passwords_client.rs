@@ -0,0 +1,161 @@
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+
+use crate::utils::error::{Error, Result};
+
+/// Hex chars of a SHA-1 digest, the sort key of every corpus line.
+const SHA1_HEX_LEN: usize = 40;
+
+/// Offline breached-password lookup in a file of uppercase SHA-1 hashes, one
+/// per line and sorted, e.g. the "ordered by hash" Pwned Passwords download
+/// (`HASH:COUNT` lines). The file is binary searched in place, never loaded.
+#[derive(Clone)]
+pub struct BreachedPasswordsClient {
+    path: PathBuf,
+}
+
+impl BreachedPasswordsClient {
+    /// `None` without `BREACHED_PASSWORDS_PATH`: the check is skipped.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(path) = std::env::var("BREACHED_PASSWORDS_PATH") else {
+            return Ok(None);
+        };
+        let path = PathBuf::from(path);
+        File::open(&path).map_err(|e| {
+            Error::Unexpected(format!("failed to open breached passwords file: {e}"))
+        })?;
+        Ok(Some(Self { path }))
+    }
+
+    pub async fn contains(&self, password: &str) -> Result<bool> {
+        let target = hex::encode_upper(digest(&SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes()));
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || search(&path, &target))
+            .await
+            .map_err(|e| Error::Unexpected(format!("breached password lookup: {e}")))?
+            .map_err(|e| Error::Unexpected(format!("breached password lookup: {e}")))
+    }
+}
+
+/// Binary search over byte offsets: probe the first line starting at or
+/// after the midpoint and narrow `[lo, hi)` to where the target line must start.
+fn search(path: &PathBuf, target: &str) -> std::io::Result<bool> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let (mut lo, mut hi) = (0u64, reader.get_ref().metadata()?.len());
+    let mut line = String::new();
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        reader.seek(SeekFrom::Start(mid))?;
+        let mut start = mid;
+        if mid > 0 {
+            // finish the line `mid` landed in, unless it starts right at `mid`
+            reader.seek(SeekFrom::Start(mid - 1))?;
+            line.clear();
+            start = mid - 1 + reader.read_line(&mut line)? as u64;
+        }
+        if start >= hi {
+            hi = mid;
+            continue;
+        }
+
+        line.clear();
+        let read = reader.read_line(&mut line)? as u64;
+        if read == 0 {
+            hi = mid;
+            continue;
+        }
+        let key = line.get(..SHA1_HEX_LEN).unwrap_or(line.trim_end());
+        match key.to_ascii_uppercase().as_str().cmp(target) {
+            Ordering::Equal => return Ok(true),
+            Ordering::Less => lo = start + read,
+            Ordering::Greater => hi = mid,
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha1_hex(password: &str) -> String {
+        hex::encode_upper(digest(&SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes()))
+    }
+
+    /// Corpus file in the Pwned Passwords layout, removed again on drop.
+    struct Corpus(PathBuf);
+
+    impl Corpus {
+        fn new(name: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "forest_gate_breached_{}_{name}.txt",
+                std::process::id()
+            ));
+            std::fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for Corpus {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn sorted_hashes(count: usize) -> Vec<String> {
+        let mut hashes: Vec<String> = (0..count).map(|i| sha1_hex(&format!("pw{i}"))).collect();
+        hashes.sort();
+        hashes
+    }
+
+    #[test]
+    fn finds_every_line_and_nothing_else() {
+        let hashes = sorted_hashes(200);
+        let lines: Vec<String> = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, h)| format!("{h}:{}", i * 37 + 1))
+            .collect();
+        let corpus = Corpus::new("counts", &(lines.join("\n") + "\n"));
+
+        for hash in &hashes {
+            assert!(search(&corpus.0, hash).unwrap(), "{hash} not found");
+        }
+        for i in 200..400 {
+            let absent = sha1_hex(&format!("pw{i}"));
+            assert!(!search(&corpus.0, &absent).unwrap(), "{absent} found");
+        }
+        assert!(!search(&corpus.0, &"0".repeat(SHA1_HEX_LEN)).unwrap());
+        assert!(!search(&corpus.0, &"F".repeat(SHA1_HEX_LEN)).unwrap());
+    }
+
+    #[test]
+    fn tolerates_bare_hashes_crlf_lowercase_and_no_trailing_newline() {
+        let hashes = sorted_hashes(50);
+        let lines: Vec<String> = hashes.iter().map(|h| h.to_ascii_lowercase()).collect();
+        let corpus = Corpus::new("bare", &lines.join("\r\n"));
+
+        for hash in &hashes {
+            assert!(search(&corpus.0, hash).unwrap(), "{hash} not found");
+        }
+        assert!(!search(&corpus.0, &sha1_hex("not in there")).unwrap());
+    }
+
+    #[test]
+    fn handles_tiny_files() {
+        let only = sha1_hex("password");
+        let single = Corpus::new("single", &format!("{only}:3861493\n"));
+        assert!(search(&single.0, &only).unwrap());
+        assert!(!search(&single.0, &sha1_hex("123456")).unwrap());
+
+        let empty = Corpus::new("empty", "");
+        assert!(!search(&empty.0, &only).unwrap());
+    }
+}
@@ -14,10 +14,11 @@ pub struct EmailClient {
 impl EmailClient {
     pub fn from_env() -> Result<Self> {
         // you can use dotenvy::dotenv().ok(); if needed
-        let api_key = std::env::var("SENDGRID_API_KEY").map_err(|_| missing_env("SENDGRID_API_KEY"))?;
+        let api_key =
+            std::env::var("SENDGRID_API_KEY").map_err(|_| missing_env("SENDGRID_API_KEY"))?;
         let from_email = std::env::var("FROM_EMAIL").map_err(|_| missing_env("FROM_EMAIL"))?;
-        let from_name  = std::env::var("FROM_NAME").map_err(|_| missing_env("FROM_NAME"))?;
-        let reply_to   = std::env::var("REPLY_TO_EMAIL").ok(); // optional
+        let from_name = std::env::var("FROM_NAME").map_err(|_| missing_env("FROM_NAME"))?;
+        let reply_to = std::env::var("REPLY_TO_EMAIL").ok(); // optional
 
         Ok(Self {
             http: reqwest::Client::new(),
@@ -38,26 +39,46 @@ impl EmailClient {
         let url = "https://api.sendgrid.com/v3/mail/send";
 
         let mut content = Vec::new();
-        if let Some(t) = text { content.push(SgContent { r#type: "text/plain".into(), value: t.into() }); }
-        if let Some(h) = html { content.push(SgContent { r#type: "text/html".into(),  value: h.into() }); }
+        if let Some(t) = text {
+            content.push(SgContent {
+                r#type: "text/plain".into(),
+                value: t.into(),
+            });
+        }
+        if let Some(h) = html {
+            content.push(SgContent {
+                r#type: "text/html".into(),
+                value: h.into(),
+            });
+        }
         if content.is_empty() {
-            content.push(SgContent { r#type: "text/plain".into(), value: String::new() });
+            content.push(SgContent {
+                r#type: "text/plain".into(),
+                value: String::new(),
+            });
         }
 
         let body = SgMail {
             personalizations: vec![SgPersonalization {
-                to: vec![SgEmail { email: to_email.into(), name: None }],
+                to: vec![SgEmail {
+                    email: to_email.into(),
+                    name: None,
+                }],
                 subject: Some(subject.into()),
             }],
             from: SgEmail {
                 email: self.from_email.clone(),
                 name: Some(self.from_name.clone()),
             },
-            reply_to: self.reply_to.as_ref().map(|e| SgEmail { email: e.clone(), name: None }),
+            reply_to: self.reply_to.as_ref().map(|e| SgEmail {
+                email: e.clone(),
+                name: None,
+            }),
             content,
         };
 
-        let resp = self.http
+        let resp = self
+            .http
             .post(url)
             .bearer_auth(&self.api_key)
             .json(&body)
@@ -71,7 +92,9 @@ impl EmailClient {
         } else {
             let code = resp.status().as_u16();
             let text = resp.text().await.unwrap_or_default();
-            Err(Error::Unexpected(format!("sendgrid failed: status={code} body={text}")))
+            Err(Error::Unexpected(format!(
+                "sendgrid failed: status={code} body={text}"
+            )))
         }
     }
 }
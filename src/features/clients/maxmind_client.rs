@@ -3,7 +3,7 @@
 use crate::utils::error::{Error, Result};
 use maxminddb::{geoip2, Reader};
 use std::{net::IpAddr, path::Path, sync::Arc};
-use tracing::{info, error}; // <-- make sure this is imported
+use tracing::{error, info}; // <-- make sure this is imported
 
 // ---------- add this type ----------
 #[derive(Debug)]
@@ -52,6 +52,17 @@ impl MaxMindClient {
         self.lookup_all(ip)
     }
 
+    /// ISO 3166 alpha-2 code of the country `ip` is in; `None` for private or unknown addresses.
+    pub fn country_code(&self, ip: IpAddr) -> Option<String> {
+        self.country_reader
+            .lookup::<geoip2::Country>(ip)
+            .ok()
+            .flatten()
+            .and_then(|c| c.country)
+            .and_then(|c| c.iso_code)
+            .map(str::to_string)
+    }
+
     pub fn lookup_all<'a>(&'a self, ip: IpAddr) -> Result<GeoIpInfo<'a>> {
         let asn = match (&*self.asn_reader).lookup::<geoip2::Asn>(ip) {
             Ok(v) => {
@@ -86,7 +97,10 @@ impl MaxMindClient {
             }
         };
 
-        info!("GeoIP lookup complete: {:?}, {:?}, {:?}", asn, city, country);
+        info!(
+            "GeoIP lookup complete: {:?}, {:?}, {:?}",
+            asn, city, country
+        );
         Ok(GeoIpInfo { asn, city, country })
     }
 }
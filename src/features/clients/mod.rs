@@ -1,7 +1,13 @@
+mod breached_passwords_client;
 mod email_client;
 mod maxmind_client;
+mod oidc_client;
 mod openrouter_client;
+mod sms_client;
 
+pub use breached_passwords_client::*;
 pub use email_client::*;
 pub use maxmind_client::*;
-pub use openrouter_client::*;
\ No newline at end of file
+pub use oidc_client::*;
+pub use openrouter_client::*;
+pub use sms_client::*;
@@ -0,0 +1,308 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{decode, decode_header, jwk::Jwk, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::Value;
+use url::Url;
+
+use crate::utils::error::{Error, Result};
+
+/// Unknown `kid`s refetch the JWKS at most this often.
+const JWKS_REFRESH: Duration = Duration::from_secs(60);
+
+/// Signature algorithms accepted on ID tokens; never `none` or a shared secret.
+const ID_TOKEN_ALGORITHMS: [Algorithm; 8] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+];
+
+/// One upstream OpenID Connect provider (Google, Azure AD, Keycloak, ...).
+/// Endpoints left out are discovered from
+/// `{issuer}/.well-known/openid-configuration`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcProviderConfig {
+    /// lowercase; names the routes (`/auth/federated/{name}/...`) and the
+    /// linked identities
+    pub name: String,
+    pub display_name: Option<String>,
+    /// exactly as the provider puts it in `iss`
+    pub issuer: String,
+    pub client_id: String,
+    /// unset for public clients, which rely on PKCE alone
+    pub client_secret: Option<String>,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    pub authorization_endpoint: Option<String>,
+    pub token_endpoint: Option<String>,
+    pub jwks_uri: Option<String>,
+    /// create a local account on the first sign-in of an unknown email
+    #[serde(default = "default_true")]
+    pub create_accounts: bool,
+    /// link to the local account with the same email when the provider says
+    /// the email is verified; only for providers that own their users' emails
+    #[serde(default)]
+    pub link_by_email: bool,
+}
+
+fn default_scopes() -> Vec<String> {
+    vec!["openid".into(), "email".into(), "profile".into()]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Signing keys of the provider and when they were fetched.
+type CachedJwks = Option<(Vec<Jwk>, Instant)>;
+
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// Verified claims of an upstream ID token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    /// a bool, or the string "true" with some providers
+    email_verified: Option<Value>,
+    pub preferred_username: Option<String>,
+}
+
+impl OidcClaims {
+    pub fn email_verified(&self) -> bool {
+        match &self.email_verified {
+            Some(Value::Bool(b)) => *b,
+            Some(Value::String(s)) => s == "true",
+            _ => false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResp {
+    id_token: String,
+}
+
+/// Relying-party side of the authorization code flow (with PKCE) against
+/// one provider. Metadata and signing keys are fetched lazily and cached.
+#[derive(Clone)]
+pub struct OidcClient {
+    config: OidcProviderConfig,
+    http: reqwest::Client,
+    metadata: Arc<RwLock<Option<ProviderMetadata>>>,
+    jwks: Arc<RwLock<CachedJwks>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcProviderConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("reqwest client"),
+            metadata: Arc::new(RwLock::new(None)),
+            jwks: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub fn config(&self) -> &OidcProviderConfig {
+        &self.config
+    }
+
+    /// Where to send the browser; `code_challenge` is the S256 PKCE challenge.
+    pub async fn authorization_url(
+        &self,
+        redirect_uri: &str,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<String> {
+        let metadata = self.metadata().await?;
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| Error::Unexpected(format!("authorization endpoint: {e}")))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", &self.config.scopes.join(" "))
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    /// Redeems `code` and returns the ID token's claims once its signature,
+    /// issuer, audience, expiry and `nonce` check out.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<OidcClaims> {
+        let metadata = self.metadata().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let resp = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            tracing::warn!(provider = %self.config.name, %status, body, "code exchange failed");
+            return Err(Error::Unauthorized);
+        }
+        let token: TokenResp = resp.json().await?;
+
+        let claims = self.verify_id_token(&token.id_token).await?;
+        if claims.nonce.as_deref() != Some(nonce) {
+            tracing::warn!(provider = %self.config.name, "id token nonce mismatch");
+            return Err(Error::Unauthorized);
+        }
+        Ok(claims)
+    }
+
+    async fn verify_id_token(&self, id_token: &str) -> Result<OidcClaims> {
+        let header = decode_header(id_token).map_err(|_| Error::Unauthorized)?;
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            tracing::warn!(provider = %self.config.name, alg = ?header.alg, "id token algorithm refused");
+            return Err(Error::Unauthorized);
+        }
+        let jwk = self.signing_key(header.kid.as_deref()).await?;
+        let key = DecodingKey::from_jwk(&jwk).map_err(|_| Error::Unauthorized)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        validation.leeway = 60;
+
+        decode::<OidcClaims>(id_token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| {
+                tracing::warn!(provider = %self.config.name, "invalid id token: {e}");
+                Error::Unauthorized
+            })
+    }
+
+    /// The key named by `kid` (the only key when there is no `kid`), with one
+    /// refetch for keys the provider rotated in since the last fetch.
+    async fn signing_key(&self, kid: Option<&str>) -> Result<Jwk> {
+        let find = |keys: &[Jwk]| match kid {
+            Some(kid) => keys
+                .iter()
+                .find(|k| k.common.key_id.as_deref() == Some(kid))
+                .cloned(),
+            None if keys.len() == 1 => keys.first().cloned(),
+            None => None,
+        };
+
+        let cached = self.jwks.read().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Some((keys, fetched_at)) = &cached {
+            if let Some(key) = find(keys) {
+                return Ok(key);
+            }
+            if fetched_at.elapsed() < JWKS_REFRESH {
+                return Err(Error::Unauthorized);
+            }
+        }
+
+        let metadata = self.metadata().await?;
+        let set: Value = self.get_json(&metadata.jwks_uri).await?;
+        // keys this crate can't parse (other curves, encryption keys) are skipped
+        let keys: Vec<Jwk> = set["keys"]
+            .as_array()
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|k| serde_json::from_value(k.clone()).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let key = find(&keys);
+        *self.jwks.write().unwrap_or_else(|e| e.into_inner()) = Some((keys, Instant::now()));
+        key.ok_or(Error::Unauthorized)
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata> {
+        if let Some(metadata) = self
+            .metadata
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+        {
+            return Ok(metadata);
+        }
+
+        let c = &self.config;
+        let metadata = match (&c.authorization_endpoint, &c.token_endpoint, &c.jwks_uri) {
+            (Some(authorization_endpoint), Some(token_endpoint), Some(jwks_uri)) => {
+                ProviderMetadata {
+                    authorization_endpoint: authorization_endpoint.clone(),
+                    token_endpoint: token_endpoint.clone(),
+                    jwks_uri: jwks_uri.clone(),
+                }
+            }
+            _ => {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    c.issuer.trim_end_matches('/')
+                );
+                let discovered: ProviderMetadata = self.get_json(&url).await?;
+                ProviderMetadata {
+                    authorization_endpoint: c
+                        .authorization_endpoint
+                        .clone()
+                        .unwrap_or(discovered.authorization_endpoint),
+                    token_endpoint: c
+                        .token_endpoint
+                        .clone()
+                        .unwrap_or(discovered.token_endpoint),
+                    jwks_uri: c.jwks_uri.clone().unwrap_or(discovered.jwks_uri),
+                }
+            }
+        };
+
+        *self.metadata.write().unwrap_or_else(|e| e.into_inner()) = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let resp = self.http.get(url).send().await?;
+        if !resp.status().is_success() {
+            return Err(Error::Unexpected(format!(
+                "{} answered {} for {url}",
+                self.config.name,
+                resp.status()
+            )));
+        }
+        Ok(resp.json().await?)
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use crate::utils::error::{Error, Result};
+
+/// Anything that can deliver a text message to an E.164 number.
+pub trait SmsProvider: Send + Sync {
+    fn send<'a>(&'a self, to: &'a str, body: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Picks the provider from `SMS_PROVIDER` (default `log`).
+pub fn sms_provider_from_env() -> Result<Arc<dyn SmsProvider>> {
+    let provider = std::env::var("SMS_PROVIDER").unwrap_or_else(|_| "log".into());
+    match provider.as_str() {
+        "log" => Ok(Arc::new(LogSmsProvider::from_env())),
+        other => Err(Error::Validation(format!("unknown SMS_PROVIDER: {other}"))),
+    }
+}
+
+/// Local development / tests: messages go to the log and, when `SMS_LOG_PATH`
+/// is set, are appended to that file so a test can read the code back.
+#[derive(Clone)]
+pub struct LogSmsProvider {
+    path: Option<String>,
+}
+
+impl LogSmsProvider {
+    pub fn from_env() -> Self {
+        Self {
+            path: std::env::var("SMS_LOG_PATH").ok(),
+        }
+    }
+}
+
+impl SmsProvider for LogSmsProvider {
+    fn send<'a>(&'a self, to: &'a str, body: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            tracing::info!(to, body, "sms (log provider)");
+
+            let Some(path) = &self.path else {
+                return Ok(());
+            };
+            let line = format!("{}\t{}\t{}\n", chrono::Utc::now().to_rfc3339(), to, body);
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await
+                .map_err(|e| Error::Unexpected(format!("open {path} failed: {e}")))?;
+            file.write_all(line.as_bytes())
+                .await
+                .map_err(|e| Error::Unexpected(format!("write {path} failed: {e}")))?;
+            Ok(())
+        })
+    }
+}
@@ -2,6 +2,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use sqlx::{prelude::Type, FromRow};
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize)]
 #[sqlx(type_name = "device_type_enum", rename_all = "lowercase")]
@@ -44,3 +45,22 @@ pub struct Device {
     pub created_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[sqlx(type_name = "key_operation_enum", rename_all = "lowercase")]
+pub enum KeyOperation {
+    Auth,
+    Decryption,
+    Encryption,
+}
+
+/// Public key held by a device. `public_key` is the uncompressed SEC1 point.
+/// Passkeys (WebAuthn) also carry a sign counter.
+#[derive(Debug, FromRow)]
+pub struct DeviceKey {
+    pub id: Uuid,
+    pub user_id: Option<i64>,
+    pub public_key: Vec<u8>,
+    pub sign_count: Option<i64>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
@@ -1,6 +1,7 @@
 use sqlx::PgPool;
+use uuid::Uuid;
 
-use super::{types::CreateDeviceDto, Device, DeviceStatus};
+use super::{types::CreateDeviceDto, Device, DeviceKey, DeviceStatus, KeyOperation};
 
 #[derive(Clone)]
 pub struct DeviceRepository {
@@ -100,3 +101,168 @@ impl DeviceRepository {
         Ok(device)
     }
 }
+
+const KEY_COLUMNS: &str = "id, user_id, public_key, sign_count, revoked_at";
+
+#[derive(Clone)]
+pub struct DeviceKeyRepository {
+    pool: PgPool,
+}
+
+impl DeviceKeyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find(
+        &self,
+        device_id: i64,
+        public_key: &[u8],
+        operation: KeyOperation,
+    ) -> Result<Option<DeviceKey>, sqlx::Error> {
+        sqlx::query_as::<_, DeviceKey>(&format!(
+            r#"
+            SELECT {KEY_COLUMNS} FROM device_keys
+            WHERE device_id = $1 AND public_key = $2 AND operation_usage = $3
+              AND deleted_at IS NULL
+            "#
+        ))
+        .bind(device_id)
+        .bind(public_key)
+        .bind(operation)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn create(
+        &self,
+        device_id: i64,
+        user_id: i64,
+        public_key: &[u8],
+        operation: KeyOperation,
+    ) -> Result<DeviceKey, sqlx::Error> {
+        sqlx::query_as::<_, DeviceKey>(&format!(
+            r#"
+            INSERT INTO device_keys (device_id, user_id, public_key, operation_usage)
+            VALUES ($1, $2, $3, $4)
+            RETURNING {KEY_COLUMNS}
+            "#
+        ))
+        .bind(device_id)
+        .bind(user_id)
+        .bind(public_key)
+        .bind(operation)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Passkey by WebAuthn credential id, revoked ones included so callers can tell.
+    pub async fn find_by_credential_id(
+        &self,
+        credential_id: &[u8],
+    ) -> Result<Option<DeviceKey>, sqlx::Error> {
+        sqlx::query_as::<_, DeviceKey>(&format!(
+            r#"
+            SELECT {KEY_COLUMNS} FROM device_keys
+            WHERE credential_id = $1 AND deleted_at IS NULL
+            "#
+        ))
+        .bind(credential_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Credential ids of the user's live passkeys (`excludeCredentials`).
+    pub async fn credential_ids_for_user(&self, user_id: i64) -> Result<Vec<Vec<u8>>, sqlx::Error> {
+        sqlx::query_scalar::<_, Vec<u8>>(
+            r#"
+            SELECT credential_id
+            FROM device_keys
+            WHERE user_id = $1 AND credential_id IS NOT NULL
+              AND revoked_at IS NULL AND deleted_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Store a passkey and make sure the device is linked to the user.
+    pub async fn create_passkey(
+        &self,
+        device_id: i64,
+        user_id: i64,
+        public_key: &[u8],
+        credential_id: &[u8],
+        sign_count: i64,
+    ) -> Result<DeviceKey, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_devices (user_id, device_id)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, device_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(device_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let key = sqlx::query_as::<_, DeviceKey>(&format!(
+            r#"
+            INSERT INTO device_keys
+              (device_id, user_id, public_key, operation_usage, credential_id, sign_count)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING {KEY_COLUMNS}
+            "#
+        ))
+        .bind(device_id)
+        .bind(user_id)
+        .bind(public_key)
+        .bind(KeyOperation::Auth)
+        .bind(credential_id)
+        .bind(sign_count)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(key)
+    }
+
+    /// Move the counter forward. `false` if another login got there first with
+    /// an equal or higher value.
+    pub async fn advance_sign_count(&self, id: Uuid, sign_count: i64) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query(
+            r#"
+            UPDATE device_keys
+            SET sign_count = $2
+            WHERE id = $1 AND (sign_count IS NULL OR sign_count < $2)
+            "#,
+        )
+        .bind(id)
+        .bind(sign_count)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Stop a passkey from signing in; its row stays for the audit trail.
+    pub async fn revoke_passkey(&self, user_id: i64, credential_id: &[u8]) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query(
+            r#"
+            UPDATE device_keys
+            SET revoked_at = now()
+            WHERE user_id = $1 AND credential_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(credential_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,6 @@
+mod routes;
+mod service;
+pub mod types;
+
+pub use routes::*;
+pub use service::*;
@@ -0,0 +1,155 @@
+use actix_web::{
+    cookie::{Cookie, SameSite},
+    get,
+    http::header,
+    web, FromRequest, HttpRequest, HttpResponse, Responder,
+};
+use time::Duration;
+
+use crate::{
+    features::{
+        federation::{
+            types::{CallbackQuery, ProviderResp, StartQuery},
+            FederatedOutcome, FederationService, COOKIE_FEDERATED_LOGIN,
+            FEDERATED_LOGIN_TTL_SECONDS,
+        },
+        identities::types::IdentityResp,
+        users::{UserService, VerifiedLogin},
+    },
+    infrastructure::middlewares::auth::AuthenticatedUser,
+    utils::error::Error,
+};
+
+#[utoipa::path(
+    get,
+    path = "/auth/federated/providers",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Identity providers users can sign in with", body = [ProviderResp]),
+    )
+)]
+#[get("/auth/federated/providers")]
+pub async fn federated_providers(
+    federation_service: web::Data<FederationService>,
+) -> actix_web::Result<impl Responder> {
+    Ok(HttpResponse::Ok().json(federation_service.providers()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/federated/{provider}/start",
+    tag = "auth",
+    params(
+        ("provider" = String, Path, description = "Provider name"),
+        StartQuery,
+    ),
+    responses(
+        (status = 302, description = "To the provider's login page; the cookie carries state, nonce and PKCE verifier"),
+        (status = 400, description = "return_to leaves the site"),
+        (status = 401, description = "link=true without a valid access token"),
+        (status = 404, description = "No such provider"),
+    )
+)]
+#[get("/auth/federated/{provider}/start")]
+pub async fn federated_start(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<StartQuery>,
+    federation_service: web::Data<FederationService>,
+) -> actix_web::Result<impl Responder> {
+    let link_user_id = match query.link {
+        true => Some(AuthenticatedUser::extract(&req).await?.uid),
+        false => None,
+    };
+
+    let (url, cookie_value) = federation_service
+        .start(&path, query.return_to.as_deref(), link_user_id)
+        .await?;
+    // Lax: the provider's redirect back is a cross-site top-level navigation
+    let cookie = Cookie::build(COOKIE_FEDERATED_LOGIN, cookie_value)
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(FEDERATED_LOGIN_TTL_SECONDS))
+        .path("/") // required for __Host-*
+        .finish();
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .cookie(cookie)
+        .finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/federated/{provider}/callback",
+    tag = "auth",
+    params(
+        ("provider" = String, Path, description = "Provider name"),
+        CallbackQuery,
+    ),
+    responses(
+        (status = 200, description = "Tokens issued (or the identity linked) when the flow had no return_to", body = IdentityResp),
+        (status = 303, description = "Signed in (or linked), on to return_to; with 2FA on, to return_to#challenge_token=..."),
+        (status = 400, description = "Missing device cookie, or no verified email from the provider"),
+        (status = 401, description = "Missing or stale cookie, state mismatch, or a rejected ID token"),
+        (status = 403, description = "The provider may not create accounts"),
+        (status = 409, description = "A local account has the email, or the identity belongs to another user"),
+    )
+)]
+#[get("/auth/federated/{provider}/callback")]
+pub async fn federated_callback(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<CallbackQuery>,
+    federation_service: web::Data<FederationService>,
+    user_service: web::Data<UserService>,
+) -> actix_web::Result<impl Responder> {
+    let cookie = req
+        .cookie(COOKIE_FEDERATED_LOGIN)
+        .map(|c| c.value().to_string());
+    let outcome = federation_service
+        .callback(&path, cookie.as_deref(), &query)
+        .await?;
+
+    let (mut resp, return_to) = match outcome {
+        // the provider's sign-in is one factor; with 2FA on, the page at
+        // `return_to` gets the challenge in the fragment
+        FederatedOutcome::Login { user_id, return_to } => {
+            match user_service.mfa_challenge(user_id).await? {
+                Some(challenge) => (
+                    HttpResponse::Ok()
+                        .insert_header((header::CACHE_CONTROL, "no-store"))
+                        .json(&challenge),
+                    return_to
+                        .map(|to| format!("{to}#challenge_token={}", challenge.challenge_token)),
+                ),
+                None => (
+                    user_service
+                        .finish_verified_login(&req, VerifiedLogin::User(user_id))
+                        .await?,
+                    return_to,
+                ),
+            }
+        }
+        FederatedOutcome::Linked {
+            identity,
+            return_to,
+        } => (HttpResponse::Ok().json(identity), return_to),
+    };
+
+    if let Some(return_to) = return_to.filter(|_| resp.status().is_success()) {
+        let mut redirect = HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, return_to))
+            .finish();
+        for c in resp.cookies() {
+            redirect
+                .add_cookie(&c)
+                .map_err(|e| Error::Unexpected(format!("cookie: {e}")))?;
+        }
+        resp = redirect;
+    }
+    resp.add_removal_cookie(&Cookie::build(COOKIE_FEDERATED_LOGIN, "").path("/").finish())
+        .map_err(|e| Error::Unexpected(format!("removal cookie: {e}")))?;
+    Ok(resp)
+}
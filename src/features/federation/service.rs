@@ -0,0 +1,339 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use url::Url;
+
+use crate::{
+    features::{
+        clients::{OidcClaims, OidcClient, OidcProviderConfig},
+        federation::types::{CallbackQuery, PendingFederatedLogin, ProviderResp},
+        identities::{types::IdentityResp, IdentityKind, IdentityRepository, UserIdentity},
+        users::{types::CreateUserDto, PasswordHashing, UserRepository},
+    },
+    utils::{
+        crypto::ClientHMAC,
+        error::{Error, Result},
+    },
+};
+
+pub const COOKIE_FEDERATED_LOGIN: &str = "__Host-federated_login";
+/// How long the provider's login page may take.
+pub const FEDERATED_LOGIN_TTL_SECONDS: i64 = 600;
+
+/// Where a finished callback leads.
+pub enum FederatedOutcome {
+    /// sign `user_id` in
+    Login {
+        user_id: i64,
+        return_to: Option<String>,
+    },
+    /// the signed-in user who started the flow now has the identity
+    Linked {
+        identity: IdentityResp,
+        return_to: Option<String>,
+    },
+}
+
+/// Sign-in through upstream OpenID Connect providers: the browser is sent to
+/// the provider with state, nonce and a PKCE verifier kept in a signed
+/// cookie, and the verified ID token is mapped to a local account.
+#[derive(Clone)]
+pub struct FederationService {
+    providers: Arc<Vec<OidcClient>>,
+    identity_repo: IdentityRepository,
+    user_repo: UserRepository,
+    hmac_client: ClientHMAC,
+    password_hashing: PasswordHashing,
+    public_url: String,
+    return_origins: Vec<String>,
+}
+
+impl FederationService {
+    /// `public_url` is this server's base URL (`AUTH_ISSUER`); `return_to`
+    /// may point at its origin or at any of `app_urls`' origins.
+    pub fn new(
+        pool: PgPool,
+        hmac_client: ClientHMAC,
+        password_hashing: PasswordHashing,
+        providers: Vec<OidcProviderConfig>,
+        public_url: &str,
+        app_urls: &[&str],
+    ) -> Self {
+        let return_origins = std::iter::once(public_url)
+            .chain(app_urls.iter().copied())
+            .filter_map(|u| Url::parse(u).ok())
+            .map(|u| u.origin().ascii_serialization())
+            .collect();
+        Self {
+            providers: Arc::new(providers.into_iter().map(OidcClient::new).collect()),
+            identity_repo: IdentityRepository::new(pool.clone()),
+            user_repo: UserRepository::new(pool),
+            hmac_client,
+            password_hashing,
+            public_url: public_url.trim_end_matches('/').to_string(),
+            return_origins,
+        }
+    }
+
+    pub fn providers(&self) -> Vec<ProviderResp> {
+        self.providers
+            .iter()
+            .map(|p| ProviderResp {
+                name: p.config().name.clone(),
+                display_name: p
+                    .config()
+                    .display_name
+                    .clone()
+                    .unwrap_or_else(|| p.config().name.clone()),
+            })
+            .collect()
+    }
+
+    /// Step 1: the provider's authorization URL and the signed cookie value
+    /// that has to come back with the callback. `link_user_id` links the
+    /// identity to that (signed-in) user instead of signing in.
+    pub async fn start(
+        &self,
+        provider: &str,
+        return_to: Option<&str>,
+        link_user_id: Option<i64>,
+    ) -> Result<(String, String)> {
+        let client = self.provider(provider)?;
+        let return_to = return_to.map(|r| self.checked_return_to(r)).transpose()?;
+
+        let pending = PendingFederatedLogin {
+            provider: provider.to_string(),
+            state: random_token(),
+            nonce: random_token(),
+            code_verifier: random_token(),
+            return_to,
+            link_user_id,
+            expires_at: Utc::now().timestamp() + FEDERATED_LOGIN_TTL_SECONDS,
+        };
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(pending.code_verifier.as_bytes()));
+        let url = client
+            .authorization_url(
+                &self.redirect_uri(provider),
+                &pending.state,
+                &pending.nonce,
+                &challenge,
+            )
+            .await?;
+
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&pending)?);
+        Ok((url, self.hmac_client.encode_cookie_value(&payload)))
+    }
+
+    /// Step 2: checks the callback against the cookie, redeems the code and
+    /// resolves the local account.
+    pub async fn callback(
+        &self,
+        provider: &str,
+        cookie: Option<&str>,
+        query: &CallbackQuery,
+    ) -> Result<FederatedOutcome> {
+        let client = self.provider(provider)?;
+        let pending = cookie
+            .and_then(|c| self.hmac_client.decode_cookie_value(c))
+            .and_then(|p| URL_SAFE_NO_PAD.decode(p).ok())
+            .and_then(|p| serde_json::from_slice::<PendingFederatedLogin>(&p).ok())
+            .filter(|p| p.provider == provider && p.expires_at > Utc::now().timestamp())
+            .ok_or(Error::Unauthorized)?;
+        if query.state.as_deref() != Some(pending.state.as_str()) {
+            tracing::warn!(provider, "federated callback state mismatch");
+            return Err(Error::Unauthorized);
+        }
+        if let Some(error) = &query.error {
+            tracing::info!(provider, error, description = ?query.error_description, "provider refused the login");
+            return Err(Error::Unauthorized);
+        }
+        let code = query.code.as_deref().ok_or(Error::Unauthorized)?;
+
+        let claims = client
+            .exchange_code(
+                code,
+                &self.redirect_uri(provider),
+                &pending.code_verifier,
+                &pending.nonce,
+            )
+            .await?;
+
+        if let Some(user_id) = pending.link_user_id {
+            let identity = self.link(client.config(), &claims, user_id).await?;
+            return Ok(FederatedOutcome::Linked {
+                identity,
+                return_to: pending.return_to,
+            });
+        }
+        let user_id = self.resolve_user(client.config(), &claims).await?;
+        Ok(FederatedOutcome::Login {
+            user_id,
+            return_to: pending.return_to,
+        })
+    }
+
+    async fn link(
+        &self,
+        provider: &OidcProviderConfig,
+        claims: &OidcClaims,
+        user_id: i64,
+    ) -> Result<IdentityResp> {
+        if let Some(identity) = self.find_identity(provider, claims).await? {
+            if identity.user_id != user_id {
+                return Err(Error::Conflict(format!(
+                    "this {} account is linked to another user",
+                    provider.name
+                )));
+            }
+            return Ok(identity.into());
+        }
+        let identity = self
+            .identity_repo
+            .link(
+                user_id,
+                IdentityKind::Federated,
+                Some(&provider.name),
+                &claims.sub,
+                claims.email.as_deref(),
+            )
+            .await?
+            .ok_or_else(|| Error::Conflict("identity linked concurrently".into()))?;
+        tracing::info!(user_id, provider = %provider.name, "linked federated identity");
+        Ok(identity.into())
+    }
+
+    /// The linked account, else the account with the same verified email
+    /// when the provider may link by email, else a new account when it may
+    /// create them.
+    async fn resolve_user(
+        &self,
+        provider: &OidcProviderConfig,
+        claims: &OidcClaims,
+    ) -> Result<i64> {
+        let email = claims.email.as_deref().map(|e| e.trim().to_lowercase());
+
+        if let Some(identity) = self.find_identity(provider, claims).await? {
+            self.identity_repo
+                .touch(identity.id, email.as_deref())
+                .await?;
+            return Ok(identity.user_id);
+        }
+
+        let Some(email) = email.filter(|_| claims.email_verified()) else {
+            return Err(Error::Validation(format!(
+                "{} did not share a verified email",
+                provider.name
+            )));
+        };
+
+        let user_id = match self.user_repo.find_by_email(&email).await? {
+            Some(user) if provider.link_by_email => user.id,
+            Some(_) => {
+                return Err(Error::Conflict(format!(
+                    "an account with this email exists; sign in and link {} from there",
+                    provider.name
+                )))
+            }
+            None if provider.create_accounts => self.create_user(claims, &email).await?,
+            None => return Err(Error::Forbidden),
+        };
+
+        self.identity_repo
+            .link(
+                user_id,
+                IdentityKind::Federated,
+                Some(&provider.name),
+                &claims.sub,
+                Some(&email),
+            )
+            .await?
+            .ok_or_else(|| Error::Conflict("identity linked concurrently".into()))?;
+        tracing::info!(user_id, provider = %provider.name, subject = %claims.sub, "linked federated identity on login");
+        Ok(user_id)
+    }
+
+    /// Just-in-time account. Its password is random and never shown, so the
+    /// user signs in through the provider until they reset it.
+    async fn create_user(&self, claims: &OidcClaims, email: &str) -> Result<i64> {
+        let base: String = claims
+            .preferred_username
+            .as_deref()
+            .unwrap_or_else(|| email.split('@').next().unwrap_or(email))
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || "._-".contains(*c))
+            .take(32)
+            .collect();
+        let base = if base.is_empty() { "user".into() } else { base };
+
+        let mut username = base.clone();
+        while self.user_repo.find_by_username(&username).await?.is_some() {
+            username = format!("{base}-{:06x}", OsRng.next_u32() & 0xff_ffff);
+        }
+
+        let user = self
+            .user_repo
+            .create(
+                CreateUserDto {
+                    username,
+                    email: email.to_string(),
+                    phone_number: None,
+                },
+                self.password_hashing.hash(&random_token())?,
+            )
+            .await?;
+        tracing::info!(
+            user_id = user.id,
+            "created account on first federated login"
+        );
+        Ok(user.id)
+    }
+
+    async fn find_identity(
+        &self,
+        provider: &OidcProviderConfig,
+        claims: &OidcClaims,
+    ) -> Result<Option<UserIdentity>> {
+        Ok(self
+            .identity_repo
+            .find(IdentityKind::Federated, Some(&provider.name), &claims.sub)
+            .await?)
+    }
+
+    fn provider(&self, name: &str) -> Result<&OidcClient> {
+        self.providers
+            .iter()
+            .find(|p| p.config().name == name)
+            .ok_or(Error::NotFound)
+    }
+
+    fn redirect_uri(&self, provider: &str) -> String {
+        format!("{}/auth/federated/{provider}/callback", self.public_url)
+    }
+
+    /// Paths on this server or URLs on a known origin only: no open redirects.
+    fn checked_return_to(&self, return_to: &str) -> Result<String> {
+        let is_path = return_to.starts_with('/')
+            && !return_to.starts_with("//")
+            && !return_to.starts_with("/\\");
+        let known_origin = Url::parse(return_to).is_ok_and(|u| {
+            self.return_origins
+                .contains(&u.origin().ascii_serialization())
+        });
+        if !is_path && !known_origin {
+            return Err(Error::Validation("return_to must stay on this site".into()));
+        }
+        Ok(return_to.to_string())
+    }
+}
+
+/// 32 random bytes, base64url.
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
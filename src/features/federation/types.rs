@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderResp {
+    /// goes into `/auth/federated/{name}/start`
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct StartQuery {
+    /// a path here, or a URL on the `AUTH_ISSUER` or `OAUTH_LOGIN_URL` origin;
+    /// without it the callback answers with JSON
+    pub return_to: Option<String>,
+    /// link the provider to the signed-in caller instead of signing in
+    #[serde(default)]
+    pub link: bool,
+}
+
+/// What the provider appends to the redirect URI.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Signed into the `__Host-federated_login` cookie between the redirect to
+/// the provider and its callback.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct PendingFederatedLogin {
+    pub provider: String,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub return_to: Option<String>,
+    /// set when a signed-in user links the provider instead of logging in
+    pub link_user_id: Option<i64>,
+    /// unix seconds
+    pub expires_at: i64,
+}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, Type};
+use utoipa::ToSchema;
+
+/// A way to sign in; see the `user_identities` migration for what
+/// `identifier` holds for each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize, ToSchema)]
+#[sqlx(type_name = "identity_kind_enum", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityKind {
+    Password,
+    EmailOtp,
+    Phone,
+    Passkey,
+    Federated,
+}
+
+/// Row of `user_identities`.
+#[derive(FromRow, Clone)]
+pub struct UserIdentity {
+    pub id: i64,
+    pub user_id: i64,
+    pub kind: IdentityKind,
+    pub provider: Option<String>,
+    pub identifier: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
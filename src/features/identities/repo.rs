@@ -0,0 +1,169 @@
+use sqlx::PgPool;
+
+use super::{IdentityKind, UserIdentity};
+
+const COLUMNS: &str = "id, user_id, kind, provider, identifier, email, created_at, last_used_at";
+
+/// What `unlink` did.
+pub enum Unlinked {
+    Removed(UserIdentity),
+    /// no such identity on this user
+    Missing,
+    /// refused: the user would have no way left to sign in
+    LastOne,
+}
+
+#[derive(Clone)]
+pub struct IdentityRepository {
+    pool: PgPool,
+}
+
+impl IdentityRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// The identity a login resolves through; `provider` only for federated ones.
+    pub async fn find(
+        &self,
+        kind: IdentityKind,
+        provider: Option<&str>,
+        identifier: &str,
+    ) -> sqlx::Result<Option<UserIdentity>> {
+        sqlx::query_as::<_, UserIdentity>(&format!(
+            r#"
+            SELECT {COLUMNS} FROM user_identities
+            WHERE kind = $1 AND provider IS NOT DISTINCT FROM $2 AND identifier = $3
+            "#
+        ))
+        .bind(kind)
+        .bind(provider)
+        .bind(identifier)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn for_user(&self, user_id: i64) -> sqlx::Result<Vec<UserIdentity>> {
+        sqlx::query_as::<_, UserIdentity>(&format!(
+            "SELECT {COLUMNS} FROM user_identities WHERE user_id = $1 ORDER BY id"
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn has(&self, user_id: i64, kind: IdentityKind) -> sqlx::Result<bool> {
+        sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM user_identities WHERE user_id = $1 AND kind = $2)",
+        )
+        .bind(user_id)
+        .bind(kind)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// `None` if the identity is already linked, to this user or another.
+    pub async fn link(
+        &self,
+        user_id: i64,
+        kind: IdentityKind,
+        provider: Option<&str>,
+        identifier: &str,
+        email: Option<&str>,
+    ) -> sqlx::Result<Option<UserIdentity>> {
+        sqlx::query_as::<_, UserIdentity>(&format!(
+            r#"
+            INSERT INTO user_identities (user_id, kind, provider, identifier, email)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (kind, provider, identifier) DO NOTHING
+            RETURNING {COLUMNS}
+            "#
+        ))
+        .bind(user_id)
+        .bind(kind)
+        .bind(provider)
+        .bind(identifier)
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Makes `identifier` the user's only identity of `kind` (a new verified
+    /// phone number replaces the old one).
+    pub async fn replace(
+        &self,
+        user_id: i64,
+        kind: IdentityKind,
+        identifier: &str,
+    ) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "DELETE FROM user_identities WHERE user_id = $1 AND kind = $2 AND identifier <> $3",
+        )
+        .bind(user_id)
+        .bind(kind)
+        .bind(identifier)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO user_identities (user_id, kind, identifier)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (kind, provider, identifier) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(kind)
+        .bind(identifier)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+
+    /// Removes the identity unless it is the user's last one. The user row is
+    /// locked so two concurrent unlinks cannot both pass the check.
+    pub async fn unlink(&self, user_id: i64, id: i64) -> sqlx::Result<Unlinked> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT 1 FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let removed = sqlx::query_as::<_, UserIdentity>(&format!(
+            "DELETE FROM user_identities WHERE id = $1 AND user_id = $2 RETURNING {COLUMNS}"
+        ))
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(removed) = removed else {
+            return Ok(Unlinked::Missing);
+        };
+        let left: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM user_identities WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_one(&mut *tx)
+                .await?;
+        if left == 0 {
+            // dropping the transaction rolls the delete back
+            return Ok(Unlinked::LastOne);
+        }
+        tx.commit().await?;
+        Ok(Unlinked::Removed(removed))
+    }
+
+    pub async fn touch(&self, id: i64, email: Option<&str>) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE user_identities
+            SET last_used_at = now(), email = COALESCE($2, email)
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(email)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+
+use crate::{
+    features::{
+        clients::EmailClient,
+        identities::{types::IdentityResp, IdentityService},
+        users::UserService,
+    },
+    infrastructure::middlewares::auth::AuthenticatedUser,
+};
+
+#[utoipa::path(
+    get,
+    path = "/users/me/identities",
+    tag = "users",
+    responses(
+        (status = 200, description = "Every way the caller can sign in", body = [IdentityResp]),
+        (status = 401, description = "Missing or invalid access token"),
+    )
+)]
+#[get("/users/me/identities")]
+pub async fn list_identities(
+    user: AuthenticatedUser,
+    identity_service: web::Data<IdentityService>,
+) -> actix_web::Result<impl Responder> {
+    Ok(HttpResponse::Ok().json(identity_service.list(user.uid).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/identities/password",
+    tag = "users",
+    responses(
+        (status = 202, description = "A link to choose a password is emailed to the account's address"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Called with an API key"),
+        (status = 409, description = "The account already has a password"),
+    )
+)]
+#[post("/users/me/identities/password")]
+pub async fn add_password_identity(
+    user: AuthenticatedUser,
+    identity_service: web::Data<IdentityService>,
+    user_service: web::Data<UserService>,
+    email_client: web::Data<EmailClient>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    identity_service.can_add_password(user.uid).await?;
+    user_service
+        .send_password_setup(user.uid, &email_client)
+        .await?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/identities/email",
+    tag = "users",
+    responses(
+        (status = 201, description = "Email code / magic link sign-in linked", body = IdentityResp),
+        (status = 400, description = "The email is not verified"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 409, description = "Already linked"),
+    )
+)]
+#[post("/users/me/identities/email")]
+pub async fn add_email_identity(
+    user: AuthenticatedUser,
+    identity_service: web::Data<IdentityService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    let identity = identity_service.add_email(user.uid).await?;
+    Ok(HttpResponse::Created().json(identity))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/identities/{id}",
+    tag = "users",
+    params(("id" = i64, Path, description = "Identity id")),
+    responses(
+        (status = 204, description = "Unlinked; a passkey is revoked with it"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 404, description = "No such identity on this account"),
+        (status = 409, description = "The last way to sign in"),
+    )
+)]
+#[delete("/users/me/identities/{id}")]
+pub async fn unlink_identity(
+    user: AuthenticatedUser,
+    path: web::Path<i64>,
+    identity_service: web::Data<IdentityService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    identity_service.unlink(user.uid, path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
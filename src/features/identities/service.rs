@@ -0,0 +1,105 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sqlx::PgPool;
+
+use crate::{
+    features::{
+        audits::{AuditRepository, EventType, LogLevel},
+        devices::DeviceKeyRepository,
+        identities::{types::IdentityResp, IdentityKind, IdentityRepository, Unlinked},
+        users::UserRepository,
+    },
+    utils::error::{Error, Result},
+};
+
+/// The ways a user can sign in. Phone numbers, passkeys and federated
+/// accounts are linked by their own flows (`/users/me/phone/verify`,
+/// `/auth/passkeys/register`, `/auth/federated/{provider}/start?link=true`),
+/// passwords through an emailed link (`/users/password/reset`); email codes
+/// are switched on here.
+#[derive(Clone)]
+pub struct IdentityService {
+    repo: IdentityRepository,
+    user_repo: UserRepository,
+    device_key_repo: DeviceKeyRepository,
+    audit_repo: AuditRepository,
+}
+
+impl IdentityService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            repo: IdentityRepository::new(pool.clone()),
+            user_repo: UserRepository::new(pool.clone()),
+            device_key_repo: DeviceKeyRepository::new(pool.clone()),
+            audit_repo: AuditRepository::new(pool),
+        }
+    }
+
+    pub async fn list(&self, user_id: i64) -> Result<Vec<IdentityResp>> {
+        let identities = self.repo.for_user(user_id).await?;
+        Ok(identities.into_iter().map(IdentityResp::from).collect())
+    }
+
+    /// For accounts without one (created through a provider, or whose
+    /// password was unlinked); the password itself is chosen through the
+    /// emailed link, existing ones are changed with `PUT /users/me/password`.
+    pub async fn can_add_password(&self, user_id: i64) -> Result<()> {
+        if self.repo.has(user_id, IdentityKind::Password).await? {
+            return Err(Error::Conflict("the account already has a password".into()));
+        }
+        Ok(())
+    }
+
+    /// Sign-in codes and magic links to the account's (verified) email.
+    pub async fn add_email(&self, user_id: i64) -> Result<IdentityResp> {
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await?
+            .ok_or(Error::NotFound)?;
+        if !user.is_email_verified {
+            return Err(Error::Validation("verify the email first".into()));
+        }
+        let identity = self
+            .repo
+            .link(
+                user_id,
+                IdentityKind::EmailOtp,
+                None,
+                &user.email.to_lowercase(),
+                None,
+            )
+            .await?
+            .ok_or_else(|| Error::Conflict("email sign-in is already linked".into()))?;
+        self.audited(user_id, EventType::IdentityLinked).await?;
+        Ok(identity.into())
+    }
+
+    /// Refuses to remove the last identity; unlinking a passkey revokes it.
+    pub async fn unlink(&self, user_id: i64, identity_id: i64) -> Result<()> {
+        let identity = match self.repo.unlink(user_id, identity_id).await? {
+            Unlinked::Removed(identity) => identity,
+            Unlinked::Missing => return Err(Error::NotFound),
+            Unlinked::LastOne => {
+                return Err(Error::Conflict(
+                    "cannot remove the last way to sign in".into(),
+                ))
+            }
+        };
+        if identity.kind == IdentityKind::Passkey {
+            if let Ok(credential_id) = URL_SAFE_NO_PAD.decode(&identity.identifier) {
+                self.device_key_repo
+                    .revoke_passkey(user_id, &credential_id)
+                    .await?;
+            }
+        }
+        tracing::info!(user_id, identity_id, kind = ?identity.kind, "unlinked identity");
+        self.audited(user_id, EventType::IdentityUnlinked).await
+    }
+
+    async fn audited(&self, user_id: i64, event_type: EventType) -> Result<()> {
+        self.audit_repo
+            .create(user_id, event_type, LogLevel::Info, None)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{IdentityKind, UserIdentity};
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityResp {
+    pub id: i64,
+    pub kind: IdentityKind,
+    /// federated identities: the provider's name
+    pub provider: Option<String>,
+    /// email, phone number or passkey credential id; the provider's email for
+    /// federated identities, nothing for passwords
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserIdentity> for IdentityResp {
+    fn from(i: UserIdentity) -> Self {
+        let label = match i.kind {
+            IdentityKind::Password => None,
+            IdentityKind::Federated => i.email,
+            _ => Some(i.identifier),
+        };
+        Self {
+            id: i.id,
+            kind: i.kind,
+            provider: i.provider,
+            label,
+            created_at: i.created_at,
+            last_used_at: i.last_used_at,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, Type};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[sqlx(type_name = "signing_key_status_enum", rename_all = "lowercase")]
+pub enum SigningKeyStatus {
+    Active,
+    Retired,
+}
+
+/// Row of `signing_keys`. No `Debug` on purpose: it carries the private key.
+#[derive(FromRow, Clone)]
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: String,
+    /// `SecretCipher::seal` output of the PKCS#8 DER, kid as AAD
+    pub private_sealed: Vec<u8>,
+    pub public_x: String,
+    pub public_y: String,
+    pub status: SigningKeyStatus,
+}
@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::{SigningKey, SigningKeyStatus};
+
+#[derive(Clone)]
+pub struct SigningKeyRepository {
+    pool: PgPool,
+}
+
+impl SigningKeyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// The active key plus every retired key still inside its verification window.
+    pub async fn usable(&self) -> sqlx::Result<Vec<SigningKey>> {
+        sqlx::query_as::<_, SigningKey>(
+            r#"
+            SELECT kid, algorithm, private_sealed, public_x, public_y, status
+            FROM signing_keys
+            WHERE private_sealed IS NOT NULL AND (status = $1 OR verify_until > now())
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(SigningKeyStatus::Active)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Keys still stored as plain PKCS#8, from before keys were sealed.
+    pub async fn unsealed(&self) -> sqlx::Result<Vec<(String, Vec<u8>)>> {
+        sqlx::query_as(
+            "SELECT kid, private_pkcs8 FROM signing_keys \
+             WHERE private_sealed IS NULL AND private_pkcs8 IS NOT NULL",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Replace the plain key of `kid` with its sealed form.
+    pub async fn seal(&self, kid: &str, private_sealed: &[u8]) -> sqlx::Result<()> {
+        sqlx::query(
+            "UPDATE signing_keys SET private_sealed = $2, private_pkcs8 = NULL WHERE kid = $1",
+        )
+        .bind(kid)
+        .bind(private_sealed)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Insert `kid` as the active key unless some key is active already
+    /// (several instances may bootstrap at the same time).
+    pub async fn insert_active_if_none(
+        &self,
+        kid: &str,
+        private_sealed: &[u8],
+        public_x: &str,
+        public_y: &str,
+    ) -> sqlx::Result<bool> {
+        let res = sqlx::query(
+            r#"
+            INSERT INTO signing_keys (kid, private_sealed, public_x, public_y, status)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(kid)
+        .bind(private_sealed)
+        .bind(public_x)
+        .bind(public_y)
+        .bind(SigningKeyStatus::Active)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Retire the active key (verifiable until `verify_until`) and activate the new one.
+    pub async fn rotate(
+        &self,
+        kid: &str,
+        private_sealed: &[u8],
+        public_x: &str,
+        public_y: &str,
+        verify_until: DateTime<Utc>,
+    ) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE signing_keys
+            SET status = $1, retired_at = now(), verify_until = $2
+            WHERE status = $3
+            "#,
+        )
+        .bind(SigningKeyStatus::Retired)
+        .bind(verify_until)
+        .bind(SigningKeyStatus::Active)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO signing_keys (kid, private_sealed, public_x, public_y, status)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(kid)
+        .bind(private_sealed)
+        .bind(public_x)
+        .bind(public_y)
+        .bind(SigningKeyStatus::Active)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+}
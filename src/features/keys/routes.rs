@@ -0,0 +1,45 @@
+use actix_web::{get, http::header, post, web, HttpResponse, Responder};
+
+use crate::{
+    features::keys::{
+        types::{Jwks, RotateKeyResp},
+        KeyRingService,
+    },
+    infrastructure::middlewares::{admin_auth::require_role, auth::AuthenticatedUser},
+};
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "keys",
+    responses(
+        (status = 200, description = "Public keys that verify our access tokens", body = Jwks),
+    )
+)]
+#[get("/.well-known/jwks.json")]
+pub async fn jwks(key_ring: web::Data<KeyRingService>) -> impl Responder {
+    // short max-age: verifiers must notice a rotation well before the old key retires
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(key_ring.jwks())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys/rotate",
+    tag = "admin",
+    responses(
+        (status = 200, description = "New signing key is active, the old one keeps verifying", body = RotateKeyResp),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+    )
+)]
+#[post("/admin/keys/rotate", wrap = "require_role(\"admin\")")]
+pub async fn rotate_key(
+    admin: AuthenticatedUser,
+    key_ring: web::Data<KeyRingService>,
+) -> actix_web::Result<impl Responder> {
+    tracing::info!(admin_id = admin.uid, "rotating signing key");
+    let kid = key_ring.rotate().await?;
+    Ok(HttpResponse::Ok().json(RotateKeyResp { kid }))
+}
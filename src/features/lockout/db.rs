@@ -0,0 +1,14 @@
+use chrono::{DateTime, Utc};
+use sqlx::prelude::FromRow;
+
+/// Row of `account_lockouts` for one account; what the lockout history shows.
+#[derive(FromRow, Clone)]
+pub struct AccountLockout {
+    pub id: i64,
+    pub failures: i32,
+    pub locked_at: DateTime<Utc>,
+    pub locked_until: DateTime<Utc>,
+    pub unlocked_at: Option<DateTime<Utc>>,
+    /// `admin` or `email` when released early
+    pub unlocked_by: Option<String>,
+}
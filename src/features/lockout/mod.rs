@@ -0,0 +1,10 @@
+mod db;
+mod repo;
+mod routes;
+mod service;
+pub mod types;
+
+pub(super) use db::*;
+pub(super) use repo::*;
+pub use routes::*;
+pub use service::*;
@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::AccountLockout;
+
+#[derive(Clone)]
+pub struct LockoutRepository {
+    pool: PgPool,
+}
+
+impl LockoutRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        user_id: Option<i64>,
+        ip_bucket: Option<&str>,
+        failures: i32,
+        locked_until: DateTime<Utc>,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_lockouts (user_id, ip_bucket, failures, locked_until)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(user_id)
+        .bind(ip_bucket)
+        .bind(failures)
+        .bind(locked_until)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Mark the user's running locks as released early; false if none was running.
+    pub async fn release_user(&self, user_id: i64, unlocked_by: &str) -> sqlx::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE account_lockouts
+            SET unlocked_at = now(), unlocked_by = $2
+            WHERE user_id = $1 AND unlocked_at IS NULL AND locked_until > now()
+            "#,
+        )
+        .bind(user_id)
+        .bind(unlocked_by)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Newest first.
+    pub async fn for_user(&self, user_id: i64, limit: i64) -> sqlx::Result<Vec<AccountLockout>> {
+        sqlx::query_as::<_, AccountLockout>(
+            r#"
+            SELECT id, failures, locked_at, locked_until, unlocked_at, unlocked_by
+            FROM account_lockouts
+            WHERE user_id = $1
+            ORDER BY locked_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,77 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use validator::Validate;
+
+use crate::{
+    features::lockout::{
+        types::{LockoutResp, UnlockAccountReq},
+        LockoutService,
+    },
+    infrastructure::middlewares::{admin_auth::require_role, auth::AuthenticatedUser},
+};
+
+#[utoipa::path(
+    post,
+    path = "/users/unlock",
+    tag = "users",
+    request_body = UnlockAccountReq,
+    responses(
+        (status = 204, description = "Lockout lifted, password sign-in works again"),
+        (status = 409, description = "Invalid, used or expired unlock token"),
+    )
+)]
+#[post("/users/unlock")]
+pub async fn unlock_account(
+    payload: web::Json<UnlockAccountReq>,
+    lockout_service: web::Data<LockoutService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    lockout_service.unlock_with_token(&payload.token).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/unlock",
+    tag = "admin",
+    params(("id" = i64, Path, description = "User id")),
+    responses(
+        (status = 204, description = "Lockout lifted and failed attempts forgotten"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+    )
+)]
+#[post("/admin/users/{id}/unlock", wrap = "require_role(\"admin\")")]
+pub async fn admin_unlock_account(
+    admin: AuthenticatedUser,
+    path: web::Path<i64>,
+    lockout_service: web::Data<LockoutService>,
+) -> actix_web::Result<impl Responder> {
+    let user_id = path.into_inner();
+    tracing::info!(admin_id = admin.uid, user_id, "unlocking account");
+
+    lockout_service.unlock(user_id, "admin").await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/users/{id}/lockouts",
+    tag = "admin",
+    params(("id" = i64, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The account's lockouts, newest first", body = [LockoutResp]),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+    )
+)]
+#[get("/admin/users/{id}/lockouts", wrap = "require_role(\"admin\")")]
+pub async fn account_lockouts(
+    _admin: AuthenticatedUser,
+    path: web::Path<i64>,
+    lockout_service: web::Data<LockoutService>,
+) -> actix_web::Result<impl Responder> {
+    let lockouts = lockout_service.history(path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(lockouts))
+}
@@ -0,0 +1,398 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Datelike, Duration, Utc};
+use deadpool_redis::{redis, Pool};
+use password_hash::rand_core::{OsRng, RngCore};
+use sqlx::PgPool;
+use std::{net::IpAddr, sync::Arc};
+
+use crate::{
+    features::{
+        audits::{AuditRepository, EventType, LogLevel},
+        clients::EmailClient,
+        lockout::{types::LockoutResp, LockoutRepository},
+        onboarding::{ip_to_bucket, sha256_hex},
+        system::ConfigService,
+    },
+    utils::error::{Error, Result},
+};
+
+/// Frontend page that receives `?token=` and posts it to `/users/unlock`.
+const DEFAULT_ACCOUNT_UNLOCK_URL: &str = "/unlock-account";
+/// Lockouts listed by the admin history endpoint.
+const LOCKOUT_HISTORY_LIMIT: i64 = 50;
+
+/// region Redis prefixes
+/// `user:{id}` / `ip:{sha256(bucket)}` -> failed logins in the current window
+pub const LOGIN_FAILURES_PREFIX: &str = "rl:login:v1:fail:";
+/// same suffixes -> set while the next attempt has to wait; its PTTL is the wait
+pub const LOGIN_BACKOFF_PREFIX: &str = "rl:login:v1:backoff:";
+/// same suffixes -> set while locked out
+pub const LOCKOUT_PREFIX: &str = "auth:lockout:v1:";
+/// sha256(unlock token) -> user id
+pub const UNLOCK_TOKEN_PREFIX: &str = "auth:unlock:v1:token:";
+/// endregion Redis prefixes
+
+#[derive(Clone)]
+pub struct LockoutService {
+    redis_pool: Pool,
+    repo: LockoutRepository,
+    audit_repo: AuditRepository,
+    config_service: Arc<ConfigService>,
+    unlock_url: String,
+}
+
+/// Whether a password attempt may be checked at all.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoginGate {
+    Open,
+    /// backing off after recent failures: seconds until the next try
+    Wait(u64),
+    /// locked out: seconds until the lock runs out
+    Locked(u64),
+}
+
+/// Whose failures a counter tracks.
+enum Subject<'a> {
+    User { id: i64, email: &'a str },
+    Ip { bucket: String },
+}
+
+impl LockoutService {
+    pub fn new(pool: PgPool, redis_pool: Pool, config_service: Arc<ConfigService>) -> Self {
+        Self {
+            redis_pool,
+            repo: LockoutRepository::new(pool.clone()),
+            audit_repo: AuditRepository::new(pool),
+            config_service,
+            unlock_url: std::env::var("ACCOUNT_UNLOCK_URL")
+                .unwrap_or_else(|_| DEFAULT_ACCOUNT_UNLOCK_URL.into()),
+        }
+    }
+
+    /// Looked up before the password is: a lock outranks a backoff, and the
+    /// longer wait of the account and the IP bucket wins.
+    pub async fn check(&self, user_id: Option<i64>, ip: Option<IpAddr>) -> Result<LoginGate> {
+        let suffixes: Vec<String> = user_id
+            .map(user_suffix)
+            .into_iter()
+            .chain(ip.as_ref().map(ip_suffix))
+            .collect();
+
+        let mut pipe = redis::pipe();
+        for suffix in &suffixes {
+            pipe.cmd("PTTL")
+                .arg(format!("{}{}", LOCKOUT_PREFIX, suffix))
+                .cmd("PTTL")
+                .arg(format!("{}{}", LOGIN_BACKOFF_PREFIX, suffix));
+        }
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let ttls: Vec<i64> = pipe.query_async(&mut *conn).await.map_err(Error::from)?;
+
+        let (mut locked_ms, mut wait_ms) = (0i64, 0i64);
+        for pair in ttls.chunks(2) {
+            locked_ms = locked_ms.max(pair[0]);
+            wait_ms = wait_ms.max(pair[1]);
+        }
+        Ok(if locked_ms > 0 {
+            LoginGate::Locked(ceil_seconds(locked_ms))
+        } else if wait_ms > 0 {
+            LoginGate::Wait(ceil_seconds(wait_ms))
+        } else {
+            LoginGate::Open
+        })
+    }
+
+    /// Count a wrong password against the account (when there is one) and the
+    /// IP bucket. Past `login_backoff_after` every failure doubles the wait;
+    /// reaching a threshold locks, records the lock and emails the owner an
+    /// unlock link.
+    pub async fn record_failure(
+        &self,
+        account: Option<(i64, &str)>,
+        ip: Option<IpAddr>,
+        email_client: &EmailClient,
+    ) -> Result<()> {
+        let cfg = self.config_service.get().await?;
+        let window = cfg.lockout_duration_seconds.max(1) as i64;
+
+        let subjects = account
+            .map(|(id, email)| (Subject::User { id, email }, cfg.lockout_threshold))
+            .into_iter()
+            .chain(ip.map(|ip| {
+                (
+                    Subject::Ip {
+                        bucket: ip_to_bucket(&ip),
+                    },
+                    cfg.lockout_ip_threshold,
+                )
+            }));
+
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        for (subject, threshold) in subjects {
+            let suffix = subject.suffix();
+            let failures_key = format!("{}{}", LOGIN_FAILURES_PREFIX, suffix);
+            let backoff_key = format!("{}{}", LOGIN_BACKOFF_PREFIX, suffix);
+
+            let (failures,): (i64,) = redis::pipe()
+                .atomic()
+                .cmd("INCR")
+                .arg(&failures_key)
+                .cmd("EXPIRE")
+                .arg(&failures_key)
+                .arg(window)
+                .ignore()
+                .query_async(&mut *conn)
+                .await
+                .map_err(Error::from)?;
+
+            if failures >= threshold as i64 {
+                // NX: of several concurrent failures only one places (and reports) the lock
+                let placed: Option<String> = redis::cmd("SET")
+                    .arg(format!("{}{}", LOCKOUT_PREFIX, suffix))
+                    .arg(failures)
+                    .arg("EX")
+                    .arg(window)
+                    .arg("NX")
+                    .query_async(&mut *conn)
+                    .await
+                    .map_err(Error::from)?;
+                let _: () = redis::cmd("DEL")
+                    .arg(&failures_key)
+                    .arg(&backoff_key)
+                    .query_async(&mut *conn)
+                    .await
+                    .map_err(Error::from)?;
+                if placed.is_some() {
+                    self.on_locked(&subject, failures as i32, window, email_client)
+                        .await?;
+                }
+                continue;
+            }
+
+            let wait = backoff_seconds(
+                failures,
+                cfg.login_backoff_after as i64,
+                cfg.login_backoff_max_seconds as i64,
+            );
+            if wait > 0 {
+                let _: () = redis::cmd("SET")
+                    .arg(&backoff_key)
+                    .arg(failures)
+                    .arg("EX")
+                    .arg(wait)
+                    .query_async(&mut *conn)
+                    .await
+                    .map_err(Error::from)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A right password clears the account's failures. The IP bucket keeps
+    /// its count, or one valid account would let a bucket guess at others.
+    pub async fn record_success(&self, user_id: i64) -> Result<()> {
+        let suffix = user_suffix(user_id);
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let _: () = redis::cmd("DEL")
+            .arg(format!("{}{}", LOGIN_FAILURES_PREFIX, suffix))
+            .arg(format!("{}{}", LOGIN_BACKOFF_PREFIX, suffix))
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Lift the account's lock and forget its failures. `unlocked_by` is
+    /// recorded on the lockout row (`admin`, `email`).
+    pub async fn unlock(&self, user_id: i64, unlocked_by: &str) -> Result<()> {
+        let suffix = user_suffix(user_id);
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let removed: i64 = redis::cmd("DEL")
+            .arg(format!("{}{}", LOCKOUT_PREFIX, suffix))
+            .arg(format!("{}{}", LOGIN_FAILURES_PREFIX, suffix))
+            .arg(format!("{}{}", LOGIN_BACKOFF_PREFIX, suffix))
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        let released = self
+            .repo
+            .release_user(user_id, unlocked_by)
+            .await
+            .map_err(Error::from)?;
+        if released || removed > 0 {
+            self.audit_repo
+                .create(user_id, EventType::AccountUnlocked, LogLevel::Info, None)
+                .await
+                .map_err(Error::from)?;
+        }
+        Ok(())
+    }
+
+    /// Self-service unlock with the emailed link; each link works once.
+    pub async fn unlock_with_token(&self, token: &str) -> Result<()> {
+        let key = format!("{}{}", UNLOCK_TOKEN_PREFIX, sha256_hex(token));
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let (user_id,): (Option<i64>,) = redis::pipe()
+            .atomic()
+            .cmd("GET")
+            .arg(&key)
+            .cmd("DEL")
+            .arg(&key)
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        let user_id =
+            user_id.ok_or_else(|| Error::InvalidOtp("invalid or expired unlock token".into()))?;
+        self.unlock(user_id, "email").await
+    }
+
+    pub async fn history(&self, user_id: i64) -> Result<Vec<LockoutResp>> {
+        Ok(self
+            .repo
+            .for_user(user_id, LOCKOUT_HISTORY_LIMIT)
+            .await
+            .map_err(Error::from)?
+            .into_iter()
+            .map(LockoutResp::from)
+            .collect())
+    }
+
+    async fn on_locked(
+        &self,
+        subject: &Subject<'_>,
+        failures: i32,
+        window: i64,
+        email_client: &EmailClient,
+    ) -> Result<()> {
+        let locked_until = Utc::now() + Duration::seconds(window);
+        match subject {
+            Subject::Ip { bucket } => {
+                tracing::warn!(%bucket, failures, "IP bucket locked out of password logins");
+                self.repo
+                    .create(None, Some(bucket), failures, locked_until)
+                    .await
+                    .map_err(Error::from)
+            }
+            Subject::User { id, email } => {
+                tracing::warn!(
+                    user_id = id,
+                    failures,
+                    "account locked out of password logins"
+                );
+                self.repo
+                    .create(Some(*id), None, failures, locked_until)
+                    .await
+                    .map_err(Error::from)?;
+                self.audit_repo
+                    .create(*id, EventType::AccountLocked, LogLevel::Warn, None)
+                    .await
+                    .map_err(Error::from)?;
+
+                // the lock holds either way; a lost email only delays the owner
+                if let Err(e) = self
+                    .send_unlock_email(*id, email, window, email_client)
+                    .await
+                {
+                    tracing::warn!(user_id = id, "unlock email not sent: {e}");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn send_unlock_email(
+        &self,
+        user_id: i64,
+        email: &str,
+        window: i64,
+        email_client: &EmailClient,
+    ) -> Result<()> {
+        let token = {
+            let mut bytes = [0u8; 32];
+            OsRng.fill_bytes(&mut bytes);
+            URL_SAFE_NO_PAD.encode(bytes)
+        };
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let _: () = redis::cmd("SET")
+            .arg(format!("{}{}", UNLOCK_TOKEN_PREFIX, sha256_hex(&token)))
+            .arg(user_id)
+            .arg("EX")
+            .arg(window)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        let separator = if self.unlock_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        let link = format!("{}{}token={}", self.unlock_url, separator, token);
+        let minutes = (window as u64).div_ceil(60);
+
+        let text_body = format!(
+            "There were too many failed sign-in attempts on your Forest Gate account, so password sign-in is paused for {minutes} minutes.\n\nIf that was you, open this link to sign in again right away:\n{link}\n\nIf it was not you, someone may know your email address; consider changing your password."
+        );
+        let html_body = format!(
+            r#"
+<!doctype html>
+<html>
+  <body style="background:#f6f8fb;margin:0;padding:24px;font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',Roboto,Helvetica,Arial,sans-serif;color:#0f172a;">
+    <p style="text-align:center;">Too many failed sign-in attempts: password sign-in is paused for {minutes} minutes.</p>
+    <div style="text-align:center;margin:20px 0;">
+      <a href="{link}" style="color:#2563eb;">Unlock my account</a>
+    </div>
+    <p style="text-align:center;color:#6b7280;">If this was not you, consider changing your password.</p>
+    © {year} Forest Gate
+  </body>
+</html>
+"#,
+            year = Utc::now().year()
+        );
+
+        email_client
+            .send_text_and_html(
+                email,
+                "Your account is temporarily locked",
+                Some(text_body.as_str()),
+                Some(html_body.as_str()),
+            )
+            .await
+    }
+}
+
+impl Subject<'_> {
+    fn suffix(&self) -> String {
+        match self {
+            Subject::User { id, .. } => user_suffix(*id),
+            Subject::Ip { bucket } => bucket_suffix(bucket),
+        }
+    }
+}
+
+fn user_suffix(user_id: i64) -> String {
+    format!("user:{user_id}")
+}
+
+fn ip_suffix(ip: &IpAddr) -> String {
+    bucket_suffix(&ip_to_bucket(ip))
+}
+
+fn bucket_suffix(bucket: &str) -> String {
+    format!("ip:{}", sha256_hex(bucket))
+}
+
+/// 1, 2, 4, ... seconds for each failure past `after`, capped at `max`.
+fn backoff_seconds(failures: i64, after: i64, max: i64) -> i64 {
+    let over = failures - after;
+    if over <= 0 || max <= 0 {
+        return 0;
+    }
+    (1i64 << (over - 1).min(62)).min(max)
+}
+
+fn ceil_seconds(ms: i64) -> u64 {
+    (ms as u64).div_ceil(1000)
+}
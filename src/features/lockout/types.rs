@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::AccountLockout;
+
+/// Lift a lockout with the link emailed when it started.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockAccountReq {
+    /// from the link in the lockout email
+    #[validate(length(min = 1, max = 128))]
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LockoutResp {
+    pub id: i64,
+    pub failures: i32,
+    pub locked_at: DateTime<Utc>,
+    pub locked_until: DateTime<Utc>,
+    pub unlocked_at: Option<DateTime<Utc>>,
+    /// `admin` or `email`; empty when the lock ran out on its own
+    pub unlocked_by: Option<String>,
+}
+
+impl From<AccountLockout> for LockoutResp {
+    fn from(l: AccountLockout) -> Self {
+        Self {
+            id: l.id,
+            failures: l.failures,
+            locked_at: l.locked_at,
+            locked_until: l.locked_until,
+            unlocked_at: l.unlocked_at,
+            unlocked_by: l.unlocked_by,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use sqlx::prelude::FromRow;
+
+/// Row of `user_totp`. No `Debug` on purpose: it carries the (sealed) seed.
+#[derive(FromRow, Clone)]
+pub struct UserTotp {
+    pub user_id: i64,
+    /// `SecretCipher::seal` output, user id as AAD
+    pub secret_sealed: Vec<u8>,
+    /// `None` until the first code confirmed the enrollment
+    pub enabled_at: Option<DateTime<Utc>>,
+}
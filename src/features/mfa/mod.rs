@@ -0,0 +1,11 @@
+mod db;
+mod repo;
+mod routes;
+mod service;
+mod totp;
+pub mod types;
+
+pub(super) use db::*;
+pub(super) use repo::*;
+pub use routes::*;
+pub use service::*;
@@ -0,0 +1,89 @@
+use sqlx::PgPool;
+
+use super::UserTotp;
+
+#[derive(Clone)]
+pub struct TotpRepository {
+    pool: PgPool,
+}
+
+impl TotpRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find(&self, user_id: i64) -> sqlx::Result<Option<UserTotp>> {
+        sqlx::query_as::<_, UserTotp>(
+            r#"
+            SELECT user_id, secret_sealed, enabled_at
+            FROM user_totp
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Store a new, not yet confirmed seed. Replaces a pending one, never an
+    /// enabled one (returns `false` then).
+    pub async fn upsert_pending(&self, user_id: i64, secret_sealed: &[u8]) -> sqlx::Result<bool> {
+        let res = sqlx::query(
+            r#"
+            INSERT INTO user_totp (user_id, secret_sealed)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+              SET secret_sealed = EXCLUDED.secret_sealed,
+                  last_used_step = NULL,
+                  updated_at = now()
+              WHERE user_totp.enabled_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(secret_sealed)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    pub async fn enable(&self, user_id: i64) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE user_totp
+            SET enabled_at = now(), updated_at = now()
+            WHERE user_id = $1 AND enabled_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Record `step` as used. `false` when it (or a later step) was used
+    /// already, which makes the code a replay. Atomic across instances.
+    pub async fn use_step(&self, user_id: i64, step: i64) -> sqlx::Result<bool> {
+        let res = sqlx::query(
+            r#"
+            UPDATE user_totp
+            SET last_used_step = $2, updated_at = now()
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    pub async fn delete(&self, user_id: i64) -> sqlx::Result<()> {
+        sqlx::query("DELETE FROM user_totp WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
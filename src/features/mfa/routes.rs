@@ -0,0 +1,155 @@
+use actix_web::{delete, http::header, post, web, HttpRequest, HttpResponse, Responder};
+use validator::Validate;
+
+use crate::{
+    features::{
+        clients::EmailClient,
+        mfa::{
+            types::{MfaRecoveryReq, MfaVerifyReq, TotpCodeReq, TotpEnrollResp},
+            MfaProof, MfaService,
+        },
+        users::UserService,
+    },
+    infrastructure::middlewares::auth::AuthenticatedUser,
+};
+
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/totp",
+    tag = "auth",
+    responses(
+        (status = 200, description = "New TOTP seed; 2FA turns on once a first code is confirmed", body = TotpEnrollResp),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 409, description = "2FA is already enabled"),
+    )
+)]
+#[post("/auth/mfa/totp")]
+pub async fn enroll_totp(
+    user: AuthenticatedUser,
+    user_service: web::Data<UserService>,
+    mfa_service: web::Data<MfaService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    let profile = user_service.profile(user.uid).await?;
+    let enrollment = mfa_service.enroll(user.uid, &profile.email).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(enrollment))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/totp/confirm",
+    tag = "auth",
+    request_body = TotpCodeReq,
+    responses(
+        (status = 204, description = "2FA enabled"),
+        (status = 404, description = "No pending enrollment"),
+        (status = 409, description = "Invalid or already used code"),
+    )
+)]
+#[post("/auth/mfa/totp/confirm")]
+pub async fn confirm_totp(
+    user: AuthenticatedUser,
+    payload: web::Json<TotpCodeReq>,
+    mfa_service: web::Data<MfaService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    mfa_service.confirm(user.uid, &payload.code).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/auth/mfa/totp",
+    tag = "auth",
+    request_body = TotpCodeReq,
+    responses(
+        (status = 204, description = "2FA disabled"),
+        (status = 404, description = "2FA is not enabled"),
+        (status = 409, description = "Invalid or already used code"),
+    )
+)]
+#[delete("/auth/mfa/totp")]
+pub async fn disable_totp(
+    user: AuthenticatedUser,
+    payload: web::Json<TotpCodeReq>,
+    mfa_service: web::Data<MfaService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    mfa_service.disable(user.uid, &payload.code).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/verify",
+    tag = "auth",
+    request_body = MfaVerifyReq,
+    responses(
+        (status = 200, description = "Second factor accepted, tokens issued"),
+        (status = 400, description = "Missing device cookie"),
+        (status = 409, description = "Invalid or expired challenge or code"),
+        (status = 423, description = "Account or IP locked out after failed logins"),
+        (status = 429, description = "Too many requests, or backing off after failed logins"),
+    )
+)]
+#[post("/auth/mfa/verify")]
+pub async fn verify_mfa(
+    req: HttpRequest,
+    payload: web::Json<MfaVerifyReq>,
+    user_service: web::Data<UserService>,
+    email_client: web::Data<EmailClient>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    user_service
+        .complete_mfa_login(
+            &req,
+            &payload.challenge_token,
+            MfaProof::Totp(&payload.code),
+            &email_client,
+        )
+        .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/verify/recovery-code",
+    tag = "auth",
+    request_body = MfaRecoveryReq,
+    responses(
+        (status = 200, description = "Recovery code accepted (and spent), tokens issued"),
+        (status = 400, description = "Missing device cookie"),
+        (status = 403, description = "Recovery codes are disabled"),
+        (status = 409, description = "Invalid or expired challenge or code"),
+        (status = 423, description = "Account or IP locked out after failed logins"),
+        (status = 429, description = "Too many requests, or backing off after failed logins"),
+    )
+)]
+#[post("/auth/mfa/verify/recovery-code")]
+pub async fn verify_mfa_recovery(
+    req: HttpRequest,
+    payload: web::Json<MfaRecoveryReq>,
+    user_service: web::Data<UserService>,
+    email_client: web::Data<EmailClient>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    user_service
+        .complete_mfa_login(
+            &req,
+            &payload.challenge_token,
+            MfaProof::RecoveryCode(&payload.recovery_code),
+            &email_client,
+        )
+        .await
+}
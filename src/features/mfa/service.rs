@@ -0,0 +1,263 @@
+use chrono::Utc;
+use deadpool_redis::{redis, Pool};
+use password_hash::rand_core::{OsRng, RngCore};
+use sqlx::PgPool;
+
+use crate::{
+    features::{
+        mfa::{
+            totp,
+            types::{MfaChallengeResp, TotpEnrollResp},
+            TotpRepository, UserTotp,
+        },
+        recovery::RecoveryService,
+    },
+    utils::{
+        crypto::SecretCipher,
+        error::{Error, Result},
+    },
+};
+
+/// How long a password-verified login waits for its second factor.
+pub const MFA_CHALLENGE_TTL_SECONDS: i64 = 5 * 60;
+/// Wrong codes allowed per challenge.
+const MFA_CHALLENGE_MAX_ATTEMPTS: i64 = 5;
+
+/// region Redis prefixes
+/// challenge token -> hash { uid, attempts } of a login waiting for its second factor
+pub const MFA_CHALLENGE_PREFIX: &str = "auth:mfa:v1:challenge:";
+/// endregion Redis prefixes
+
+#[derive(Clone)]
+pub struct MfaService {
+    repo: TotpRepository,
+    redis_pool: Pool,
+    cipher: SecretCipher,
+    issuer: String,
+    recovery: RecoveryService,
+}
+
+impl MfaService {
+    pub fn new(
+        pool: PgPool,
+        redis_pool: Pool,
+        cipher: SecretCipher,
+        issuer: String,
+        recovery: RecoveryService,
+    ) -> Self {
+        Self {
+            repo: TotpRepository::new(pool),
+            redis_pool,
+            cipher,
+            issuer,
+            recovery,
+        }
+    }
+
+    pub async fn is_enabled(&self, user_id: i64) -> Result<bool> {
+        Ok(self
+            .repo
+            .find(user_id)
+            .await
+            .map_err(Error::from)?
+            .is_some_and(|t| t.enabled_at.is_some()))
+    }
+
+    /// Start (or restart) enrollment. The seed is only active once `confirm`
+    /// saw a first code from it.
+    pub async fn enroll(&self, user_id: i64, account: &str) -> Result<TotpEnrollResp> {
+        let secret = totp::generate_secret()
+            .ok_or_else(|| Error::Unexpected("generate totp secret".into()))?;
+        let sealed = self
+            .cipher
+            .seal(&secret, &user_id.to_be_bytes())
+            .ok_or_else(|| Error::Unexpected("seal totp secret".into()))?;
+
+        let stored = self
+            .repo
+            .upsert_pending(user_id, &sealed)
+            .await
+            .map_err(Error::from)?;
+        if !stored {
+            return Err(Error::Conflict(
+                "two-factor authentication is already enabled".into(),
+            ));
+        }
+
+        Ok(TotpEnrollResp {
+            secret: totp::encode_secret(&secret),
+            otpauth_uri: totp::otpauth_uri(&secret, &self.issuer, account),
+        })
+    }
+
+    /// Finish enrollment with the first code from the app.
+    pub async fn confirm(&self, user_id: i64, code: &str) -> Result<()> {
+        let entry = self
+            .repo
+            .find(user_id)
+            .await
+            .map_err(Error::from)?
+            .filter(|t| t.enabled_at.is_none())
+            .ok_or(Error::NotFound)?;
+
+        if !self.check_code(&entry, code).await? {
+            return Err(Error::InvalidOtp("invalid or already used code".into()));
+        }
+        self.repo.enable(user_id).await.map_err(Error::from)
+    }
+
+    /// Turn 2FA off; requires a current code so a stolen session alone cannot.
+    pub async fn disable(&self, user_id: i64, code: &str) -> Result<()> {
+        let entry = self
+            .repo
+            .find(user_id)
+            .await
+            .map_err(Error::from)?
+            .filter(|t| t.enabled_at.is_some())
+            .ok_or(Error::NotFound)?;
+
+        if !self.check_code(&entry, code).await? {
+            return Err(Error::InvalidOtp("invalid or already used code".into()));
+        }
+        self.repo.delete(user_id).await.map_err(Error::from)
+    }
+
+    /// Park a login that proved one factor until the second one arrives.
+    pub async fn create_challenge(&self, user_id: i64) -> Result<MfaChallengeResp> {
+        let token = {
+            let mut bytes = [0u8; 32];
+            OsRng.fill_bytes(&mut bytes);
+            hex::encode(bytes)
+        };
+
+        let key = format!("{}{}", MFA_CHALLENGE_PREFIX, token);
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let _: () = redis::pipe()
+            .atomic()
+            .cmd("HSET")
+            .arg(&key)
+            .arg("uid")
+            .arg(user_id)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(MFA_CHALLENGE_TTL_SECONDS)
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        let mut methods = vec!["totp".to_string()];
+        if self.recovery.is_available(user_id).await? {
+            methods.push("recovery_code".into());
+        }
+
+        Ok(MfaChallengeResp {
+            mfa_required: true,
+            challenge_token: token,
+            methods,
+            expires_in: MFA_CHALLENGE_TTL_SECONDS,
+        })
+    }
+
+    /// The user whose login is parked under `token`, if it is still open.
+    pub async fn challenge_user(&self, token: &str) -> Result<Option<i64>> {
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        redis::cmd("HGET")
+            .arg(format!("{}{}", MFA_CHALLENGE_PREFIX, token))
+            .arg("uid")
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Check `proof` for the login parked under `token`. A pass consumes the
+    /// challenge; too many failures drop it.
+    pub async fn verify_challenge(
+        &self,
+        token: &str,
+        proof: MfaProof<'_>,
+    ) -> Result<ChallengeOutcome> {
+        let key = format!("{}{}", MFA_CHALLENGE_PREFIX, token);
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let user_id: Option<i64> = redis::cmd("HGET")
+            .arg(&key)
+            .arg("uid")
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        let Some(user_id) = user_id else {
+            return Ok(ChallengeOutcome::Failed(None));
+        };
+
+        let passed = match (self.repo.find(user_id).await.map_err(Error::from)?, proof) {
+            (Some(entry), MfaProof::Totp(code)) if entry.enabled_at.is_some() => {
+                self.check_code(&entry, code).await?
+            }
+            (Some(entry), MfaProof::RecoveryCode(code)) if entry.enabled_at.is_some() => {
+                self.recovery.redeem(user_id, code).await?
+            }
+            _ => false,
+        };
+
+        if !passed {
+            let attempts: i64 = redis::cmd("HINCRBY")
+                .arg(&key)
+                .arg("attempts")
+                .arg(1)
+                .query_async(&mut *conn)
+                .await
+                .map_err(Error::from)?;
+            if attempts >= MFA_CHALLENGE_MAX_ATTEMPTS {
+                let _: () = redis::cmd("DEL")
+                    .arg(&key)
+                    .query_async(&mut *conn)
+                    .await
+                    .map_err(Error::from)?;
+            }
+            return Ok(ChallengeOutcome::Failed(Some(user_id)));
+        }
+
+        // single use: whoever deletes the challenge wins
+        let deleted: i64 = redis::cmd("DEL")
+            .arg(&key)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        if deleted == 0 {
+            return Ok(ChallengeOutcome::Failed(Some(user_id)));
+        }
+        Ok(ChallengeOutcome::Passed(user_id))
+    }
+
+    /// Code within the drift window and newer than the last accepted one.
+    async fn check_code(&self, entry: &UserTotp, code: &str) -> Result<bool> {
+        let secret = self
+            .cipher
+            .open(&entry.secret_sealed, &entry.user_id.to_be_bytes())
+            .ok_or_else(|| Error::Unexpected("open totp secret".into()))?;
+
+        let Some(step) = totp::matching_step(&secret, code, Utc::now().timestamp()) else {
+            return Ok(false);
+        };
+        self.repo
+            .use_step(entry.user_id, step)
+            .await
+            .map_err(Error::from)
+    }
+}
+
+/// Outcome of `/auth/mfa/verify`.
+pub enum ChallengeOutcome {
+    Passed(i64),
+    /// the user id is known unless the challenge itself was unknown or expired
+    Failed(Option<i64>),
+}
+
+/// What a user answers an MFA challenge with.
+#[derive(Clone, Copy)]
+pub enum MfaProof<'a> {
+    Totp(&'a str),
+    /// single-use code from `/auth/recovery-codes`
+    RecoveryCode(&'a str),
+}
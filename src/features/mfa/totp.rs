@@ -0,0 +1,87 @@
+use data_encoding::BASE32_NOPAD;
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::utils::crypto::constant_time_eq;
+
+/// RFC 6238 with what every authenticator app supports: HMAC-SHA1, 6 digits, 30s steps.
+pub const PERIOD_SECONDS: i64 = 30;
+pub const DIGITS: usize = 6;
+/// Steps accepted on either side of the current one, for clock drift.
+pub const DRIFT_STEPS: i64 = 1;
+/// RFC 4226 recommends at least 160 bits.
+const SECRET_LEN: usize = 20;
+
+pub fn generate_secret() -> Option<Vec<u8>> {
+    let mut secret = vec![0u8; SECRET_LEN];
+    SystemRandom::new().fill(&mut secret).ok()?;
+    Some(secret)
+}
+
+/// Base32 without padding, what authenticator apps expect to be typed in.
+pub fn encode_secret(secret: &[u8]) -> String {
+    BASE32_NOPAD.encode(secret)
+}
+
+pub fn step_at(unix_seconds: i64) -> i64 {
+    unix_seconds.div_euclid(PERIOD_SECONDS)
+}
+
+/// RFC 4226 HOTP value for counter `step`.
+pub fn code_at(secret: &[u8], step: i64) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let tag = hmac::sign(&key, &step.to_be_bytes());
+    let mac = tag.as_ref();
+
+    // dynamic truncation
+    let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        mac[offset] & 0x7f,
+        mac[offset + 1],
+        mac[offset + 2],
+        mac[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS as u32),
+        width = DIGITS
+    )
+}
+
+/// The step within the drift window whose code equals `code`, if any.
+/// Every candidate is compared, so timing does not tell which one matched.
+pub fn matching_step(secret: &[u8], code: &str, unix_seconds: i64) -> Option<i64> {
+    let current = step_at(unix_seconds);
+    let mut found = None;
+    for step in current - DRIFT_STEPS..=current + DRIFT_STEPS {
+        if constant_time_eq(code_at(secret, step).as_bytes(), code.as_bytes()) {
+            found = Some(step);
+        }
+    }
+    found
+}
+
+/// Key URI understood by authenticator apps (the "Key Uri Format").
+pub fn otpauth_uri(secret: &[u8], issuer: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer_label}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={PERIOD_SECONDS}",
+        issuer_label = percent_encode(issuer),
+        account = percent_encode(account),
+        secret = encode_secret(secret),
+        issuer = percent_encode(issuer),
+    )
+}
+
+/// RFC 3986: everything but unreserved characters, spaces as `%20`.
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
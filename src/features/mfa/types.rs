@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpEnrollResp {
+    /// Base32 seed, for typing into the app by hand.
+    pub secret: String,
+    /// `otpauth://` URI, usually shown as a QR code.
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpCodeReq {
+    #[validate(length(min = 6, max = 6))]
+    pub code: String,
+}
+
+/// What `/users/login` answers instead of tokens when the user has 2FA on.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaChallengeResp {
+    pub mfa_required: bool,
+    /// Send back to `/auth/mfa/verify` together with a code, or to
+    /// `/auth/mfa/verify/recovery-code` with a recovery code.
+    pub challenge_token: String,
+    /// `totp`, plus `recovery_code` while the user has unused ones
+    pub methods: Vec<String>,
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaVerifyReq {
+    #[validate(length(min = 1, max = 128))]
+    pub challenge_token: String,
+    #[validate(length(min = 6, max = 6))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaRecoveryReq {
+    #[validate(length(min = 1, max = 128))]
+    pub challenge_token: String,
+    #[validate(length(min = 10, max = 32))]
+    pub recovery_code: String,
+}
@@ -1,7 +1,19 @@
+pub mod api_keys;
 pub mod audits;
+pub mod auth;
 pub mod clients;
 pub mod devices;
+pub mod federation;
+pub mod identities;
+pub mod keys;
+pub mod lockout;
+pub mod mfa;
+pub mod oauth;
 pub mod onboarding;
+pub mod passkeys;
+pub mod policies;
+pub mod recovery;
+pub mod roles;
 pub mod system;
 pub mod users;
 pub mod ws;
@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use jsonwebtoken::jwk::JwkSet;
+use sqlx::{prelude::FromRow, types::Json};
+
+#[derive(Debug, FromRow, Clone)]
+pub struct OAuthClient {
+    pub id: i64,
+    pub client_id: String,
+    pub client_secret_hash: Option<String>,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub allowed_scopes: Vec<String>,
+    pub grant_types: Vec<String>,
+    /// keys of `private_key_jwt` clients
+    pub jwks: Option<Json<JwkSet>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl OAuthClient {
+    pub fn allows_redirect(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|u| u == redirect_uri)
+    }
+
+    pub fn allows_grant(&self, grant_type: &str) -> bool {
+        self.grant_types.iter().any(|g| g == grant_type)
+    }
+
+    /// Has a secret or keys, i.e. can prove who it is.
+    pub fn is_confidential(&self) -> bool {
+        self.client_secret_hash.is_some() || self.jwks.is_some()
+    }
+}
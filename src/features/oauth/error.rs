@@ -0,0 +1,96 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use std::fmt;
+
+use crate::utils::error::Error;
+
+/// Errors of the OAuth endpoints, rendered as RFC 6749 §5.2 bodies
+/// (`{"error": "...", "error_description": "..."}`) instead of our usual `ErrorBody`.
+#[derive(Debug)]
+pub enum OAuthError {
+    InvalidRequest(String),
+    InvalidClient,
+    InvalidGrant(String),
+    UnauthorizedClient(String),
+    UnsupportedGrantType,
+    UnsupportedResponseType,
+    InvalidScope(String),
+    Server(Error),
+}
+
+impl OAuthError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant(_) => "invalid_grant",
+            OAuthError::UnauthorizedClient(_) => "unauthorized_client",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::UnsupportedResponseType => "unsupported_response_type",
+            OAuthError::InvalidScope(_) => "invalid_scope",
+            OAuthError::Server(_) => "server_error",
+        }
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            OAuthError::InvalidRequest(msg)
+            | OAuthError::InvalidGrant(msg)
+            | OAuthError::UnauthorizedClient(msg)
+            | OAuthError::InvalidScope(msg) => Some(msg),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.description() {
+            Some(msg) => write!(f, "{}: {msg}", self.code()),
+            None => write!(f, "{}", self.code()),
+        }
+    }
+}
+
+impl std::error::Error for OAuthError {}
+
+impl From<Error> for OAuthError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Unauthorized => OAuthError::InvalidGrant("token is invalid or expired".into()),
+            Error::Forbidden => OAuthError::UnauthorizedClient("grant is disabled".into()),
+            other => OAuthError::Server(other),
+        }
+    }
+}
+
+impl From<sqlx::Error> for OAuthError {
+    fn from(err: sqlx::Error) -> Self {
+        OAuthError::Server(Error::from(err))
+    }
+}
+
+impl ResponseError for OAuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthError::Server(e) => e.status_code(),
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let OAuthError::Server(e) = self {
+            tracing::error!("oauth server error: {e}");
+        }
+
+        let mut resp = HttpResponse::build(self.status_code());
+        if let OAuthError::InvalidClient = self {
+            resp.insert_header(("WWW-Authenticate", "Basic"));
+        }
+        resp.insert_header(("Cache-Control", "no-store"))
+            .json(serde_json::json!({
+                "error": self.code(),
+                "error_description": self.description(),
+            }))
+    }
+}
@@ -0,0 +1,12 @@
+mod db;
+mod error;
+mod repo;
+mod routes;
+mod service;
+pub mod types;
+
+pub(super) use db::*;
+pub use error::*;
+pub(super) use repo::*;
+pub use routes::*;
+pub use service::*;
@@ -0,0 +1,78 @@
+use jsonwebtoken::jwk::JwkSet;
+use sqlx::{types::Json, PgPool};
+
+use super::OAuthClient;
+
+const COLUMNS: &str = "id, client_id, client_secret_hash, name, redirect_uris, allowed_scopes, \
+                       grant_types, jwks, created_at, updated_at";
+
+#[derive(Clone)]
+pub struct OAuthClientRepository {
+    pool: PgPool,
+}
+
+impl OAuthClientRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        client_id: &str,
+        client_secret_hash: Option<&str>,
+        name: &str,
+        redirect_uris: &[String],
+        allowed_scopes: &[String],
+    ) -> sqlx::Result<OAuthClient> {
+        sqlx::query_as::<_, OAuthClient>(&format!(
+            r#"
+            INSERT INTO oauth_clients (client_id, client_secret_hash, name, redirect_uris, allowed_scopes)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING {COLUMNS}
+            "#
+        ))
+        .bind(client_id)
+        .bind(client_secret_hash)
+        .bind(name)
+        .bind(redirect_uris)
+        .bind(allowed_scopes)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// A client for the client-credentials grant only: no redirect URIs, a
+    /// secret hash or a key set.
+    pub async fn create_machine(
+        &self,
+        client_id: &str,
+        client_secret_hash: Option<&str>,
+        jwks: Option<&JwkSet>,
+        name: &str,
+        allowed_scopes: &[String],
+    ) -> sqlx::Result<OAuthClient> {
+        sqlx::query_as::<_, OAuthClient>(&format!(
+            r#"
+            INSERT INTO oauth_clients
+                (client_id, client_secret_hash, jwks, name, allowed_scopes, grant_types)
+            VALUES ($1, $2, $3, $4, $5, '{{client_credentials}}')
+            RETURNING {COLUMNS}
+            "#
+        ))
+        .bind(client_id)
+        .bind(client_secret_hash)
+        .bind(jwks.map(Json))
+        .bind(name)
+        .bind(allowed_scopes)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn find_by_client_id(&self, client_id: &str) -> sqlx::Result<Option<OAuthClient>> {
+        sqlx::query_as::<_, OAuthClient>(&format!(
+            "SELECT {COLUMNS} FROM oauth_clients WHERE client_id = $1"
+        ))
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,238 @@
+use actix_web::{get, http::header, post, web, HttpRequest, HttpResponse, Responder};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use url::Url;
+use validator::Validate;
+
+use crate::{
+    features::{
+        auth::DpopRequest,
+        oauth::{
+            types::{
+                AuthorizeReq, CreateMachineClientReq, CreateOAuthClientReq, CreateOAuthClientResp,
+                DiscoveryResp, IntrospectReq, IntrospectResp, TokenReq, TokenResp, UserInfoResp,
+            },
+            OAuthError, OAuthService,
+        },
+        onboarding::get_client_ip,
+    },
+    infrastructure::middlewares::{
+        admin_auth::require_role,
+        auth::{AuthenticatedUser, OAuthUser},
+    },
+};
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/openid-configuration",
+    tag = "oauth",
+    responses(
+        (status = 200, description = "OpenID Connect discovery document", body = DiscoveryResp),
+    )
+)]
+#[get("/.well-known/openid-configuration")]
+pub async fn openid_configuration(oauth_service: web::Data<OAuthService>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=3600"))
+        .json(oauth_service.discovery())
+}
+
+#[utoipa::path(
+    get,
+    path = "/oauth/authorize",
+    tag = "oauth",
+    params(AuthorizeReq),
+    responses(
+        (status = 302, description = "Back to the client with `code` (or `error`) and `state`, or to the login page"),
+        (status = 400, description = "Unknown client or unregistered redirect_uri"),
+    )
+)]
+#[get("/oauth/authorize")]
+pub async fn authorize(
+    req: HttpRequest,
+    query: web::Query<AuthorizeReq>,
+    user: Option<AuthenticatedUser>,
+    oauth_service: web::Data<OAuthService>,
+) -> Result<HttpResponse, OAuthError> {
+    let client = oauth_service
+        .client_for_redirect(&query.client_id, &query.redirect_uri)
+        .await?;
+
+    // API keys have no device to start a session on; they count as signed out
+    let Some(user) = user.filter(|u| u.api_key_id.is_none()) else {
+        return Ok(found(&oauth_service.login_redirect(&req.uri().to_string())));
+    };
+
+    let mut redirect = Url::parse(&query.redirect_uri)
+        .map_err(|_| OAuthError::InvalidRequest("malformed redirect_uri".into()))?;
+    {
+        let mut pairs = redirect.query_pairs_mut();
+        match oauth_service.authorize(&client, &user, &query).await {
+            Ok(code) => {
+                pairs.append_pair("code", &code);
+            }
+            Err(e) => {
+                pairs.append_pair("error", e.code());
+                if let Some(description) = e.description() {
+                    pairs.append_pair("error_description", description);
+                }
+            }
+        }
+        if let Some(state) = &query.state {
+            pairs.append_pair("state", state);
+        }
+    }
+
+    Ok(found(redirect.as_str()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/token",
+    tag = "oauth",
+    request_body(content = TokenReq, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Access token, ID token and (with offline_access) refresh token; only an access token for client_credentials", body = TokenResp),
+        (status = 400, description = "RFC 6749 error, e.g. invalid_grant"),
+        (status = 401, description = "Client authentication failed"),
+    )
+)]
+#[post("/oauth/token")]
+pub async fn token(
+    req: HttpRequest,
+    form: web::Form<TokenReq>,
+    oauth_service: web::Data<OAuthService>,
+) -> Result<HttpResponse, OAuthError> {
+    let resp = oauth_service
+        .token(
+            &form,
+            basic_credentials(&req),
+            get_client_ip(&req),
+            &DpopRequest::from_request(&req),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(resp))
+}
+
+#[utoipa::path(
+    post,
+    path = "/oauth/introspect",
+    tag = "oauth",
+    request_body(content = IntrospectReq, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Token state; inactive tokens carry no other fields", body = IntrospectResp),
+        (status = 400, description = "Client is not allowed to introspect"),
+        (status = 401, description = "Client authentication failed"),
+    )
+)]
+#[post("/oauth/introspect")]
+pub async fn introspect(
+    req: HttpRequest,
+    form: web::Form<IntrospectReq>,
+    oauth_service: web::Data<OAuthService>,
+) -> Result<HttpResponse, OAuthError> {
+    let resp = oauth_service
+        .introspect(&form, basic_credentials(&req))
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(resp))
+}
+
+#[utoipa::path(
+    get,
+    path = "/oauth/userinfo",
+    tag = "oauth",
+    responses(
+        (status = 200, description = "Claims about the user, filtered by the token's scopes", body = UserInfoResp),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Access token lacks the openid scope"),
+    )
+)]
+#[get("/oauth/userinfo")]
+pub async fn userinfo(
+    user: OAuthUser,
+    oauth_service: web::Data<OAuthService>,
+) -> actix_web::Result<impl Responder> {
+    let info = oauth_service.userinfo(&user).await?;
+    Ok(HttpResponse::Ok().json(info))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/oauth/clients",
+    tag = "admin",
+    request_body = CreateOAuthClientReq,
+    responses(
+        (status = 201, description = "Client registered, the secret is shown only here", body = CreateOAuthClientResp),
+        (status = 400, description = "Invalid redirect uri or scope"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+    )
+)]
+#[post("/admin/oauth/clients", wrap = "require_role(\"admin\")")]
+pub async fn create_client(
+    admin: AuthenticatedUser,
+    payload: web::Json<CreateOAuthClientReq>,
+    oauth_service: web::Data<OAuthService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    tracing::info!(admin_id = admin.uid, name = %payload.name, "registering oauth client");
+
+    let client = oauth_service.register_client(payload.into_inner()).await?;
+    Ok(HttpResponse::Created().json(client))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/oauth/machine-clients",
+    tag = "admin",
+    request_body = CreateMachineClientReq,
+    responses(
+        (status = 201, description = "Client registered for client_credentials; a secret, if any, is shown only here", body = CreateOAuthClientResp),
+        (status = 400, description = "Invalid scope or key set"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+    )
+)]
+#[post("/admin/oauth/machine-clients", wrap = "require_role(\"admin\")")]
+pub async fn create_machine_client(
+    admin: AuthenticatedUser,
+    payload: web::Json<CreateMachineClientReq>,
+    oauth_service: web::Data<OAuthService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    tracing::info!(admin_id = admin.uid, name = %payload.name, "registering machine client");
+
+    let client = oauth_service
+        .register_machine_client(payload.into_inner())
+        .await?;
+    Ok(HttpResponse::Created().json(client))
+}
+
+fn found(location: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+/// `Authorization: Basic base64(client_id:client_secret)`. Our ids and secrets
+/// are base64url, so the form-encoding step of RFC 6749 §2.3.1 is a no-op.
+fn basic_credentials(req: &HttpRequest) -> Option<(String, String)> {
+    let encoded = req
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (id, secret) = decoded.split_once(':')?;
+    Some((id.to_string(), secret.to_string()))
+}
@@ -0,0 +1,850 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use deadpool_redis::{redis, Pool};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk},
+    Algorithm, DecodingKey, Validation,
+};
+use password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use sqlx::{types::Json, PgPool};
+use std::{net::IpAddr, sync::Arc};
+use url::Url;
+
+use crate::{
+    features::{
+        auth::{AuthService, DpopRequest},
+        oauth::{
+            types::{
+                AuthorizationCode, AuthorizeReq, ClientAssertionClaims, CreateMachineClientReq,
+                CreateOAuthClientReq, CreateOAuthClientResp, DiscoveryResp, IdTokenClaims,
+                IntrospectReq, IntrospectResp, TokenReq, TokenResp, UserInfoResp,
+                JWT_BEARER_ASSERTION, SUPPORTED_SCOPES,
+            },
+            OAuthClient, OAuthClientRepository, OAuthError,
+        },
+        users::{PasswordCheck, PasswordHashing, UserRepository},
+    },
+    infrastructure::middlewares::auth::{AuthenticatedUser, OAuthUser},
+    utils::{
+        error::{Error, Result},
+        token_service::{IssuedTokens, TokenGrant, TokenKind, TokenService},
+    },
+};
+
+/// Authorization codes are single use and short lived (RFC 6749 §4.1.2).
+const AUTH_CODE_TTL_SECONDS: u64 = 60;
+/// `private_key_jwt` assertions may not be valid for longer than this.
+const MAX_ASSERTION_LIFETIME_SECONDS: i64 = 300;
+
+/// region Redis prefixes
+pub const AUTH_CODE_PREFIX: &str = "oauth:code:v1:";
+/// `{client_id}:{jti}` of a used client assertion, until it expires
+pub const ASSERTION_JTI_PREFIX: &str = "oauth:assertion:v1:";
+/// endregion Redis prefixes
+
+#[derive(Clone)]
+pub struct OAuthService {
+    redis_pool: Pool,
+    client_repo: OAuthClientRepository,
+    user_repo: UserRepository,
+    auth_service: AuthService,
+    token_service: Arc<TokenService>,
+    password_hashing: PasswordHashing,
+    login_url: String,
+}
+
+impl OAuthService {
+    /// `login_url` is where `/oauth/authorize` sends users without a session;
+    /// it receives the original authorize URL as `return_to`.
+    pub fn new(
+        pool: PgPool,
+        redis_pool: Pool,
+        auth_service: AuthService,
+        token_service: Arc<TokenService>,
+        password_hashing: PasswordHashing,
+        login_url: impl Into<String>,
+    ) -> Self {
+        Self {
+            redis_pool,
+            client_repo: OAuthClientRepository::new(pool.clone()),
+            user_repo: UserRepository::new(pool),
+            auth_service,
+            token_service,
+            password_hashing,
+            login_url: login_url.into(),
+        }
+    }
+
+    /// OpenID Connect Discovery 1.0. Every endpoint lives under the issuer URL.
+    pub fn discovery(&self) -> DiscoveryResp {
+        let issuer = self.token_service.issuer().trim_end_matches('/');
+        DiscoveryResp {
+            issuer: issuer.to_string(),
+            authorization_endpoint: format!("{issuer}/oauth/authorize"),
+            token_endpoint: format!("{issuer}/oauth/token"),
+            introspection_endpoint: format!("{issuer}/oauth/introspect"),
+            userinfo_endpoint: format!("{issuer}/oauth/userinfo"),
+            jwks_uri: format!("{issuer}/.well-known/jwks.json"),
+            scopes_supported: SUPPORTED_SCOPES.to_vec(),
+            response_types_supported: vec!["code"],
+            grant_types_supported: vec![
+                "authorization_code",
+                "refresh_token",
+                "client_credentials",
+            ],
+            subject_types_supported: vec!["public"],
+            id_token_signing_alg_values_supported: vec!["ES256"],
+            token_endpoint_auth_methods_supported: vec![
+                "client_secret_basic",
+                "client_secret_post",
+                "private_key_jwt",
+                "none",
+            ],
+            token_endpoint_auth_signing_alg_values_supported: vec!["ES256"],
+            code_challenge_methods_supported: vec!["S256"],
+            dpop_signing_alg_values_supported: vec!["ES256"],
+            claims_supported: vec![
+                "sub",
+                "iss",
+                "aud",
+                "exp",
+                "iat",
+                "sid",
+                "nonce",
+                "preferred_username",
+                "email",
+                "email_verified",
+                "phone_number",
+                "phone_number_verified",
+            ],
+        }
+    }
+
+    /// The client and redirect URI must be known before we may redirect anywhere;
+    /// errors here are shown to the user instead (RFC 6749 §4.1.2.1).
+    pub async fn client_for_redirect(
+        &self,
+        client_id: &str,
+        redirect_uri: &str,
+    ) -> std::result::Result<OAuthClient, OAuthError> {
+        let client = self
+            .client_repo
+            .find_by_client_id(client_id)
+            .await?
+            .ok_or_else(|| OAuthError::InvalidRequest("unknown client_id".into()))?;
+
+        if !client.allows_redirect(redirect_uri) {
+            return Err(OAuthError::InvalidRequest(
+                "redirect_uri is not registered for this client".into(),
+            ));
+        }
+        Ok(client)
+    }
+
+    /// Validate an authorization request of a signed in user and hand out a code.
+    pub async fn authorize(
+        &self,
+        client: &OAuthClient,
+        user: &AuthenticatedUser,
+        req: &AuthorizeReq,
+    ) -> std::result::Result<String, OAuthError> {
+        if req.response_type != "code" {
+            return Err(OAuthError::UnsupportedResponseType);
+        }
+        let scope = resolve_scope(client, req.scope.as_deref())?;
+
+        // PKCE is mandatory for every client, confidential ones included (OAuth 2.1)
+        let code_challenge = req
+            .code_challenge
+            .as_deref()
+            .ok_or_else(|| OAuthError::InvalidRequest("code_challenge is required".into()))?;
+        if req.code_challenge_method.as_deref() != Some("S256") {
+            return Err(OAuthError::InvalidRequest(
+                "code_challenge_method must be S256".into(),
+            ));
+        }
+        if !(43..=128).contains(&code_challenge.len()) {
+            return Err(OAuthError::InvalidRequest(
+                "malformed code_challenge".into(),
+            ));
+        }
+
+        let code = random_token(32);
+        let payload = serde_json::to_string(&AuthorizationCode {
+            client_id: client.client_id.clone(),
+            redirect_uri: req.redirect_uri.clone(),
+            user_id: user.uid,
+            device_id: user.did,
+            scope,
+            code_challenge: code_challenge.to_string(),
+            nonce: req.nonce.clone(),
+        })
+        .map_err(Error::from)?;
+
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let _: () = redis::cmd("SET")
+            .arg(format!("{}{}", AUTH_CODE_PREFIX, code))
+            .arg(payload)
+            .arg("EX")
+            .arg(AUTH_CODE_TTL_SECONDS)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        Ok(code)
+    }
+
+    /// Where to send a user that has no session yet; they come back to `authorize_path`.
+    pub fn login_redirect(&self, authorize_path: &str) -> String {
+        let return_to: String =
+            url::form_urlencoded::byte_serialize(authorize_path.as_bytes()).collect();
+        let sep = if self.login_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        format!("{}{sep}return_to={return_to}", self.login_url)
+    }
+
+    /// `/oauth/token` for every supported grant; each client is registered
+    /// for either the user-facing grants or `client_credentials`.
+    pub async fn token(
+        &self,
+        req: &TokenReq,
+        basic: Option<(String, String)>,
+        ip: Option<IpAddr>,
+        dpop: &DpopRequest,
+    ) -> std::result::Result<TokenResp, OAuthError> {
+        let client = self
+            .authenticate_client(
+                basic,
+                req.client_id.as_deref(),
+                req.client_secret.as_deref(),
+                req.client_assertion_type.as_deref(),
+                req.client_assertion.as_deref(),
+            )
+            .await?;
+
+        let grant_type = req.grant_type.as_str();
+        if !matches!(
+            grant_type,
+            "authorization_code" | "refresh_token" | "client_credentials"
+        ) {
+            return Err(OAuthError::UnsupportedGrantType);
+        }
+        if !client.allows_grant(grant_type) {
+            return Err(OAuthError::UnauthorizedClient(format!(
+                "{grant_type} is not allowed for this client"
+            )));
+        }
+
+        match grant_type {
+            "authorization_code" => self.exchange_code(&client, req, ip, dpop).await,
+            "refresh_token" => self.refresh(&client, req, dpop).await,
+            _ => self.client_credentials(&client, req).await,
+        }
+    }
+
+    /// RFC 7662 introspection for resource servers. Only confidential clients
+    /// may ask; anything not currently usable is reported as `active: false`.
+    pub async fn introspect(
+        &self,
+        req: &IntrospectReq,
+        basic: Option<(String, String)>,
+    ) -> std::result::Result<IntrospectResp, OAuthError> {
+        let client = self
+            .authenticate_client(
+                basic,
+                req.client_id.as_deref(),
+                req.client_secret.as_deref(),
+                req.client_assertion_type.as_deref(),
+                req.client_assertion.as_deref(),
+            )
+            .await?;
+        if !client.is_confidential() {
+            return Err(OAuthError::UnauthorizedClient(
+                "introspection needs a confidential client".into(),
+            ));
+        }
+
+        let Some((claims, session)) = self.auth_service.introspect(&req.token).await? else {
+            return self.introspect_client_token(&req.token).await;
+        };
+
+        Ok(IntrospectResp {
+            active: true,
+            scope: Some(claims.scope).filter(|s| !s.is_empty()),
+            client_id: claims.cid,
+            token_type: Some(
+                match claims.typ {
+                    TokenKind::Access => "access_token",
+                    TokenKind::Refresh => "refresh_token",
+                }
+                .into(),
+            ),
+            sub: Some(claims.sub),
+            uid: Some(claims.uid),
+            did: Some(claims.did),
+            sid: Some(claims.sid),
+            session_status: Some(session.status),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            jti: Some(claims.jti),
+        })
+    }
+
+    /// Machine client tokens are active while their client may still use the grant.
+    async fn introspect_client_token(
+        &self,
+        token: &str,
+    ) -> std::result::Result<IntrospectResp, OAuthError> {
+        let Some(claims) = self.auth_service.introspect_client(token).await? else {
+            return Ok(IntrospectResp::default());
+        };
+        let registered = self
+            .client_repo
+            .find_by_client_id(&claims.client_id)
+            .await?
+            .is_some_and(|c| c.allows_grant("client_credentials"));
+        if !registered {
+            return Ok(IntrospectResp::default());
+        }
+
+        Ok(IntrospectResp {
+            active: true,
+            scope: Some(claims.scope).filter(|s| !s.is_empty()),
+            client_id: Some(claims.client_id),
+            token_type: Some("access_token".into()),
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            jti: Some(claims.jti),
+            ..Default::default()
+        })
+    }
+
+    /// Claims about the caller, limited to what its access token's scopes allow.
+    pub async fn userinfo(&self, user: &OAuthUser) -> Result<UserInfoResp> {
+        if !user.has_scope("openid") {
+            return Err(Error::Forbidden);
+        }
+        let profile = self
+            .user_repo
+            .find_by_id(user.uid)
+            .await
+            .map_err(Error::from)?
+            .ok_or(Error::NotFound)?;
+
+        let mut info = UserInfoResp {
+            sub: profile.id.to_string(),
+            ..Default::default()
+        };
+        if user.has_scope("profile") {
+            info.preferred_username = Some(profile.username);
+        }
+        if user.has_scope("email") {
+            info.email = Some(profile.email);
+            info.email_verified = Some(profile.is_email_verified);
+        }
+        if user.has_scope("phone") {
+            info.phone_number_verified = profile
+                .phone_number
+                .as_ref()
+                .map(|_| profile.is_phone_verified);
+            info.phone_number = profile.phone_number;
+        }
+        Ok(info)
+    }
+
+    /// Register a client application. The secret is returned once and only its hash is kept.
+    pub async fn register_client(
+        &self,
+        req: CreateOAuthClientReq,
+    ) -> Result<CreateOAuthClientResp> {
+        for uri in &req.redirect_uris {
+            let parsed = Url::parse(uri)
+                .map_err(|_| Error::Validation(format!("invalid redirect uri: {uri}")))?;
+            if parsed.fragment().is_some() {
+                return Err(Error::Validation(format!(
+                    "redirect uri must not contain a fragment: {uri}"
+                )));
+            }
+        }
+
+        let allowed_scopes = req.allowed_scopes.unwrap_or_else(|| vec!["openid".into()]);
+        if let Some(unknown) = allowed_scopes
+            .iter()
+            .find(|s| !SUPPORTED_SCOPES.contains(&s.as_str()))
+        {
+            return Err(Error::Validation(format!("unsupported scope: {unknown}")));
+        }
+
+        let client_id = random_token(16);
+        let client_secret = req.confidential.then(|| random_token(32));
+        let client_secret_hash = match &client_secret {
+            Some(secret) => Some(self.password_hashing.hash(secret)?),
+            None => None,
+        };
+
+        let client = self
+            .client_repo
+            .create(
+                &client_id,
+                client_secret_hash.as_deref(),
+                &req.name,
+                &req.redirect_uris,
+                &allowed_scopes,
+            )
+            .await
+            .map_err(Error::from)?;
+
+        Ok(CreateOAuthClientResp {
+            client_id: client.client_id,
+            client_secret,
+            redirect_uris: client.redirect_uris,
+            allowed_scopes: client.allowed_scopes,
+        })
+    }
+
+    /// Register a backend for the client-credentials grant. Without `jwks` it
+    /// gets a secret, returned once; with them it signs `private_key_jwt` assertions.
+    pub async fn register_machine_client(
+        &self,
+        req: CreateMachineClientReq,
+    ) -> Result<CreateOAuthClientResp> {
+        let mut allowed_scopes = req
+            .allowed_scopes
+            .iter()
+            .map(|s| valid_api_scope(s))
+            .collect::<Result<Vec<_>>>()?;
+        allowed_scopes.sort();
+        allowed_scopes.dedup();
+
+        if let Some(jwks) = &req.jwks {
+            if jwks.keys.is_empty() || !jwks.keys.iter().all(is_p256_key) {
+                return Err(Error::Validation(
+                    "jwks must hold EC P-256 public keys".into(),
+                ));
+            }
+        }
+
+        let client_id = random_token(16);
+        let client_secret = req.jwks.is_none().then(|| random_token(32));
+        let client_secret_hash = match &client_secret {
+            Some(secret) => Some(self.password_hashing.hash(secret)?),
+            None => None,
+        };
+
+        let client = self
+            .client_repo
+            .create_machine(
+                &client_id,
+                client_secret_hash.as_deref(),
+                req.jwks.as_ref(),
+                &req.name,
+                &allowed_scopes,
+            )
+            .await
+            .map_err(Error::from)?;
+
+        Ok(CreateOAuthClientResp {
+            client_id: client.client_id,
+            client_secret,
+            redirect_uris: client.redirect_uris,
+            allowed_scopes: client.allowed_scopes,
+        })
+    }
+
+    /// HTTP Basic wins over `client_id`/`client_secret` in the body (RFC 6749 §2.3.1).
+    /// A `client_assertion` (private_key_jwt) replaces both.
+    async fn authenticate_client(
+        &self,
+        basic: Option<(String, String)>,
+        client_id: Option<&str>,
+        client_secret: Option<&str>,
+        assertion_type: Option<&str>,
+        assertion: Option<&str>,
+    ) -> std::result::Result<OAuthClient, OAuthError> {
+        if let Some(assertion) = assertion {
+            if basic.is_some() || client_secret.is_some() {
+                return Err(OAuthError::InvalidRequest(
+                    "use a single client authentication method".into(),
+                ));
+            }
+            if assertion_type != Some(JWT_BEARER_ASSERTION) {
+                return Err(OAuthError::InvalidRequest(
+                    "unsupported client_assertion_type".into(),
+                ));
+            }
+            return self.verify_client_assertion(client_id, assertion).await;
+        }
+
+        let (client_id, secret) = match basic {
+            Some((id, secret)) => (Some(id), Some(secret)),
+            None => (
+                client_id.map(str::to_owned),
+                client_secret.map(str::to_owned),
+            ),
+        };
+        let client_id = client_id.ok_or(OAuthError::InvalidClient)?;
+
+        let client = self
+            .client_repo
+            .find_by_client_id(&client_id)
+            .await?
+            .ok_or(OAuthError::InvalidClient)?;
+
+        match (&client.client_secret_hash, secret) {
+            (Some(hash), Some(secret))
+                if self.password_hashing.verify(hash, &secret)? != PasswordCheck::Mismatch =>
+            {
+                Ok(client)
+            }
+            (None, None) if client.jwks.is_none() => Ok(client),
+            _ => Err(OAuthError::InvalidClient),
+        }
+    }
+
+    /// RFC 7523 §3: signed by one of the client's keys, `iss` = `sub` = the
+    /// client, `aud` this server, short lived, and never seen before.
+    async fn verify_client_assertion(
+        &self,
+        client_id: Option<&str>,
+        assertion: &str,
+    ) -> std::result::Result<OAuthClient, OAuthError> {
+        // the assertion names its client; a client_id next to it must agree
+        let named = unverified_subject(assertion).ok_or(OAuthError::InvalidClient)?;
+        if client_id.is_some_and(|id| id != named) {
+            return Err(OAuthError::InvalidClient);
+        }
+        let client = self
+            .client_repo
+            .find_by_client_id(&named)
+            .await?
+            .ok_or(OAuthError::InvalidClient)?;
+        let Some(Json(jwks)) = &client.jwks else {
+            return Err(OAuthError::InvalidClient);
+        };
+
+        let header = decode_header(assertion).map_err(|_| OAuthError::InvalidClient)?;
+        if header.alg != Algorithm::ES256 {
+            return Err(OAuthError::InvalidClient);
+        }
+        let keys: Vec<&Jwk> = match header.kid.as_deref() {
+            Some(kid) => jwks.find(kid).into_iter().collect(),
+            None => jwks.keys.iter().collect(),
+        };
+
+        let issuer = self.token_service.issuer().trim_end_matches('/');
+        let mut validation = Validation::new(Algorithm::ES256);
+        validation.set_audience(&[
+            issuer.to_string(),
+            format!("{issuer}/oauth/token"),
+            format!("{issuer}/oauth/introspect"),
+        ]);
+        validation.set_issuer(&[&client.client_id]);
+        validation.sub = Some(client.client_id.clone());
+        validation.set_required_spec_claims(&["exp", "iss", "sub", "aud"]);
+        validation.leeway = 60;
+
+        let Some(claims) = keys
+            .into_iter()
+            .filter_map(|jwk| DecodingKey::from_jwk(jwk).ok())
+            .find_map(|key| decode::<ClientAssertionClaims>(assertion, &key, &validation).ok())
+            .map(|data| data.claims)
+        else {
+            tracing::warn!(client_id = %client.client_id, "invalid client assertion");
+            return Err(OAuthError::InvalidClient);
+        };
+        if claims.exp > Utc::now().timestamp() + MAX_ASSERTION_LIFETIME_SECONDS {
+            return Err(OAuthError::InvalidClient);
+        }
+
+        // SET NX: an assertion authenticates once, a replay finds the jti taken
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let fresh: Option<String> = redis::cmd("SET")
+            .arg(format!(
+                "{}{}:{}",
+                ASSERTION_JTI_PREFIX, client.client_id, claims.jti
+            ))
+            .arg("1")
+            .arg("NX")
+            .arg("EXAT")
+            .arg(claims.exp + validation.leeway as i64)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        if fresh.is_none() {
+            tracing::warn!(client_id = %client.client_id, "client assertion replayed");
+            return Err(OAuthError::InvalidClient);
+        }
+
+        Ok(client)
+    }
+
+    /// RFC 6749 §4.4: a token for the client itself, no refresh or ID token.
+    async fn client_credentials(
+        &self,
+        client: &OAuthClient,
+        req: &TokenReq,
+    ) -> std::result::Result<TokenResp, OAuthError> {
+        if !client.is_confidential() {
+            return Err(OAuthError::UnauthorizedClient(
+                "client_credentials needs a confidential client".into(),
+            ));
+        }
+        let scope = resolve_client_scope(client, req.scope.as_deref())?;
+
+        let (access_token, expires_at) = self
+            .token_service
+            .mint_client_token(&client.client_id, &scope)
+            .await?;
+        tracing::info!(client_id = %client.client_id, %scope, "issued client token");
+
+        Ok(TokenResp {
+            access_token,
+            token_type: "Bearer",
+            expires_in: expires_at - Utc::now().timestamp(),
+            refresh_token: None,
+            id_token: None,
+            scope,
+        })
+    }
+
+    async fn exchange_code(
+        &self,
+        client: &OAuthClient,
+        req: &TokenReq,
+        ip: Option<IpAddr>,
+        dpop: &DpopRequest,
+    ) -> std::result::Result<TokenResp, OAuthError> {
+        let code = req
+            .code
+            .as_deref()
+            .ok_or_else(|| OAuthError::InvalidRequest("code is required".into()))?;
+
+        // GETDEL: a code can be redeemed once, a replay finds nothing
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let stored: Option<String> = redis::cmd("GETDEL")
+            .arg(format!("{}{}", AUTH_CODE_PREFIX, code))
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        drop(conn);
+
+        let grant: AuthorizationCode = match stored {
+            Some(json) => serde_json::from_str(&json).map_err(Error::from)?,
+            None => {
+                return Err(OAuthError::InvalidGrant(
+                    "code is invalid, expired or already used".into(),
+                ))
+            }
+        };
+
+        if grant.client_id != client.client_id {
+            return Err(OAuthError::InvalidGrant(
+                "code was issued to another client".into(),
+            ));
+        }
+        if req.redirect_uri.as_deref() != Some(grant.redirect_uri.as_str()) {
+            return Err(OAuthError::InvalidGrant(
+                "redirect_uri does not match".into(),
+            ));
+        }
+        let verifier = req
+            .code_verifier
+            .as_deref()
+            .ok_or_else(|| OAuthError::InvalidRequest("code_verifier is required".into()))?;
+        if URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) != grant.code_challenge {
+            return Err(OAuthError::InvalidGrant("PKCE verification failed".into()));
+        }
+
+        // a DPoP proof on the token request binds the tokens to that key
+        let jkt = match dpop.proof {
+            Some(_) => Some(
+                self.auth_service
+                    .bind_device_key(grant.user_id, grant.device_id, dpop)
+                    .await
+                    .map_err(|_| OAuthError::InvalidRequest("invalid DPoP proof".into()))?,
+            ),
+            None => None,
+        };
+
+        let tokens = self
+            .auth_service
+            .issue(
+                grant.user_id,
+                grant.device_id,
+                ip,
+                &TokenGrant {
+                    scope: grant.scope.clone(),
+                    client_id: Some(client.client_id.clone()),
+                    jkt,
+                },
+            )
+            .await?;
+
+        self.token_response(client, grant.user_id, &grant.scope, grant.nonce, tokens)
+            .await
+    }
+
+    async fn refresh(
+        &self,
+        client: &OAuthClient,
+        req: &TokenReq,
+        dpop: &DpopRequest,
+    ) -> std::result::Result<TokenResp, OAuthError> {
+        let refresh_token = req
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| OAuthError::InvalidRequest("refresh_token is required".into()))?;
+
+        // check the owner before rotating, so another client cannot burn the token
+        let presented = self.token_service.verify(refresh_token).await?;
+        if presented.cid.as_deref() != Some(client.client_id.as_str()) {
+            return Err(OAuthError::InvalidGrant(
+                "refresh token was issued to another client".into(),
+            ));
+        }
+
+        let (claims, tokens) = self.auth_service.refresh(refresh_token, dpop).await?;
+        self.token_response(client, claims.uid, &claims.scope, None, tokens)
+            .await
+    }
+
+    async fn token_response(
+        &self,
+        client: &OAuthClient,
+        user_id: i64,
+        scope: &str,
+        nonce: Option<String>,
+        tokens: IssuedTokens,
+    ) -> std::result::Result<TokenResp, OAuthError> {
+        let scopes: Vec<&str> = scope.split_whitespace().collect();
+        let now = Utc::now().timestamp();
+
+        let id_token = if scopes.contains(&"openid") {
+            let user = self
+                .user_repo
+                .find_by_id(user_id)
+                .await?
+                .ok_or_else(|| OAuthError::InvalidGrant("user no longer exists".into()))?;
+            let with_email = scopes.contains(&"email");
+
+            Some(self.token_service.sign(&IdTokenClaims {
+                iss: self.token_service.issuer().to_string(),
+                sub: user_id.to_string(),
+                aud: client.client_id.clone(),
+                exp: tokens.access_expires_at,
+                iat: now,
+                sid: tokens.session_id,
+                nonce,
+                email_verified: with_email.then_some(user.is_email_verified),
+                email: with_email.then_some(user.email),
+                preferred_username: scopes.contains(&"profile").then_some(user.username),
+            })?)
+        } else {
+            None
+        };
+
+        Ok(TokenResp {
+            access_token: tokens.access_token,
+            token_type: if tokens.jkt.is_some() {
+                "DPoP"
+            } else {
+                "Bearer"
+            },
+            expires_in: tokens.access_expires_at - now,
+            // long lived access needs to be asked for explicitly
+            refresh_token: tokens
+                .refresh_token
+                .filter(|_| scopes.contains(&"offline_access")),
+            id_token,
+            scope: scope.to_string(),
+        })
+    }
+}
+
+/// Requested scopes must be a subset of the client's; `openid` is mandatory.
+fn resolve_scope(
+    client: &OAuthClient,
+    requested: Option<&str>,
+) -> std::result::Result<String, OAuthError> {
+    let requested: Vec<&str> = requested.unwrap_or("openid").split_whitespace().collect();
+
+    if !requested.contains(&"openid") {
+        return Err(OAuthError::InvalidScope("openid scope is required".into()));
+    }
+    if let Some(denied) = requested
+        .iter()
+        .find(|s| !client.allowed_scopes.iter().any(|a| a == *s))
+    {
+        return Err(OAuthError::InvalidScope(format!(
+            "scope {denied} is not allowed for this client"
+        )));
+    }
+
+    Ok(requested.join(" "))
+}
+
+/// Requested scopes must be a subset of the client's; none requested means all of them.
+fn resolve_client_scope(
+    client: &OAuthClient,
+    requested: Option<&str>,
+) -> std::result::Result<String, OAuthError> {
+    let requested: Vec<&str> = match requested {
+        Some(scope) => scope.split_whitespace().collect(),
+        None => client.allowed_scopes.iter().map(String::as_str).collect(),
+    };
+    if let Some(denied) = requested
+        .iter()
+        .find(|s| !client.allowed_scopes.iter().any(|a| a == *s))
+    {
+        return Err(OAuthError::InvalidScope(format!(
+            "scope {denied} is not allowed for this client"
+        )));
+    }
+
+    Ok(requested.join(" "))
+}
+
+/// Machine scopes name APIs, never user data: the OpenID scopes are refused.
+fn valid_api_scope(scope: &str) -> Result<String> {
+    let scope = scope.trim().to_lowercase();
+    let allowed = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-:.".contains(c);
+    if scope.is_empty() || scope.len() > 64 || !scope.chars().all(allowed) {
+        return Err(Error::Validation(
+            "scopes use lowercase letters, digits, `_`, `-`, `:` and `.`".into(),
+        ));
+    }
+    if SUPPORTED_SCOPES.contains(&scope.as_str()) {
+        return Err(Error::Validation(format!(
+            "{scope} is a user scope, machine clients have no user"
+        )));
+    }
+    Ok(scope)
+}
+
+fn is_p256_key(jwk: &Jwk) -> bool {
+    matches!(&jwk.algorithm, AlgorithmParameters::EllipticCurve(ec) if ec.curve == EllipticCurve::P256)
+        && DecodingKey::from_jwk(jwk).is_ok()
+}
+
+/// `sub` of a JWT before its signature is checked, to find the key to check it with.
+fn unverified_subject(jwt: &str) -> Option<String> {
+    let payload = URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1)?).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    claims["sub"].as_str().map(str::to_owned)
+}
+
+fn random_token(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
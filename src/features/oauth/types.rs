@@ -0,0 +1,219 @@
+use jsonwebtoken::jwk::JwkSet;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::features::auth::SessionStatus;
+
+/// Scopes this provider understands. Clients may be restricted to a subset.
+pub const SUPPORTED_SCOPES: &[&str] = &["openid", "profile", "email", "phone", "offline_access"];
+
+/// `client_assertion_type` of `private_key_jwt` (RFC 7523 §2.2).
+pub const JWT_BEARER_ASSERTION: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuthorizeReq {
+    /// Only `code` is supported.
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    /// Space separated, must contain `openid`.
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    /// base64url(SHA-256(code_verifier))
+    pub code_challenge: Option<String>,
+    /// Only `S256` is supported.
+    pub code_challenge_method: Option<String>,
+    pub nonce: Option<String>,
+}
+
+/// `application/x-www-form-urlencoded` body of `/oauth/token`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TokenReq {
+    /// `authorization_code`, `refresh_token` or `client_credentials`
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    /// `client_credentials` only: space separated, defaults to every scope of the client.
+    pub scope: Option<String>,
+    /// Used when the client does not authenticate with HTTP Basic.
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// `private_key_jwt`: must be the jwt-bearer type
+    pub client_assertion_type: Option<String>,
+    pub client_assertion: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenResp {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    pub scope: String,
+}
+
+/// `application/x-www-form-urlencoded` body of `/oauth/introspect`.
+/// A `token_type_hint` is accepted but ignored, the type is read from the token itself.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct IntrospectReq {
+    pub token: String,
+    /// Used when the client does not authenticate with HTTP Basic.
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub client_assertion_type: Option<String>,
+    pub client_assertion: Option<String>,
+}
+
+/// RFC 7662 §2.2 plus our own `uid`, `did`, `sid` and `session_status`
+/// (absent for machine client tokens). An inactive token is just `{"active": false}`.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct IntrospectResp {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub did: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub sid: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_status: Option<SessionStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+/// OpenID Connect Core §5.3.2, filtered by the scopes of the access token.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct UserInfoResp {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone_number_verified: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DiscoveryResp {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub introspection_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub scopes_supported: Vec<&'static str>,
+    pub response_types_supported: Vec<&'static str>,
+    pub grant_types_supported: Vec<&'static str>,
+    pub subject_types_supported: Vec<&'static str>,
+    pub id_token_signing_alg_values_supported: Vec<&'static str>,
+    pub token_endpoint_auth_methods_supported: Vec<&'static str>,
+    pub token_endpoint_auth_signing_alg_values_supported: Vec<&'static str>,
+    pub code_challenge_methods_supported: Vec<&'static str>,
+    pub dpop_signing_alg_values_supported: Vec<&'static str>,
+    pub claims_supported: Vec<&'static str>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOAuthClientReq {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1))]
+    pub redirect_uris: Vec<String>,
+    /// Defaults to `["openid"]`.
+    pub allowed_scopes: Option<Vec<String>>,
+    /// Confidential (server side) clients get a secret, public ones rely on PKCE.
+    #[serde(default)]
+    pub confidential: bool,
+}
+
+/// A backend that gets tokens for itself (client-credentials grant).
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMachineClientReq {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    /// API scopes, e.g. `audit:write`: lowercase letters, digits, `_`, `-`, `:` and `.`
+    #[validate(length(min = 1))]
+    pub allowed_scopes: Vec<String>,
+    /// EC P-256 public keys for `private_key_jwt`; without them the client gets a secret.
+    #[schema(value_type = Option<Object>)]
+    pub jwks: Option<JwkSet>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOAuthClientResp {
+    pub client_id: String,
+    /// Shown only once, store it right away.
+    pub client_secret: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub allowed_scopes: Vec<String>,
+}
+
+/// What `/oauth/authorize` remembers until the code is redeemed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorizationCode {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub user_id: i64,
+    pub device_id: i64,
+    pub scope: String,
+    pub code_challenge: String,
+    pub nonce: Option<String>,
+}
+
+/// `client_assertion` of `private_key_jwt` (RFC 7523 §3); `iss`, `sub`,
+/// `aud` and `exp` are checked by the decoder.
+#[derive(Debug, Deserialize)]
+pub struct ClientAssertionClaims {
+    pub exp: i64,
+    pub jti: String,
+}
+
+/// OpenID Connect Core §2.
+#[derive(Debug, Serialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub sid: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+}
@@ -1,9 +1,11 @@
 pub mod repo;
+mod rate_limit;
 mod routes;
 mod service;
 pub mod types;
 pub mod utils;
 
+pub use rate_limit::*;
 pub use routes::*;
 pub use service::*;
 pub use utils::*;
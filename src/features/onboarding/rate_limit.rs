@@ -0,0 +1,374 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use deadpool_redis::{
+    redis::{self, RedisError},
+    Pool,
+};
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+
+/// Fallback counters kept before expired ones are swept.
+const MEMORY_MAX_KEYS: usize = 100_000;
+/// 2025-01-01T00:00:00Z. GCRA in Redis counts time from here, not from 1970:
+/// `ms * 5 000` stays below 2^53, exact in a Lua double, until 2082 instead
+/// of 2027. Written out as a literal in the script; keep the two in sync.
+const GCRA_EPOCH_MS: u64 = 1_735_689_600_000;
+
+/// How a limit of `limit` hits per `window_ms` is counted in Redis. Every
+/// script takes `KEYS[1]` and `ARGV = now_ms, window_ms, limit, member` and
+/// returns `{allowed, remaining, reset_ms}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithm {
+    /// Exact sliding window log: one ZSET member per allowed hit, so memory
+    /// grows with `limit`.
+    #[default]
+    SlidingWindow,
+    /// `limit` tokens refilled evenly over the window; a HASH of two fields.
+    TokenBucket,
+    /// Generic cell rate algorithm: one theoretical arrival time per key.
+    /// Same bursts as the token bucket, a single string to store.
+    Gcra,
+}
+
+impl RateLimitAlgorithm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SlidingWindow => "sliding_window",
+            Self::TokenBucket => "token_bucket",
+            Self::Gcra => "gcra",
+        }
+    }
+
+    /// GCRA in Redis counts in `gcd(limit, window_ms) / limit` ms units since
+    /// `GCRA_EPOCH_MS` that must stay exact in a Lua double: the reduced
+    /// `limit` can't exceed 5 000.
+    pub fn supports(self, limit: u32, window_ms: u64) -> bool {
+        let (mut a, mut b) = (limit as u64, window_ms);
+        while b > 0 {
+            (a, b) = (b, a % b);
+        }
+        self != Self::Gcra || limit as u64 / a.max(1) <= 5_000
+    }
+
+    fn script(self) -> &'static str {
+        match self {
+            Self::SlidingWindow => {
+                r#"
+                local now, window, limit = tonumber(ARGV[1]), tonumber(ARGV[2]), tonumber(ARGV[3])
+                redis.call("ZREMRANGEBYSCORE", KEYS[1], 0, now - window)
+                local count = redis.call("ZCARD", KEYS[1])
+                local allowed = 0
+                if count < limit then
+                  redis.call("ZADD", KEYS[1], now, ARGV[4])
+                  redis.call("PEXPIRE", KEYS[1], window)
+                  count = count + 1
+                  allowed = 1
+                end
+                local oldest = redis.call("ZRANGE", KEYS[1], 0, 0, "WITHSCORES")
+                return {allowed, limit - count, tonumber(oldest[2] or now) + window - now}
+            "#
+            }
+            Self::TokenBucket => {
+                r#"
+                local now, window, limit = tonumber(ARGV[1]), tonumber(ARGV[2]), tonumber(ARGV[3])
+                local rate = limit / window
+                local state = redis.call("HMGET", KEYS[1], "tokens", "ts")
+                local tokens = tonumber(state[1]) or limit
+                local ts = tonumber(state[2]) or now
+                tokens = math.min(limit, tokens + math.max(0, now - ts) * rate)
+                local allowed, reset = 0, 0
+                if tokens >= 1 then
+                  tokens = tokens - 1
+                  allowed = 1
+                  reset = (limit - tokens) / rate
+                else
+                  reset = (1 - tokens) / rate
+                end
+                redis.call("HSET", KEYS[1], "tokens", string.format("%.6f", tokens), "ts", ARGV[1])
+                redis.call("PEXPIRE", KEYS[1], window)
+                return {allowed, math.floor(tokens), math.ceil(reset)}
+            "#
+            }
+            Self::Gcra => {
+                // time in exact integer units of gcd(limit, window) / limit ms
+                // since GCRA_EPOCH_MS, one `interval` apart per allowed hit
+                r#"
+                local now_ms, window_ms, limit = tonumber(ARGV[1]), tonumber(ARGV[2]), tonumber(ARGV[3])
+                local a, b = limit, window_ms
+                while b > 0 do a, b = b, a % b end
+                local per_ms, interval = limit / a, window_ms / a
+                local now, window = (now_ms - 1735689600000) * per_ms, interval * limit
+                local stored = tonumber(redis.call("GET", KEYS[1]) or 0)
+                local tat = math.min(math.max(stored, now), now + window)
+                local allowed, reset = 0, tat + interval - now - window
+                if tat + interval - now <= window then
+                  tat = tat + interval
+                  allowed = 1
+                  reset = tat - now
+                  redis.call("SET", KEYS[1], string.format("%d", tat), "PX", math.ceil(reset / per_ms))
+                end
+                return {allowed, math.floor((window - (tat - now)) / interval), math.ceil(reset / per_ms)}
+            "#
+            }
+        }
+    }
+}
+
+/// What `RateLimiter::hit` answers while Redis is unreachable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitFallback {
+    /// count in this instance's memory (GCRA); limits apply per instance
+    #[default]
+    Memory,
+    /// let every request through
+    Open,
+    /// deny every request
+    Closed,
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    pool: Pool,
+    fallback: RateLimitFallback,
+    memory: MemoryLimiter,
+}
+
+/// Outcome of one `RateLimiter::hit`.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitStatus {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// allowed: until the full limit is available again (sliding window:
+    /// until the oldest hit leaves the window); denied: until a retry can pass
+    pub reset_ms: u64,
+}
+
+impl RateLimiter {
+    pub fn new(pool: Pool, fallback: RateLimitFallback) -> Self {
+        Self {
+            pool,
+            fallback,
+            memory: MemoryLimiter::default(),
+        }
+    }
+
+    /// Counts the hit only when it is allowed. Redis errors are logged and
+    /// answered by the configured fallback.
+    pub async fn hit(
+        &self,
+        algorithm: RateLimitAlgorithm,
+        key: &str,
+        limit: u32,
+        window_ms: u64,
+    ) -> RateLimitStatus {
+        let now_ms = now_ms();
+        match self
+            .redis_hit(algorithm, key, limit, window_ms, now_ms)
+            .await
+        {
+            Ok(status) => status,
+            Err(e) => {
+                tracing::error!(
+                    key,
+                    "rate limiter, falling back to {:?}: {e}",
+                    self.fallback
+                );
+                match self.fallback {
+                    RateLimitFallback::Memory => self.memory.hit(key, limit, window_ms, now_ms),
+                    RateLimitFallback::Open => RateLimitStatus {
+                        allowed: true,
+                        limit,
+                        remaining: limit,
+                        reset_ms: 0,
+                    },
+                    RateLimitFallback::Closed => RateLimitStatus {
+                        allowed: false,
+                        limit,
+                        remaining: 0,
+                        reset_ms: window_ms,
+                    },
+                }
+            }
+        }
+    }
+
+    async fn redis_hit(
+        &self,
+        algorithm: RateLimitAlgorithm,
+        key: &str,
+        limit: u32,
+        window_ms: u64,
+        now_ms: u64,
+    ) -> Result<RateLimitStatus, RedisError> {
+        let mut conn = self.pool.get().await.map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::IoError,
+                "deadpool get failed",
+                e.to_string(),
+            ))
+        })?;
+
+        // sliding window members must be unique, or hits in the same millisecond count once
+        let member = format!("{}-{}", now_ms, OsRng.next_u32());
+
+        let (allowed, remaining, reset_ms): (i64, i64, i64) = redis::cmd("EVAL")
+            .arg(algorithm.script()) // the script
+            .arg(1) // number of keys
+            .arg(key) // KEYS[1]
+            .arg(now_ms) // ARGV[1]
+            .arg(window_ms) // ARGV[2]
+            .arg(limit) // ARGV[3]
+            .arg(member) // ARGV[4]
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(RateLimitStatus {
+            allowed: allowed == 1,
+            limit,
+            remaining: remaining.clamp(0, limit as i64) as u32,
+            reset_ms: reset_ms.max(0) as u64,
+        })
+    }
+}
+
+/// Per-instance GCRA: the theoretical arrival time of every key, in units
+/// of 1/limit ms since `GCRA_EPOCH_MS` so every step is an exact integer.
+/// Those units differ between limits, so each entry also keeps the ms at
+/// which it stops mattering, and the sweep compares that.
+#[derive(Clone, Default)]
+pub struct MemoryLimiter {
+    tats: Arc<Mutex<HashMap<String, MemoryTat>>>,
+}
+
+#[derive(Clone, Copy)]
+struct MemoryTat {
+    tat: u128,
+    /// ms since `GCRA_EPOCH_MS` when `tat` has passed
+    expires_ms: u64,
+}
+
+impl MemoryLimiter {
+    pub fn hit(&self, key: &str, limit: u32, window_ms: u64, now_ms: u64) -> RateLimitStatus {
+        let per_ms = limit.max(1) as u128;
+        let interval = window_ms as u128;
+        let now_ms = now_ms.saturating_sub(GCRA_EPOCH_MS);
+        let (now, window) = (now_ms as u128 * per_ms, interval * per_ms);
+
+        let mut tats = self.tats.lock().unwrap_or_else(|e| e.into_inner());
+        if tats.len() >= MEMORY_MAX_KEYS {
+            tats.retain(|_, entry| entry.expires_ms > now_ms);
+        }
+        let stored = tats.get(key).map_or(0, |entry| entry.tat);
+        let mut tat = stored.clamp(now, now + window);
+        let allowed = tat + interval - now <= window;
+        let reset = if allowed {
+            tat += interval;
+            let expires_ms = tat.div_ceil(per_ms) as u64;
+            tats.insert(key.to_string(), MemoryTat { tat, expires_ms });
+            tat - now
+        } else {
+            tat + interval - now - window
+        };
+
+        RateLimitStatus {
+            allowed,
+            limit,
+            remaining: ((window - (tat - now)) / interval) as u32,
+            reset_ms: reset.div_ceil(per_ms) as u64,
+        }
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gcra_script_uses_the_epoch() {
+        let script = RateLimitAlgorithm::Gcra.script();
+        assert!(script.contains(&format!("(now_ms - {GCRA_EPOCH_MS}) * per_ms")));
+    }
+
+    #[test]
+    fn gcra_stays_exact_for_decades() {
+        // 2080-01-01T00:00:00Z at the largest reduced limit `supports` accepts
+        let units = (3_471_292_800_000 - GCRA_EPOCH_MS) * 5_000;
+        assert!(units < 1 << 53);
+        assert!(RateLimitAlgorithm::Gcra.supports(5_000, 1_000));
+        assert!(!RateLimitAlgorithm::Gcra.supports(5_001, 1_000));
+        assert!(RateLimitAlgorithm::Gcra.supports(10_000, 2_000));
+    }
+
+    const T: u64 = GCRA_EPOCH_MS + 1_000_000;
+
+    #[test]
+    fn memory_allows_the_limit_then_denies_until_a_slot_frees() {
+        let limiter = MemoryLimiter::default();
+        let remaining: Vec<u32> = (0..3)
+            .map(|_| limiter.hit("k", 3, 3_000, T))
+            .inspect(|s| assert!(s.allowed))
+            .map(|s| s.remaining)
+            .collect();
+        assert_eq!(remaining, [2, 1, 0]);
+
+        let denied = limiter.hit("k", 3, 3_000, T);
+        assert!(!denied.allowed);
+        assert_eq!((denied.remaining, denied.reset_ms), (0, 1_000));
+        assert!(!limiter.hit("k", 3, 3_000, T + 999).allowed);
+    }
+
+    #[test]
+    fn memory_refills_evenly_over_the_window() {
+        let limiter = MemoryLimiter::default();
+        for _ in 0..3 {
+            limiter.hit("k", 3, 3_000, T);
+        }
+        assert!(limiter.hit("k", 3, 3_000, T + 1_000).allowed);
+        assert!(!limiter.hit("k", 3, 3_000, T + 1_000).allowed);
+
+        let full = T + 10_000;
+        assert!((0..3).all(|_| limiter.hit("k", 3, 3_000, full).allowed));
+        assert!(!limiter.hit("k", 3, 3_000, full).allowed);
+    }
+
+    #[test]
+    fn memory_keys_with_different_limits_are_independent() {
+        let limiter = MemoryLimiter::default();
+        assert!(limiter.hit("slow", 1, 60_000, T).allowed);
+        assert!(!limiter.hit("slow", 1, 60_000, T).allowed);
+        assert!((0..100).all(|_| limiter.hit("fast", 100, 1_000, T).allowed));
+        assert!(!limiter.hit("slow", 1, 60_000, T + 1).allowed);
+    }
+
+    #[test]
+    fn memory_sweep_keeps_live_keys_of_every_limit() {
+        let limiter = MemoryLimiter::default();
+        assert!(limiter.hit("slow", 1, 60_000, T).allowed);
+        for i in 0..MEMORY_MAX_KEYS {
+            limiter.hit(&format!("filler-{i}"), 1, 60_000, T);
+        }
+
+        // a high limit counts in finer units; its sweep must not drop "slow"
+        assert!(limiter.hit("fast", 1_000, 1_000, T + 1_000).allowed);
+        assert!(!limiter.hit("slow", 1, 60_000, T + 1_000).allowed);
+
+        // once the window is over, the sweep frees everything
+        limiter.hit("fast", 1_000, 1_000, T + 60_000);
+        let kept = limiter.tats.lock().unwrap().len();
+        assert!(kept <= 2, "{kept} entries survived the sweep");
+    }
+}
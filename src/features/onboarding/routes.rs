@@ -1,5 +1,3 @@
-use std::u64;
-
 use actix_web::{
     cookie::{Cookie, SameSite},
     post, web, HttpRequest, HttpResponse, Responder,
@@ -10,12 +8,11 @@ use validator::Validate;
 use crate::features::{
     clients::EmailClient,
     onboarding::{
-        get_client_ip, ip_to_bucket, parse_ip, sha256_hex,
         types::{
-            AppState, EmailVerificationReq, PreparationReq, PreparationResp, UserDetailsResp,
-            WithEmailReq, WithEmailResp,
+            EmailVerificationReq, PreparationReq, PreparationResp, UserDetailsResp, WithEmailReq,
+            WithEmailResp,
         },
-        OnboardingService, EMAIL_PREFIX, INSTALL_PREFIX, IP_PREFIX, VISITOR_PREFIX,
+        OnboardingService,
     },
     users::types::UserDetailsReq,
 };
@@ -40,7 +37,6 @@ pub const COOKIE_USER_ID: &str = "__Host-user_id";
 pub async fn preparation(
     req: HttpRequest,
     payload: web::Json<PreparationReq>,
-    state: web::Data<AppState>,
     onboarding_service: web::Data<OnboardingService>,
 ) -> actix_web::Result<impl Responder> {
     // 1) visitor cookie
@@ -50,58 +46,7 @@ pub async fn preparation(
 
     print!("{:?}", payload.extra_data);
 
-    // 2) pick IP (header or peer) — if payload.ip is present, you can prefer server-detected IP instead
-    let ip = get_client_ip(&req).or_else(|| payload.ip.as_ref().and_then(|s| parse_ip(s)));
-    let ip_bucket = ip.as_ref().map(ip_to_bucket);
-
-    // 3) install id from payload
-    let install_id = &payload.extra_data.install_id;
-
-    let k_visitor = format!("{}{}", VISITOR_PREFIX, sha256_hex(&visitor_id));
-    let k_install = format!("{}{}", INSTALL_PREFIX, sha256_hex(install_id));
-    let k_ip = ip_bucket
-        .as_ref()
-        .map(|b| format!("{}{}", IP_PREFIX, sha256_hex(b)));
-
-    // 5) Apply rate limits (simple sequential calls)
-    // tune as you like
-    let window_ms = 60_000u64;
-    let limit_cookie_install = 10u32; // per 60s
-    let limit_ip = 100u32; // per 60s
-
-    let limiter = state.limiter.lock().await;
-
-    // cookie
-    if limiter
-        .hit(&k_visitor, limit_cookie_install, window_ms)
-        .await
-        .unwrap_or(u64::MAX)
-        >= limit_cookie_install as u64
-    {
-        return Ok(HttpResponse::TooManyRequests().finish());
-    }
-
-    // install
-    if limiter
-        .hit(&k_install, limit_cookie_install, window_ms)
-        .await
-        .unwrap_or(u64::MAX)
-        >= limit_cookie_install as u64
-    {
-        return Ok(HttpResponse::TooManyRequests().finish());
-    }
-
-    // ip (if present)
-    if let Some(k) = &k_ip {
-        if limiter
-            .hit(k, limit_ip, window_ms)
-            .await
-            .unwrap_or(u64::MAX)
-            >= limit_ip as u64
-        {
-            return Ok(HttpResponse::TooManyRequests().finish());
-        }
-    }
+    // rate limits per visitor, install id and IP bucket: see `RateLimit` policies
 
     // Ensure device exists or create one
     let device = onboarding_service
@@ -115,7 +60,7 @@ pub async fn preparation(
         .path("/") // required for __Host- prefix (and do not set Domain)
         .finish();
 
-    // 6) Build response
+    // 2) Build response
     let mut resp = HttpResponse::Ok();
     if let Some(value) = maybe_value {
         let cookie = Cookie::build(COOKIE_VISITOR, value)
@@ -149,7 +94,6 @@ pub async fn preparation(
 pub async fn with_email(
     req: HttpRequest,
     payload: web::Json<WithEmailReq>,
-    state: web::Data<AppState>,
     onboarding_service: web::Data<OnboardingService>,
     email_client: web::Data<EmailClient>,
 ) -> actix_web::Result<impl Responder> {
@@ -163,23 +107,7 @@ pub async fn with_email(
         None => return Ok(HttpResponse::Forbidden().finish()),
     };
 
-    let limiter = state.limiter.lock().await;
-
     let email = &payload.email;
-    let k_email = format!("{}{}", EMAIL_PREFIX, sha256_hex(email));
-    let window_ms = 60_000u64;
-    let limit_email = 3u32;
-
-    // 3) check email sending rate limits
-    if limiter
-        .hit(&k_email, limit_email, window_ms)
-        .await
-        .unwrap_or(u64::MAX) // defaults to u64::MAX if I receive a RedisError
-        >= limit_email as u64
-    {
-        return Ok(HttpResponse::TooManyRequests().finish());
-    }
-
     let cookie_value = onboarding_service.send_otp(email, &email_client).await?;
     let cookie = Cookie::build(COOKIE_WITH_EMAIL, cookie_value)
         .http_only(true)
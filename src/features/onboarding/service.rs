@@ -1,6 +1,4 @@
 use actix_web::cookie::Cookie;
-use argon2::password_hash::{PasswordHasher, SaltString};
-use argon2::Argon2;
 use chrono::Datelike;
 use deadpool_redis::Pool;
 use password_hash::rand_core::{OsRng, RngCore};
@@ -11,8 +9,9 @@ use crate::{
     features::{
         clients::EmailClient,
         devices::{types::CreateDeviceDto, Device, DeviceRepository},
+        identities::{IdentityKind, IdentityRepository},
         onboarding::types::PreparationReq,
-        users::{types::CreateUserDto, LoginMethod, UserRepository},
+        users::{types::CreateUserDto, PasswordHashing, PasswordPolicyService, UserRepository},
     },
     utils::{
         crypto::ClientHMAC,
@@ -21,10 +20,6 @@ use crate::{
 };
 
 /// region Redis prefixes
-pub const VISITOR_PREFIX: &str = "rl:prep:v1:visitor:";
-pub const INSTALL_PREFIX: &str = "rl:prep:v1:install:";
-pub const IP_PREFIX: &str = "rl:prep:v1:ip:";
-pub const EMAIL_PREFIX: &str = "rl:email:";
 pub const OTP_PREFIX: &str = "otp:with_email:v1:";
 /// endregion Redis prefixes
 
@@ -34,21 +29,33 @@ pub struct OnboardingService {
     redis_pool: Pool,
     device_repo: DeviceRepository,
     user_repo: UserRepository,
+    identity_repo: IdentityRepository,
     pool: PgPool,
+    password_policy: PasswordPolicyService,
+    password_hashing: PasswordHashing,
 }
 
 impl OnboardingService {
-    pub fn new(hmac_client: ClientHMAC, pool: PgPool, redis_pool: Pool) -> Self {
+    pub fn new(
+        hmac_client: ClientHMAC,
+        pool: PgPool,
+        redis_pool: Pool,
+        password_policy: PasswordPolicyService,
+        password_hashing: PasswordHashing,
+    ) -> Self {
         Self {
             hmac_client,
             redis_pool,
             device_repo: DeviceRepository::new(pool.clone()),
             user_repo: UserRepository::new(pool.clone()),
+            identity_repo: IdentityRepository::new(pool.clone()),
             pool: pool.clone(),
+            password_policy,
+            password_hashing,
         }
     }
 
-    pub(super) fn has_valid_cookie(&self, cookie: Option<Cookie<'static>>) -> Option<String> {
+    pub(crate) fn has_valid_cookie(&self, cookie: Option<Cookie<'static>>) -> Option<String> {
         if let Some(c) = cookie {
             if let Some(id) = self.hmac_client.decode_cookie_value(c.value()) {
                 return Some(id);
@@ -241,29 +248,32 @@ impl OnboardingService {
         } else {
             // Make a username from the email local part
             let username = email.split('@').next().unwrap_or(email).to_string();
+            self.password_policy
+                .check(password, email, &username)
+                .await?;
 
             let user_dto = CreateUserDto {
                 username,
                 email: email.to_string(),
                 phone_number: None,
-                login_method: "with_password".to_string(),
             };
 
-            // Argon2 hashing
-            let salt = SaltString::generate(&mut OsRng);
-            let argon2 = Argon2::default();
-            let password_hash = argon2
-                .hash_password(password.as_bytes(), &salt)
-                .map_err(|_| Error::InvalidOtp("failed to hash password".to_string()))?
-                .to_string();
+            let password_hash = self.password_hashing.hash(password)?;
 
-            // FIX: salt is a string; convert to bytes explicitly
-            let salt_bytes: Vec<u8> = salt.as_str().as_bytes().to_vec();
-
-            self.user_repo
-                .create(user_dto, password_hash, salt_bytes)
+            let user = self
+                .user_repo
+                .create(user_dto, password_hash)
                 .await
-                .map_err(Error::from)?
+                .map_err(Error::from)?;
+
+            // the email was just verified with a code, so it can sign in too
+            self.identity_repo
+                .link(user.id, IdentityKind::Password, None, &user.id.to_string(), None)
+                .await?;
+            self.identity_repo
+                .link(user.id, IdentityKind::EmailOtp, None, &email.to_lowercase(), None)
+                .await?;
+            user
         };
 
         // 3) Is there an active device already?
@@ -1,13 +1,9 @@
 use deadpool_redis::Pool;
 use serde::{Deserialize, Serialize};
-use tokio::sync::Mutex;
 use utoipa::ToSchema;
 use validator::Validate;
 
-use crate::features::onboarding::RateLimiter;
-
 pub struct AppState {
-    pub limiter: Mutex<RateLimiter>,
     pub redis: Pool,
 }
 
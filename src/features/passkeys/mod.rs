@@ -0,0 +1,8 @@
+mod routes;
+mod service;
+pub mod types;
+mod webauthn;
+
+pub use routes::*;
+pub use service::*;
+pub use webauthn::RelyingParty;
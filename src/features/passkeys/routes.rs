@@ -0,0 +1,104 @@
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
+use validator::Validate;
+
+use crate::{
+    features::{
+        passkeys::{
+            types::{
+                AssertionReq, CreationOptionsResp, PasskeyResp, RegistrationReq, RequestOptionsResp,
+            },
+            PasskeyService,
+        },
+        users::UserService,
+    },
+    infrastructure::middlewares::auth::AuthenticatedUser,
+};
+
+#[utoipa::path(
+    post,
+    path = "/auth/passkeys/register/options",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Options for navigator.credentials.create()", body = CreationOptionsResp),
+        (status = 401, description = "Missing or invalid access token"),
+    )
+)]
+#[post("/auth/passkeys/register/options")]
+pub async fn passkey_register_options(
+    user: AuthenticatedUser,
+    user_service: web::Data<UserService>,
+    passkey_service: web::Data<PasskeyService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    let profile = user_service.profile(user.uid).await?;
+    let options = passkey_service
+        .registration_options(user.uid, user.did, &profile.email)
+        .await?;
+    Ok(HttpResponse::Ok().json(options))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/passkeys/register",
+    tag = "auth",
+    request_body = RegistrationReq,
+    responses(
+        (status = 201, description = "Passkey stored", body = PasskeyResp),
+        (status = 400, description = "Invalid or expired ceremony"),
+        (status = 409, description = "Passkey already registered"),
+    )
+)]
+#[post("/auth/passkeys/register")]
+pub async fn passkey_register(
+    user: AuthenticatedUser,
+    payload: web::Json<RegistrationReq>,
+    passkey_service: web::Data<PasskeyService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    let passkey = passkey_service.register(user.uid, &payload).await?;
+    Ok(HttpResponse::Created().json(passkey))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/passkeys/login/options",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Options for navigator.credentials.get()", body = RequestOptionsResp),
+    )
+)]
+#[post("/auth/passkeys/login/options")]
+pub async fn passkey_login_options(
+    passkey_service: web::Data<PasskeyService>,
+) -> actix_web::Result<impl Responder> {
+    let options = passkey_service.login_options().await?;
+    Ok(HttpResponse::Ok().json(options))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/passkeys/login",
+    tag = "auth",
+    request_body = AssertionReq,
+    responses(
+        (status = 200, description = "Passkey accepted, tokens issued"),
+        (status = 400, description = "Missing device cookie"),
+        (status = 401, description = "Unknown passkey, bad signature or expired ceremony"),
+    )
+)]
+#[post("/auth/passkeys/login")]
+pub async fn passkey_login(
+    req: HttpRequest,
+    payload: web::Json<AssertionReq>,
+    user_service: web::Data<UserService>,
+    passkey_service: web::Data<PasskeyService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    let outcome = passkey_service.authenticate(&payload).await?;
+    user_service.finish_verified_login(&req, outcome).await
+}
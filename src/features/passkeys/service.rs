@@ -0,0 +1,323 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use deadpool_redis::{redis, Pool};
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::{
+    features::{
+        audits::{AuditRepository, EventType, LogLevel},
+        devices::DeviceKeyRepository,
+        identities::{IdentityKind, IdentityRepository},
+        passkeys::{
+            types::{
+                AssertionReq, AuthenticatorSelection, CreationOptionsResp, CredentialDescriptor,
+                PasskeyResp, PasskeyUserEntity, PubKeyCredParam, RegistrationReq,
+                RelyingPartyEntity, RequestOptionsResp,
+            },
+            webauthn::{
+                client_challenge, decode_b64url, sign_count_advances, RelyingParty, COSE_ALG_ES256,
+            },
+        },
+        users::VerifiedLogin,
+    },
+    utils::error::{Error, Result},
+};
+
+/// How long the browser has to finish a ceremony.
+const CEREMONY_TIMEOUT_SECONDS: i64 = 5 * 60;
+
+/// region Redis prefixes
+/// challenge -> JSON `Ceremony` while a registration or login is in flight
+pub const WEBAUTHN_CHALLENGE_PREFIX: &str = "auth:webauthn:v1:challenge:";
+/// endregion Redis prefixes
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum Ceremony {
+    Register { uid: i64, did: i64 },
+    Login,
+}
+
+#[derive(Clone)]
+pub struct PasskeyService {
+    redis_pool: Pool,
+    device_key_repo: DeviceKeyRepository,
+    identity_repo: IdentityRepository,
+    audit_repo: AuditRepository,
+    rp: RelyingParty,
+}
+
+impl PasskeyService {
+    pub fn new(pool: PgPool, redis_pool: Pool, rp: RelyingParty) -> Self {
+        Self {
+            redis_pool,
+            device_key_repo: DeviceKeyRepository::new(pool.clone()),
+            identity_repo: IdentityRepository::new(pool.clone()),
+            audit_repo: AuditRepository::new(pool),
+            rp,
+        }
+    }
+
+    /// Options for `navigator.credentials.create()`. The passkey will belong
+    /// to `user_id` and be stored against the device that asked.
+    pub async fn registration_options(
+        &self,
+        user_id: i64,
+        device_id: i64,
+        user_name: &str,
+    ) -> Result<CreationOptionsResp> {
+        let challenge = self
+            .start(&Ceremony::Register {
+                uid: user_id,
+                did: device_id,
+            })
+            .await?;
+
+        let existing = self
+            .device_key_repo
+            .credential_ids_for_user(user_id)
+            .await
+            .map_err(Error::from)?;
+
+        Ok(CreationOptionsResp {
+            challenge,
+            rp: RelyingPartyEntity {
+                id: self.rp.id.clone(),
+                name: self.rp.name.clone(),
+            },
+            user: PasskeyUserEntity {
+                id: user_handle(user_id),
+                name: user_name.to_string(),
+                display_name: user_name.to_string(),
+            },
+            pub_key_cred_params: vec![PubKeyCredParam {
+                type_: "public-key".into(),
+                alg: COSE_ALG_ES256,
+            }],
+            timeout: CEREMONY_TIMEOUT_SECONDS * 1000,
+            attestation: "none".into(),
+            authenticator_selection: AuthenticatorSelection {
+                resident_key: "required".into(),
+                user_verification: "required".into(),
+            },
+            exclude_credentials: existing
+                .iter()
+                .map(|id| CredentialDescriptor {
+                    type_: "public-key".into(),
+                    id: URL_SAFE_NO_PAD.encode(id),
+                })
+                .collect(),
+        })
+    }
+
+    /// Verify the authenticator's answer, store the new passkey in `device_keys`
+    /// and link it as one of the user's identities.
+    pub async fn register(&self, user_id: i64, req: &RegistrationReq) -> Result<PasskeyResp> {
+        let client_data = decode_b64url(&req.response.client_data_json)?;
+        let challenge = client_challenge(&client_data)?;
+        let device_id = match self.finish(&challenge).await? {
+            Some(Ceremony::Register { uid, did }) if uid == user_id => did,
+            _ => return Err(Error::Validation("unknown or expired challenge".into())),
+        };
+
+        let credential = self.rp.verify_registration(
+            &client_data,
+            &decode_b64url(&req.response.attestation_object)?,
+            &challenge,
+        )?;
+
+        if self
+            .device_key_repo
+            .find_by_credential_id(&credential.credential_id)
+            .await
+            .map_err(Error::from)?
+            .is_some()
+        {
+            return Err(Error::Conflict("passkey already registered".into()));
+        }
+
+        let key = self
+            .device_key_repo
+            .create_passkey(
+                device_id,
+                user_id,
+                &credential.public_key,
+                &credential.credential_id,
+                credential.sign_count as i64,
+            )
+            .await
+            .map_err(Error::from)?;
+        let credential_id = URL_SAFE_NO_PAD.encode(&credential.credential_id);
+        self.identity_repo
+            .link(user_id, IdentityKind::Passkey, None, &credential_id, None)
+            .await?;
+
+        self.audit_repo
+            .create(user_id, EventType::PasskeyRegistered, LogLevel::Info, None)
+            .await
+            .map_err(Error::from)?;
+
+        Ok(PasskeyResp {
+            id: key.id,
+            credential_id,
+        })
+    }
+
+    /// Options for `navigator.credentials.get()`; any discoverable passkey will do.
+    pub async fn login_options(&self) -> Result<RequestOptionsResp> {
+        let challenge = self.start(&Ceremony::Login).await?;
+        Ok(RequestOptionsResp {
+            challenge,
+            rp_id: self.rp.id.clone(),
+            timeout: CEREMONY_TIMEOUT_SECONDS * 1000,
+            user_verification: "required".into(),
+            allow_credentials: Vec::new(),
+        })
+    }
+
+    /// Verify an assertion. A sign counter that does not move forward means
+    /// the authenticator may have been cloned: audited and rejected.
+    pub async fn authenticate(&self, req: &AssertionReq) -> Result<VerifiedLogin> {
+        let Ok(credential_id) = decode_b64url(&req.id) else {
+            return Ok(VerifiedLogin::Rejected(None));
+        };
+        let key = match self
+            .device_key_repo
+            .find_by_credential_id(&credential_id)
+            .await
+            .map_err(Error::from)?
+        {
+            Some(key) => key,
+            None => return Ok(VerifiedLogin::Rejected(None)),
+        };
+        let Some(user_id) = key.user_id else {
+            return Ok(VerifiedLogin::Rejected(None));
+        };
+        let rejected = Ok(VerifiedLogin::Rejected(Some(user_id)));
+        if key.revoked_at.is_some() {
+            return rejected;
+        }
+        // only while linked as one of the user's identities
+        let Some(identity) = self
+            .identity_repo
+            .find(
+                IdentityKind::Passkey,
+                None,
+                &URL_SAFE_NO_PAD.encode(&credential_id),
+            )
+            .await?
+            .filter(|i| i.user_id == user_id)
+        else {
+            return rejected;
+        };
+
+        // the user handle, when sent, must name the passkey's owner
+        if let Some(handle) = &req.response.user_handle {
+            if decode_b64url(handle).ok() != decode_b64url(&user_handle(user_id)).ok() {
+                return rejected;
+            }
+        }
+
+        let Ok(client_data) = decode_b64url(&req.response.client_data_json) else {
+            return rejected;
+        };
+        let Ok(challenge) = client_challenge(&client_data) else {
+            return rejected;
+        };
+        if !matches!(self.finish(&challenge).await?, Some(Ceremony::Login)) {
+            return rejected;
+        }
+
+        let (Ok(authenticator_data), Ok(signature)) = (
+            decode_b64url(&req.response.authenticator_data),
+            decode_b64url(&req.response.signature),
+        ) else {
+            return rejected;
+        };
+        let Ok(sign_count) = self.rp.verify_assertion(
+            &client_data,
+            &authenticator_data,
+            &signature,
+            &key.public_key,
+            &challenge,
+        ) else {
+            return rejected;
+        };
+
+        let stored = key.sign_count.unwrap_or(0);
+        let received = sign_count as i64;
+        let advanced = sign_count_advances(stored, received)
+            && (received == 0
+                || self
+                    .device_key_repo
+                    .advance_sign_count(key.id, received)
+                    .await
+                    .map_err(Error::from)?);
+        if !advanced {
+            tracing::warn!(user_id, key_id = %key.id, stored, received, "passkey sign counter did not advance");
+            self.audit_repo
+                .create(
+                    user_id,
+                    EventType::PasskeyCloneSuspected,
+                    LogLevel::Critical,
+                    None,
+                )
+                .await
+                .map_err(Error::from)?;
+            return rejected;
+        }
+
+        self.identity_repo.touch(identity.id, None).await?;
+        // `verify_assertion` insists on the UV flag, so this is two factors
+        Ok(VerifiedLogin::MultiFactor(user_id))
+    }
+
+    /// New random challenge remembered with its ceremony.
+    async fn start(&self, ceremony: &Ceremony) -> Result<String> {
+        let challenge = {
+            let mut bytes = [0u8; 32];
+            OsRng.fill_bytes(&mut bytes);
+            URL_SAFE_NO_PAD.encode(bytes)
+        };
+
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let _: () = redis::cmd("SET")
+            .arg(format!("{}{}", WEBAUTHN_CHALLENGE_PREFIX, challenge))
+            .arg(serde_json::to_string(ceremony)?)
+            .arg("EX")
+            .arg(CEREMONY_TIMEOUT_SECONDS)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        Ok(challenge)
+    }
+
+    /// Take (and forget) the ceremony of `challenge`; each challenge answers once.
+    async fn finish(&self, challenge: &str) -> Result<Option<Ceremony>> {
+        let key = format!(
+            "{}{}",
+            WEBAUTHN_CHALLENGE_PREFIX,
+            challenge.trim_end_matches('=')
+        );
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let (raw,): (Option<String>,) = redis::pipe()
+            .atomic()
+            .cmd("GET")
+            .arg(&key)
+            .cmd("DEL")
+            .arg(&key)
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        Ok(raw.and_then(|r| serde_json::from_str(&r).ok()))
+    }
+}
+
+/// WebAuthn user handle: the user id, 8 bytes big-endian, base64url.
+fn user_handle(user_id: i64) -> String {
+    URL_SAFE_NO_PAD.encode(user_id.to_be_bytes())
+}
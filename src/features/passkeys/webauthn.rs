@@ -0,0 +1,599 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::utils::error::{Error, Result};
+
+/// COSE algorithm id of ES256, the only algorithm we offer authenticators.
+pub const COSE_ALG_ES256: i64 = -7;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_DATA: u8 = 0x40;
+/// rpIdHash (32) + flags (1) + signCount (4)
+const AUTH_DATA_MIN_LEN: usize = 37;
+/// Attestation objects and COSE keys are shallow; anything deeper is hostile.
+const MAX_CBOR_DEPTH: usize = 8;
+
+/// The relying party as authenticators see it. Both ceremonies are plain
+/// functions of their inputs, so a software authenticator can drive them.
+#[derive(Clone)]
+pub struct RelyingParty {
+    /// effective domain, e.g. `example.com`
+    pub id: String,
+    pub name: String,
+    /// exact origin the browser reports, e.g. `https://app.example.com`
+    pub origin: String,
+}
+
+/// Output of a successful registration.
+pub struct NewCredential {
+    pub credential_id: Vec<u8>,
+    /// uncompressed SEC1 point, the same shape `device_keys.public_key` uses
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    type_: String,
+    challenge: String,
+    origin: String,
+}
+
+impl RelyingParty {
+    /// Registration ceremony (WebAuthn §7.1). We ask for `none` attestation,
+    /// so the statement is not checked: all we need is the public key.
+    pub fn verify_registration(
+        &self,
+        client_data_json: &[u8],
+        attestation_object: &[u8],
+        challenge: &str,
+    ) -> Result<NewCredential> {
+        self.check_client_data(client_data_json, "webauthn.create", challenge)?;
+
+        let (attestation, _) = Cbor::decode(attestation_object)?;
+        let auth_data = attestation
+            .get(&Cbor::Text("authData".into()))
+            .and_then(Cbor::as_bytes)
+            .ok_or_else(|| invalid("attestationObject without authData"))?;
+        let (flags, sign_count) = self.check_authenticator_data(auth_data)?;
+        if flags & FLAG_ATTESTED_DATA == 0 {
+            return Err(invalid("no attested credential data"));
+        }
+
+        // aaguid (16) | credentialIdLength (2) | credentialId | credentialPublicKey
+        let attested = &auth_data[AUTH_DATA_MIN_LEN..];
+        let id_len = attested
+            .get(16..18)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(|| invalid("truncated attested credential data"))?;
+        let credential_id = attested
+            .get(18..18 + id_len)
+            .ok_or_else(|| invalid("truncated credential id"))?
+            .to_vec();
+        let (cose_key, _) = Cbor::decode(&attested[18 + id_len..])?;
+
+        Ok(NewCredential {
+            credential_id,
+            public_key: es256_point(&cose_key)?,
+            sign_count,
+        })
+    }
+
+    /// Authentication ceremony (WebAuthn §7.2) against a stored public key.
+    /// Returns the authenticator's sign counter; judging it is up to the caller.
+    pub fn verify_assertion(
+        &self,
+        client_data_json: &[u8],
+        authenticator_data: &[u8],
+        signature: &[u8],
+        public_key: &[u8],
+        challenge: &str,
+    ) -> Result<u32> {
+        self.check_client_data(client_data_json, "webauthn.get", challenge)?;
+        let (_, sign_count) = self.check_authenticator_data(authenticator_data)?;
+
+        let mut signed = authenticator_data.to_vec();
+        signed.extend(Sha256::digest(client_data_json));
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, public_key)
+            .verify(&signed, signature)
+            .map_err(|_| invalid("bad signature"))?;
+
+        Ok(sign_count)
+    }
+
+    fn check_client_data(&self, json: &[u8], type_: &str, challenge: &str) -> Result<()> {
+        let data: ClientData =
+            serde_json::from_slice(json).map_err(|_| invalid("malformed clientDataJSON"))?;
+        if data.type_ != type_ {
+            return Err(invalid("wrong ceremony type"));
+        }
+        if decode_b64url(&data.challenge)? != decode_b64url(challenge)? {
+            return Err(invalid("challenge mismatch"));
+        }
+        if data.origin != self.origin {
+            return Err(invalid("origin mismatch"));
+        }
+        Ok(())
+    }
+
+    /// rpIdHash and the user present + verified flags. Returns (flags, signCount).
+    fn check_authenticator_data(&self, data: &[u8]) -> Result<(u8, u32)> {
+        if data.len() < AUTH_DATA_MIN_LEN {
+            return Err(invalid("truncated authenticator data"));
+        }
+        if data[..32] != Sha256::digest(self.id.as_bytes())[..] {
+            return Err(invalid("rp id mismatch"));
+        }
+        let flags = data[32];
+        if flags & FLAG_USER_PRESENT == 0 || flags & FLAG_USER_VERIFIED == 0 {
+            return Err(invalid("user not present or not verified"));
+        }
+        Ok((
+            flags,
+            u32::from_be_bytes([data[33], data[34], data[35], data[36]]),
+        ))
+    }
+}
+
+/// Whether a sign counter may follow the stored one. Authenticators without a
+/// counter always send 0 (WebAuthn §6.1.1); any other counter has to grow, or
+/// the authenticator may have been cloned.
+pub fn sign_count_advances(stored: i64, received: i64) -> bool {
+    (stored == 0 && received == 0) || received > stored
+}
+
+/// The challenge a client claims to answer, to find the ceremony it belongs to.
+pub fn client_challenge(client_data_json: &[u8]) -> Result<String> {
+    serde_json::from_slice::<ClientData>(client_data_json)
+        .map(|data| data.challenge)
+        .map_err(|_| invalid("malformed clientDataJSON"))
+}
+
+/// Browsers send base64url without padding; tolerate padding anyway.
+pub fn decode_b64url(value: &str) -> Result<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| invalid("invalid base64url"))
+}
+
+/// EC2 / P-256 / ES256 COSE key (RFC 9053) -> uncompressed SEC1 point.
+fn es256_point(key: &Cbor) -> Result<Vec<u8>> {
+    let int = |label: i128| key.get(&Cbor::Int(label)).and_then(Cbor::as_int);
+    let bytes = |label: i128| key.get(&Cbor::Int(label)).and_then(Cbor::as_bytes);
+
+    if int(1) != Some(2) || int(3) != Some(COSE_ALG_ES256 as i128) || int(-1) != Some(1) {
+        return Err(invalid("only ES256 P-256 keys are supported"));
+    }
+    match (bytes(-2), bytes(-3)) {
+        (Some(x), Some(y)) if x.len() == 32 && y.len() == 32 => {
+            let mut point = vec![0x04];
+            point.extend_from_slice(x);
+            point.extend_from_slice(y);
+            Ok(point)
+        }
+        _ => Err(invalid("malformed EC2 key")),
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::Validation(format!("webauthn: {reason}"))
+}
+
+/// Just enough CBOR (RFC 8949) for attestation objects and COSE keys:
+/// definite lengths only; tags are unwrapped; simple values and floats are opaque.
+#[derive(Debug, PartialEq)]
+enum Cbor {
+    Int(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Simple,
+}
+
+impl Cbor {
+    /// First item of `input` and how many bytes it took.
+    fn decode(input: &[u8]) -> Result<(Self, usize)> {
+        Self::decode_at(input, 0)
+    }
+
+    fn decode_at(input: &[u8], depth: usize) -> Result<(Self, usize)> {
+        if depth > MAX_CBOR_DEPTH {
+            return Err(invalid("cbor nested too deep"));
+        }
+        let truncated = || invalid("truncated cbor");
+
+        let initial = *input.first().ok_or_else(truncated)?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let width = match info {
+            0..=23 => 0,
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(invalid("indefinite or reserved cbor length")),
+        };
+        let arg = if width == 0 {
+            info as u64
+        } else {
+            input
+                .get(1..1 + width)
+                .ok_or_else(truncated)?
+                .iter()
+                .fold(0u64, |acc, b| (acc << 8) | *b as u64)
+        };
+        let mut pos = 1 + width;
+
+        let item = match major {
+            0 => Cbor::Int(arg as i128),
+            1 => Cbor::Int(-1 - arg as i128),
+            2 | 3 => {
+                let end = usize::try_from(arg)
+                    .ok()
+                    .and_then(|len| pos.checked_add(len))
+                    .ok_or_else(truncated)?;
+                let raw = input.get(pos..end).ok_or_else(truncated)?.to_vec();
+                pos = end;
+                if major == 2 {
+                    Cbor::Bytes(raw)
+                } else {
+                    Cbor::Text(String::from_utf8(raw).map_err(|_| invalid("cbor text not utf-8"))?)
+                }
+            }
+            4 => {
+                let mut items = Vec::new();
+                for _ in 0..arg {
+                    let (item, used) = Self::decode_at(&input[pos..], depth + 1)?;
+                    items.push(item);
+                    pos += used;
+                }
+                Cbor::Array(items)
+            }
+            5 => {
+                let mut entries = Vec::new();
+                for _ in 0..arg {
+                    let (key, used) = Self::decode_at(&input[pos..], depth + 1)?;
+                    pos += used;
+                    let (value, used) = Self::decode_at(&input[pos..], depth + 1)?;
+                    pos += used;
+                    entries.push((key, value));
+                }
+                Cbor::Map(entries)
+            }
+            6 => {
+                let (inner, used) = Self::decode_at(&input[pos..], depth + 1)?;
+                return Ok((inner, pos + used));
+            }
+            _ => Cbor::Simple,
+        };
+        Ok((item, pos))
+    }
+
+    fn get(&self, key: &Cbor) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Cbor::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    fn as_int(&self) -> Option<i128> {
+        match self {
+            Cbor::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+    };
+
+    use super::*;
+
+    const RP_ID: &str = "example.com";
+    const ORIGIN: &str = "https://app.example.com";
+    const CHALLENGE: &str = "c2VydmVyIGNoYWxsZW5nZQ";
+    const CREDENTIAL_ID: &[u8] = b"software-credential";
+
+    fn rp() -> RelyingParty {
+        RelyingParty {
+            id: RP_ID.into(),
+            name: "Forest Gate".into(),
+            origin: ORIGIN.into(),
+        }
+    }
+
+    /// A P-256 software authenticator that answers both ceremonies.
+    struct Authenticator {
+        pair: EcdsaKeyPair,
+        rp_id: String,
+        flags: u8,
+        sign_count: u32,
+    }
+
+    impl Authenticator {
+        fn new() -> Self {
+            let rng = SystemRandom::new();
+            let pkcs8 =
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+            Self {
+                pair: EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_ASN1_SIGNING,
+                    pkcs8.as_ref(),
+                    &rng,
+                )
+                .unwrap(),
+                rp_id: RP_ID.into(),
+                flags: FLAG_USER_PRESENT | FLAG_USER_VERIFIED,
+                sign_count: 1,
+            }
+        }
+
+        fn public_key(&self) -> &[u8] {
+            self.pair.public_key().as_ref()
+        }
+
+        fn auth_data(&self, flags: u8) -> Vec<u8> {
+            let mut data = Sha256::digest(self.rp_id.as_bytes()).to_vec();
+            data.push(flags);
+            data.extend(self.sign_count.to_be_bytes());
+            data
+        }
+
+        fn cose_key(&self) -> Vec<u8> {
+            let point = self.public_key();
+            map(vec![
+                (int(1), int(2)),
+                (int(3), int(COSE_ALG_ES256)),
+                (int(-1), int(1)),
+                (int(-2), bytes(&point[1..33])),
+                (int(-3), bytes(&point[33..65])),
+            ])
+        }
+
+        /// `none` attestation object for a new credential.
+        fn attestation_object(&self) -> Vec<u8> {
+            let mut auth_data = self.auth_data(self.flags | FLAG_ATTESTED_DATA);
+            auth_data.extend([0u8; 16]); // aaguid
+            auth_data.extend((CREDENTIAL_ID.len() as u16).to_be_bytes());
+            auth_data.extend(CREDENTIAL_ID);
+            auth_data.extend(self.cose_key());
+            map(vec![
+                (text("fmt"), text("none")),
+                (text("attStmt"), map(Vec::new())),
+                (text("authData"), bytes(&auth_data)),
+            ])
+        }
+
+        /// (authenticatorData, signature) over `client_data_json`.
+        fn assert(&self, client_data_json: &[u8]) -> (Vec<u8>, Vec<u8>) {
+            let auth_data = self.auth_data(self.flags);
+            let mut signed = auth_data.clone();
+            signed.extend(Sha256::digest(client_data_json));
+            let signature = self.pair.sign(&SystemRandom::new(), &signed).unwrap();
+            (auth_data, signature.as_ref().to_vec())
+        }
+    }
+
+    fn client_data(type_: &str, challenge: &str, origin: &str) -> Vec<u8> {
+        serde_json::json!({ "type": type_, "challenge": challenge, "origin": origin })
+            .to_string()
+            .into_bytes()
+    }
+
+    // region minimal CBOR encoder
+    fn head(major: u8, len: u64) -> Vec<u8> {
+        match len {
+            0..=23 => vec![major << 5 | len as u8],
+            24..=0xff => vec![major << 5 | 24, len as u8],
+            _ => {
+                let mut out = vec![major << 5 | 25];
+                out.extend((len as u16).to_be_bytes());
+                out
+            }
+        }
+    }
+
+    fn int(value: i64) -> Vec<u8> {
+        match value {
+            0.. => head(0, value as u64),
+            _ => head(1, (-1 - value) as u64),
+        }
+    }
+
+    fn bytes(value: &[u8]) -> Vec<u8> {
+        let mut out = head(2, value.len() as u64);
+        out.extend(value);
+        out
+    }
+
+    fn text(value: &str) -> Vec<u8> {
+        let mut out = head(3, value.len() as u64);
+        out.extend(value.as_bytes());
+        out
+    }
+
+    fn map(entries: Vec<(Vec<u8>, Vec<u8>)>) -> Vec<u8> {
+        let mut out = head(5, entries.len() as u64);
+        for (key, value) in entries {
+            out.extend(key);
+            out.extend(value);
+        }
+        out
+    }
+    // endregion minimal CBOR encoder
+
+    fn register(authenticator: &Authenticator, client_data_json: &[u8]) -> Result<NewCredential> {
+        rp().verify_registration(
+            client_data_json,
+            &authenticator.attestation_object(),
+            CHALLENGE,
+        )
+    }
+
+    fn login(authenticator: &Authenticator, client_data_json: &[u8]) -> Result<u32> {
+        let (auth_data, signature) = authenticator.assert(client_data_json);
+        rp().verify_assertion(
+            client_data_json,
+            &auth_data,
+            &signature,
+            authenticator.public_key(),
+            CHALLENGE,
+        )
+    }
+
+    #[test]
+    fn registration_yields_the_authenticators_key() {
+        let authenticator = Authenticator::new();
+        let credential = register(
+            &authenticator,
+            &client_data("webauthn.create", CHALLENGE, ORIGIN),
+        )
+        .unwrap();
+
+        assert_eq!(credential.credential_id, CREDENTIAL_ID);
+        assert_eq!(credential.public_key, authenticator.public_key());
+        assert_eq!(credential.sign_count, 1);
+    }
+
+    #[test]
+    fn registration_rejects_a_foreign_ceremony() {
+        let authenticator = Authenticator::new();
+        for client_data_json in [
+            client_data("webauthn.get", CHALLENGE, ORIGIN),
+            client_data("webauthn.create", "b3RoZXIgY2hhbGxlbmdl", ORIGIN),
+            client_data("webauthn.create", CHALLENGE, "https://evil.example"),
+        ] {
+            assert!(register(&authenticator, &client_data_json).is_err());
+        }
+    }
+
+    #[test]
+    fn registration_rejects_another_rp_id() {
+        let mut authenticator = Authenticator::new();
+        authenticator.rp_id = "evil.example".into();
+        let client_data_json = client_data("webauthn.create", CHALLENGE, ORIGIN);
+        assert!(register(&authenticator, &client_data_json).is_err());
+    }
+
+    #[test]
+    fn registration_requires_user_verification() {
+        let mut authenticator = Authenticator::new();
+        authenticator.flags = FLAG_USER_PRESENT;
+        let client_data_json = client_data("webauthn.create", CHALLENGE, ORIGIN);
+        assert!(register(&authenticator, &client_data_json).is_err());
+    }
+
+    #[test]
+    fn assertion_returns_the_sign_counter() {
+        let mut authenticator = Authenticator::new();
+        authenticator.sign_count = 42;
+        let client_data_json = client_data("webauthn.get", CHALLENGE, ORIGIN);
+        assert_eq!(login(&authenticator, &client_data_json).unwrap(), 42);
+    }
+
+    #[test]
+    fn assertion_rejects_mismatches() {
+        let authenticator = Authenticator::new();
+        for client_data_json in [
+            client_data("webauthn.create", CHALLENGE, ORIGIN),
+            client_data("webauthn.get", "b3RoZXIgY2hhbGxlbmdl", ORIGIN),
+            client_data("webauthn.get", CHALLENGE, "https://evil.example"),
+        ] {
+            assert!(login(&authenticator, &client_data_json).is_err());
+        }
+
+        let mut elsewhere = Authenticator::new();
+        elsewhere.rp_id = "evil.example".into();
+        let client_data_json = client_data("webauthn.get", CHALLENGE, ORIGIN);
+        assert!(login(&elsewhere, &client_data_json).is_err());
+    }
+
+    #[test]
+    fn assertion_requires_user_verification() {
+        let mut authenticator = Authenticator::new();
+        authenticator.flags = FLAG_USER_PRESENT;
+        let client_data_json = client_data("webauthn.get", CHALLENGE, ORIGIN);
+        assert!(login(&authenticator, &client_data_json).is_err());
+    }
+
+    #[test]
+    fn assertion_rejects_another_key() {
+        let authenticator = Authenticator::new();
+        let other = Authenticator::new();
+        let client_data_json = client_data("webauthn.get", CHALLENGE, ORIGIN);
+        let (auth_data, signature) = authenticator.assert(&client_data_json);
+
+        let verified = rp().verify_assertion(
+            &client_data_json,
+            &auth_data,
+            &signature,
+            other.public_key(),
+            CHALLENGE,
+        );
+        assert!(verified.is_err());
+    }
+
+    #[test]
+    fn sign_counter_must_advance() {
+        assert!(sign_count_advances(0, 0));
+        assert!(sign_count_advances(0, 1));
+        assert!(sign_count_advances(7, 8));
+        assert!(!sign_count_advances(7, 7));
+        assert!(!sign_count_advances(7, 3));
+        assert!(!sign_count_advances(7, 0));
+    }
+
+    #[test]
+    fn cbor_rejects_truncated_input() {
+        let attestation = Authenticator::new().attestation_object();
+        for len in 0..attestation.len() {
+            assert!(
+                Cbor::decode(&attestation[..len]).is_err(),
+                "prefix of {len} bytes"
+            );
+        }
+        // a length that does not fit the input at all
+        assert!(Cbor::decode(&[0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+    }
+
+    #[test]
+    fn cbor_rejects_deep_nesting() {
+        let nested = |depth: usize| {
+            let mut input = vec![0x81; depth]; // [[[...
+            input.push(0x00);
+            input
+        };
+        assert!(Cbor::decode(&nested(MAX_CBOR_DEPTH)).is_ok());
+        assert!(Cbor::decode(&nested(MAX_CBOR_DEPTH + 1)).is_err());
+        assert!(Cbor::decode(&nested(10_000)).is_err());
+    }
+
+    #[test]
+    fn registration_rejects_truncated_attestation() {
+        let authenticator = Authenticator::new();
+        let attestation = authenticator.attestation_object();
+        let client_data_json = client_data("webauthn.create", CHALLENGE, ORIGIN);
+        assert!(rp()
+            .verify_registration(
+                &client_data_json,
+                &attestation[..attestation.len() - 1],
+                CHALLENGE
+            )
+            .is_err());
+    }
+}
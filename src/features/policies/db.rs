@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, types::Json, Type};
+use utoipa::ToSchema;
+
+use super::types::Condition;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize, ToSchema)]
+#[sqlx(type_name = "policy_effect_enum", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+/// Row of `access_policies`.
+#[derive(FromRow, Clone)]
+pub struct AccessPolicy {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub action: String,
+    pub effect: Effect,
+    pub conditions: Json<Vec<Condition>>,
+    pub enabled: bool,
+}
@@ -0,0 +1,112 @@
+use super::{
+    types::{AccessContext, Condition, ConditionTrace, Decision, PolicyDto, PolicyTrace},
+    Effect,
+};
+
+/// Deny overrides: any applying deny policy wins, otherwise any applying
+/// allow policy, otherwise the action is denied.
+pub fn decide(policies: &[PolicyDto], action: &str, context: AccessContext) -> Decision {
+    let trace: Vec<PolicyTrace> = policies
+        .iter()
+        .filter(|p| p.enabled && action_matches(&p.action, action))
+        .map(|p| {
+            let conditions: Vec<ConditionTrace> =
+                p.conditions.iter().map(|c| check(c, &context)).collect();
+            PolicyTrace {
+                policy: p.name.clone(),
+                effect: p.effect,
+                applies: conditions.iter().all(|c| c.passed),
+                conditions,
+            }
+        })
+        .collect();
+
+    let settled_by = |effect| trace.iter().find(|t| t.applies && t.effect == effect);
+    let (allowed, policy, reason) = match (settled_by(Effect::Deny), settled_by(Effect::Allow)) {
+        (Some(deny), _) => (
+            false,
+            Some(deny.policy.clone()),
+            format!("denied by policy {}", deny.policy),
+        ),
+        (None, Some(allow)) => (
+            true,
+            Some(allow.policy.clone()),
+            format!("allowed by policy {}", allow.policy),
+        ),
+        (None, None) => (false, None, format!("no policy allows {action}")),
+    };
+
+    Decision {
+        action: action.to_string(),
+        allowed,
+        policy,
+        reason,
+        context,
+        trace,
+    }
+}
+
+/// `*` matches everything, `audit_events:*` every `audit_events:` action.
+pub fn action_matches(pattern: &str, action: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => action.starts_with(prefix),
+        None => pattern == action,
+    }
+}
+
+fn check(condition: &Condition, ctx: &AccessContext) -> ConditionTrace {
+    let unknown = || "unknown".to_string();
+    let (passed, actual) = match condition {
+        Condition::RoleIn { roles } => (
+            ctx.subject.roles.iter().any(|r| roles.contains(r)),
+            format!("[{}]", ctx.subject.roles.join(", ")),
+        ),
+        Condition::UserIn { users } => (
+            users.contains(&ctx.subject.uid),
+            ctx.subject.uid.to_string(),
+        ),
+        Condition::DeviceStatusIn { statuses } => (
+            ctx.subject
+                .device_status
+                .as_ref()
+                .is_some_and(|s| statuses.contains(s)),
+            ctx.subject.device_status.clone().unwrap_or_else(unknown),
+        ),
+        Condition::CountryIn { countries } => (
+            ctx.request
+                .country
+                .as_ref()
+                .is_some_and(|c| countries.contains(c)),
+            ctx.request.country.clone().unwrap_or_else(unknown),
+        ),
+        Condition::CountryNotIn { countries } => (
+            ctx.request
+                .country
+                .as_ref()
+                .is_none_or(|c| !countries.contains(c)),
+            ctx.request.country.clone().unwrap_or_else(unknown),
+        ),
+        Condition::HourBetween { from, to } => {
+            let hour = ctx.request.hour;
+            let passed = if from <= to {
+                (*from..*to).contains(&hour)
+            } else {
+                hour >= *from || hour < *to
+            };
+            (passed, format!("{hour:02}:00 UTC"))
+        }
+        Condition::IsOwner => (
+            ctx.resource.owner == Some(ctx.subject.uid),
+            match ctx.resource.owner {
+                Some(owner) => format!("owner {owner}, caller {}", ctx.subject.uid),
+                None => "no owner".to_string(),
+            },
+        ),
+    };
+
+    ConditionTrace {
+        condition: condition.clone(),
+        passed,
+        actual,
+    }
+}
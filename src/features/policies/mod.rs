@@ -0,0 +1,12 @@
+mod db;
+mod engine;
+mod repo;
+mod routes;
+mod service;
+pub mod types;
+
+pub(super) use db::*;
+pub use engine::*;
+pub use repo::*;
+pub use routes::*;
+pub use service::*;
@@ -0,0 +1,92 @@
+use sqlx::{types::Json, PgPool};
+
+use super::{types::PolicyReq, AccessPolicy};
+
+const COLUMNS: &str = "id, name, description, action, effect, conditions, enabled";
+
+#[derive(Clone)]
+pub struct PolicyRepository {
+    pool: PgPool,
+}
+
+impl PolicyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn all(&self) -> sqlx::Result<Vec<AccessPolicy>> {
+        sqlx::query_as::<_, AccessPolicy>(&format!(
+            "SELECT {COLUMNS} FROM access_policies ORDER BY name"
+        ))
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn find_by_name(&self, name: &str) -> sqlx::Result<Option<AccessPolicy>> {
+        sqlx::query_as::<_, AccessPolicy>(&format!(
+            "SELECT {COLUMNS} FROM access_policies WHERE name = $1"
+        ))
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// `None` if the name is taken.
+    pub async fn create(&self, req: &PolicyReq) -> sqlx::Result<Option<AccessPolicy>> {
+        sqlx::query_as::<_, AccessPolicy>(&format!(
+            r#"
+            INSERT INTO access_policies (name, description, action, effect, conditions, enabled)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (name) DO NOTHING
+            RETURNING {COLUMNS}
+            "#
+        ))
+        .bind(&req.name)
+        .bind(&req.description)
+        .bind(&req.action)
+        .bind(req.effect)
+        .bind(Json(&req.conditions))
+        .bind(req.enabled)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn update(&self, id: i32, req: &PolicyReq) -> sqlx::Result<Option<AccessPolicy>> {
+        sqlx::query_as::<_, AccessPolicy>(&format!(
+            r#"
+            UPDATE access_policies
+            SET name = $2, description = $3, action = $4, effect = $5,
+                conditions = $6, enabled = $7, updated_at = now()
+            WHERE id = $1
+            RETURNING {COLUMNS}
+            "#
+        ))
+        .bind(id)
+        .bind(&req.name)
+        .bind(&req.description)
+        .bind(&req.action)
+        .bind(req.effect)
+        .bind(Json(&req.conditions))
+        .bind(req.enabled)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn delete(&self, id: i32) -> sqlx::Result<bool> {
+        let res = sqlx::query("DELETE FROM access_policies WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// `active`, `inactive` or `blocked`; `None` for unknown or deleted devices.
+    pub async fn device_status(&self, device_id: i64) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar::<_, String>(
+            "SELECT device_status::text FROM devices WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(device_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,147 @@
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
+use validator::Validate;
+
+use crate::{
+    features::{
+        onboarding::get_client_ip,
+        policies::{
+            types::{Decision, ExplainReq, PolicyDto, PolicyReq},
+            PolicyService,
+        },
+    },
+    infrastructure::middlewares::{admin_auth::require_role, auth::AuthenticatedUser},
+};
+
+#[utoipa::path(
+    get,
+    path = "/admin/policies",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Every access policy, by name", body = [PolicyDto]),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+    )
+)]
+#[get("/admin/policies", wrap = "require_role(\"admin\")")]
+pub async fn list_policies(
+    policy_service: web::Data<PolicyService>,
+) -> actix_web::Result<impl Responder> {
+    Ok(HttpResponse::Ok().json(policy_service.list().await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/policies",
+    tag = "admin",
+    request_body = PolicyReq,
+    responses(
+        (status = 201, description = "Policy created, in effect right away", body = PolicyDto),
+        (status = 400, description = "Invalid action or condition"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 409, description = "Name already taken"),
+    )
+)]
+#[post("/admin/policies", wrap = "require_role(\"admin\")")]
+pub async fn create_policy(
+    admin: AuthenticatedUser,
+    payload: web::Json<PolicyReq>,
+    policy_service: web::Data<PolicyService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    tracing::info!(admin_id = admin.uid, name = %payload.name, "creating access policy");
+
+    let policy = policy_service.create(payload.into_inner()).await?;
+    Ok(HttpResponse::Created().json(policy))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/policies/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Policy id")),
+    request_body = PolicyReq,
+    responses(
+        (status = 200, description = "Policy replaced, in effect right away", body = PolicyDto),
+        (status = 400, description = "Invalid action or condition"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "No such policy"),
+        (status = 409, description = "Name already taken"),
+    )
+)]
+#[put("/admin/policies/{id}", wrap = "require_role(\"admin\")")]
+pub async fn update_policy(
+    admin: AuthenticatedUser,
+    path: web::Path<i32>,
+    payload: web::Json<PolicyReq>,
+    policy_service: web::Data<PolicyService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    let policy_id = path.into_inner();
+    tracing::info!(admin_id = admin.uid, policy_id, "updating access policy");
+
+    let policy = policy_service
+        .update(policy_id, payload.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/policies/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Policy id")),
+    responses(
+        (status = 204, description = "Policy deleted"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "No such policy"),
+    )
+)]
+#[delete("/admin/policies/{id}", wrap = "require_role(\"admin\")")]
+pub async fn delete_policy(
+    admin: AuthenticatedUser,
+    path: web::Path<i32>,
+    policy_service: web::Data<PolicyService>,
+) -> actix_web::Result<impl Responder> {
+    let policy_id = path.into_inner();
+    tracing::info!(admin_id = admin.uid, policy_id, "deleting access policy");
+
+    policy_service.delete(policy_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/policies/explain",
+    tag = "admin",
+    request_body = ExplainReq,
+    responses(
+        (status = 200, description = "The decision, the attributes it saw and every policy it weighed", body = Decision),
+        (status = 400, description = "Invalid action, IP or hour"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "No such user"),
+    )
+)]
+#[post("/admin/policies/explain", wrap = "require_role(\"admin\")")]
+pub async fn explain_policy(
+    req: HttpRequest,
+    admin: AuthenticatedUser,
+    payload: web::Json<ExplainReq>,
+    policy_service: web::Data<PolicyService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+
+    let decision = policy_service
+        .explain(&admin, get_client_ip(&req), &payload)
+        .await?;
+    Ok(HttpResponse::Ok().json(decision))
+}
@@ -0,0 +1,248 @@
+use std::{net::IpAddr, sync::Arc};
+
+use chrono::{Timelike, Utc};
+use deadpool_redis::{redis::AsyncCommands, Pool};
+use sqlx::PgPool;
+
+use crate::{
+    features::{
+        clients::MaxMindClient,
+        policies::{
+            decide,
+            types::{
+                AccessContext, Condition, Decision, ExplainReq, PolicyDto, PolicyReq, RequestAttrs,
+                ResourceAttrs, SubjectAttrs,
+            },
+            PolicyRepository,
+        },
+        roles::RoleRepository,
+        users::UserRepository,
+    },
+    infrastructure::middlewares::auth::AuthenticatedUser,
+    utils::error::{Error, Result},
+};
+
+/// Redis copy of every policy; rewritten on each change so all instances
+/// pick it up on their next decision.
+const CACHE_KEY: &str = "access_policies";
+
+const DEVICE_STATUSES: [&str; 3] = ["active", "inactive", "blocked"];
+
+#[derive(Clone)]
+pub struct PolicyService {
+    repo: PolicyRepository,
+    role_repo: RoleRepository,
+    user_repo: UserRepository,
+    redis_pool: Pool,
+    maxmind: Arc<MaxMindClient>,
+}
+
+impl PolicyService {
+    pub fn new(pool: PgPool, redis_pool: Pool, maxmind: Arc<MaxMindClient>) -> Self {
+        Self {
+            repo: PolicyRepository::new(pool.clone()),
+            role_repo: RoleRepository::new(pool.clone()),
+            user_repo: UserRepository::new(pool),
+            redis_pool,
+            maxmind,
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<PolicyDto>> {
+        self.policies().await
+    }
+
+    pub async fn create(&self, req: PolicyReq) -> Result<PolicyDto> {
+        let req = normalized(req)?;
+        let policy = self
+            .repo
+            .create(&req)
+            .await?
+            .ok_or_else(|| Error::Conflict(format!("policy {} already exists", req.name)))?;
+        self.reload().await?;
+        Ok(policy.into())
+    }
+
+    pub async fn update(&self, id: i32, req: PolicyReq) -> Result<PolicyDto> {
+        let req = normalized(req)?;
+        if let Some(other) = self.repo.find_by_name(&req.name).await? {
+            if other.id != id {
+                return Err(Error::Conflict(format!(
+                    "policy {} already exists",
+                    req.name
+                )));
+            }
+        }
+        let policy = self.repo.update(id, &req).await?.ok_or(Error::NotFound)?;
+        self.reload().await?;
+        Ok(policy.into())
+    }
+
+    pub async fn delete(&self, id: i32) -> Result<()> {
+        if !self.repo.delete(id).await? {
+            return Err(Error::NotFound);
+        }
+        self.reload().await
+    }
+
+    /// Attributes of `user`'s current request; `owner` is the user the
+    /// resource belongs to, if it has one.
+    pub async fn context(
+        &self,
+        user: &AuthenticatedUser,
+        ip: Option<IpAddr>,
+        owner: Option<i64>,
+    ) -> Result<AccessContext> {
+        let device_status = self.repo.device_status(user.did).await?;
+        Ok(AccessContext {
+            subject: SubjectAttrs {
+                uid: user.uid,
+                roles: user.roles.clone(),
+                device_status,
+            },
+            request: self.request_attrs(ip, Utc::now().hour() as u8),
+            resource: ResourceAttrs { owner },
+        })
+    }
+
+    pub async fn authorize(&self, action: &str, context: AccessContext) -> Result<Decision> {
+        let policies = self.policies().await?;
+        Ok(decide(&policies, action, context))
+    }
+
+    /// Decision with its full trace for `req`, filled in from the caller's
+    /// own request where `req` leaves attributes out.
+    pub async fn explain(
+        &self,
+        caller: &AuthenticatedUser,
+        caller_ip: Option<IpAddr>,
+        req: &ExplainReq,
+    ) -> Result<Decision> {
+        let mut context = self.context(caller, caller_ip, req.resource_owner).await?;
+
+        if let Some(user_id) = req.user_id {
+            self.user_repo
+                .find_by_id(user_id)
+                .await?
+                .ok_or(Error::NotFound)?;
+            context.subject = SubjectAttrs {
+                uid: user_id,
+                roles: self.role_repo.names_for_user(user_id).await?,
+                device_status: None,
+            };
+        }
+        if let Some(device_id) = req.device_id {
+            context.subject.device_status = self.repo.device_status(device_id).await?;
+        }
+        if req.ip.is_some() || req.hour.is_some() {
+            let ip = match &req.ip {
+                Some(ip) => Some(
+                    ip.parse()
+                        .map_err(|e| Error::Validation(format!("invalid IP address: {e}")))?,
+                ),
+                None => caller_ip,
+            };
+            context.request = self.request_attrs(ip, req.hour.unwrap_or(context.request.hour));
+        }
+
+        self.authorize(&req.action, context).await
+    }
+
+    fn request_attrs(&self, ip: Option<IpAddr>, hour: u8) -> RequestAttrs {
+        RequestAttrs {
+            ip: ip.map(|ip| ip.to_string()),
+            country: ip.and_then(|ip| self.maxmind.country_code(ip)),
+            hour,
+        }
+    }
+
+    /// Redis first, the database when the cache is cold or unreachable.
+    async fn policies(&self) -> Result<Vec<PolicyDto>> {
+        if let Ok(mut conn) = self.redis_pool.get().await {
+            if let Ok::<String, _>(cached) = conn.get(CACHE_KEY).await {
+                if let Ok(policies) = serde_json::from_str::<Vec<PolicyDto>>(&cached) {
+                    return Ok(policies);
+                }
+            }
+        }
+
+        let policies = self.load().await?;
+        if let Err(e) = self.cache(&policies).await {
+            tracing::warn!("caching access policies failed: {e}");
+        }
+        Ok(policies)
+    }
+
+    async fn reload(&self) -> Result<()> {
+        let policies = self.load().await?;
+        self.cache(&policies).await
+    }
+
+    async fn load(&self) -> Result<Vec<PolicyDto>> {
+        let policies = self.repo.all().await?;
+        Ok(policies.into_iter().map(PolicyDto::from).collect())
+    }
+
+    async fn cache(&self, policies: &[PolicyDto]) -> Result<()> {
+        let mut conn = self.redis_pool.get().await?;
+        let _: () = conn
+            .set(CACHE_KEY, serde_json::to_string(policies)?)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Checks what serde can't and puts names, roles and countries in the form
+/// the engine compares against.
+fn normalized(mut req: PolicyReq) -> Result<PolicyReq> {
+    req.name = req.name.trim().to_string();
+    req.action = req.action.trim().to_lowercase();
+    let allowed = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-:.".contains(c);
+    let body = req.action.strip_suffix('*').unwrap_or(&req.action);
+    if req.name.is_empty() || !body.chars().all(allowed) {
+        return Err(Error::Validation(
+            "actions use lowercase letters, digits, `_`, `-`, `:` and `.`, with an optional trailing `*`".into(),
+        ));
+    }
+
+    for condition in &mut req.conditions {
+        match condition {
+            Condition::RoleIn { roles } => {
+                roles.iter_mut().for_each(|r| *r = r.trim().to_lowercase())
+            }
+            Condition::DeviceStatusIn { statuses } => {
+                statuses
+                    .iter_mut()
+                    .for_each(|s| *s = s.trim().to_lowercase());
+                if let Some(s) = statuses
+                    .iter()
+                    .find(|s| !DEVICE_STATUSES.contains(&s.as_str()))
+                {
+                    return Err(Error::Validation(format!("unknown device status {s}")));
+                }
+            }
+            Condition::CountryIn { countries } | Condition::CountryNotIn { countries } => {
+                countries
+                    .iter_mut()
+                    .for_each(|c| *c = c.trim().to_uppercase());
+                if let Some(c) = countries
+                    .iter()
+                    .find(|c| c.len() != 2 || !c.chars().all(|c| c.is_ascii_uppercase()))
+                {
+                    return Err(Error::Validation(format!(
+                        "{c} is not an ISO 3166 alpha-2 country code"
+                    )));
+                }
+            }
+            Condition::HourBetween { from, to } => {
+                if *from > 23 || *to > 24 || from == to {
+                    return Err(Error::Validation(
+                        "hour_between needs from in 0..=23, to in 0..=24 and from != to".into(),
+                    ));
+                }
+            }
+            Condition::UserIn { .. } | Condition::IsOwner => {}
+        }
+    }
+    Ok(req)
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{AccessPolicy, Effect};
+
+/// One test on the access context; a policy applies when all of its
+/// conditions hold (no conditions: always).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    /// caller holds at least one of the roles
+    RoleIn { roles: Vec<String> },
+    /// caller is one of these users
+    UserIn { users: Vec<i64> },
+    /// the caller's device is `active`, `inactive` or `blocked`; unknown devices never match
+    DeviceStatusIn { statuses: Vec<String> },
+    /// ISO 3166 alpha-2 code of the request's GeoIP country; unknown countries never match
+    CountryIn { countries: Vec<String> },
+    /// unknown countries always match
+    CountryNotIn { countries: Vec<String> },
+    /// UTC hour of the request in `from..to`; wraps past midnight when `from > to`
+    HourBetween { from: u8, to: u8 },
+    /// caller is the owner of the resource
+    IsOwner,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyReq {
+    #[validate(length(min = 1, max = 64))]
+    pub name: String,
+    #[validate(length(max = 512))]
+    pub description: Option<String>,
+    /// `resource:verb`; a trailing `*` matches any suffix, `*` alone every action
+    #[validate(length(min = 1, max = 128))]
+    pub action: String,
+    pub effect: Effect,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyDto {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub action: String,
+    pub effect: Effect,
+    pub conditions: Vec<Condition>,
+    pub enabled: bool,
+}
+
+impl From<AccessPolicy> for PolicyDto {
+    fn from(p: AccessPolicy) -> Self {
+        Self {
+            id: p.id,
+            name: p.name,
+            description: p.description,
+            action: p.action,
+            effect: p.effect,
+            conditions: p.conditions.0,
+            enabled: p.enabled,
+        }
+    }
+}
+
+/// Everything a decision looks at.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessContext {
+    pub subject: SubjectAttrs,
+    pub request: RequestAttrs,
+    pub resource: ResourceAttrs,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SubjectAttrs {
+    pub uid: i64,
+    pub roles: Vec<String>,
+    pub device_status: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestAttrs {
+    pub ip: Option<String>,
+    /// ISO 3166 alpha-2, from MaxMind
+    pub country: Option<String>,
+    /// 0..=23, UTC
+    pub hour: u8,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceAttrs {
+    pub owner: Option<i64>,
+}
+
+/// What to evaluate; anything left out is taken from the caller's own request.
+#[derive(Debug, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainReq {
+    #[validate(length(min = 1, max = 128))]
+    pub action: String,
+    /// evaluate as this user, with their current roles and no device unless `deviceId` is set
+    pub user_id: Option<i64>,
+    pub device_id: Option<i64>,
+    pub ip: Option<String>,
+    #[validate(range(max = 23))]
+    pub hour: Option<u8>,
+    pub resource_owner: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Decision {
+    pub action: String,
+    pub allowed: bool,
+    /// the policy that settled it; none when no policy applied
+    pub policy: Option<String>,
+    pub reason: String,
+    pub context: AccessContext,
+    /// every enabled policy for the action, in evaluation order
+    pub trace: Vec<PolicyTrace>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyTrace {
+    pub policy: String,
+    pub effect: Effect,
+    /// all conditions held
+    pub applies: bool,
+    pub conditions: Vec<ConditionTrace>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionTrace {
+    pub condition: Condition,
+    pub passed: bool,
+    /// the attribute value the condition saw
+    pub actual: String,
+}
@@ -0,0 +1,9 @@
+use sqlx::prelude::FromRow;
+
+/// Unused row of `recovery_codes`; only what redeeming a code needs.
+#[derive(FromRow, Clone)]
+pub struct RecoveryCode {
+    pub id: i64,
+    /// Argon2 PHC string of the normalized code
+    pub code_hash: String,
+}
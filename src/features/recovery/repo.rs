@@ -1,110 +1,75 @@
 use sqlx::PgPool;
-use chrono::Utc;
-use crate::models::user::{User, CreateUserDto, LoginMethod};
 
-pub struct UserRepository {
+use super::RecoveryCode;
+
+#[derive(Clone)]
+pub struct RecoveryRepository {
     pool: PgPool,
 }
 
-impl UserRepository {
+impl RecoveryRepository {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
 
-    pub async fn create(&self, user_dto: CreateUserDto, password_hash: String, salt: Vec<u8>) -> Result<User, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
+    /// Replace the user's codes with `code_hashes` in one go.
+    pub async fn replace(&self, user_id: i64, code_hashes: &[String]) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
             r#"
-            INSERT INTO users 
-            (
-                username, 
-                email, 
-                phone_number, 
-                password_hash, 
-                salt, 
-                is_email_verified, 
-                is_phone_verified, 
-                login_method, 
-                created_at, 
-                updated_at
-            )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-            RETURNING 
-                id, 
-                username, 
-                email, 
-                phone_number, 
-                password_hash, 
-                salt, 
-                is_email_verified, 
-                is_phone_verified, 
-                login_method as "login_method: LoginMethod", 
-                created_at, 
-                updated_at, 
-                deleted_at
+            INSERT INTO recovery_codes (user_id, code_hash)
+            SELECT $1, UNNEST($2::text[])
             "#,
-            user_dto.username,
-            user_dto.email,
-            user_dto.phone_number,
-            password_hash,
-            salt,
-            false, // is_email_verified
-            false, // is_phone_verified
-            user_dto.login_method as LoginMethod,
-            Utc::now(),
-            Utc::now()
         )
-        .fetch_one(&self.pool)
+        .bind(user_id)
+        .bind(code_hashes)
+        .execute(&mut *tx)
         .await?;
-
-        Ok(user)
+        tx.commit().await
     }
 
-    pub async fn find_by_id(&self, user_id: i64) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
+    pub async fn unused(&self, user_id: i64) -> sqlx::Result<Vec<RecoveryCode>> {
+        sqlx::query_as::<_, RecoveryCode>(
             r#"
-            SELECT id, username, email, phone_number, password_hash, salt, is_email_verified, is_phone_verified, login_method as "login_method: LoginMethod", created_at, updated_at, deleted_at
-            FROM users 
-            WHERE id = $1 AND deleted_at IS NULL
+            SELECT id, code_hash
+            FROM recovery_codes
+            WHERE user_id = $1 AND used_at IS NULL
+            ORDER BY id
             "#,
-            user_id
         )
-        .fetch_optional(&self.pool)
-        .await?;
-
-        Ok(user)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
     }
 
-    pub async fn find_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
-            r#"
-            SELECT id, username, email, phone_number, password_hash, salt, is_email_verified, is_phone_verified, login_method as "login_method: LoginMethod", created_at, updated_at, deleted_at
-            FROM users 
-            WHERE email = $1 AND deleted_at IS NULL
-            "#,
-            email
+    pub async fn count_unused(&self, user_id: i64) -> sqlx::Result<i64> {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL",
         )
-        .fetch_optional(&self.pool)
-        .await?;
-
-        Ok(user)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
     }
 
-    pub async fn find_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as!(
-            User,
-            r#"
-            SELECT id, username, email, phone_number, password_hash, salt, is_email_verified, is_phone_verified, login_method as "login_method: LoginMethod", created_at, updated_at, deleted_at
-            FROM users 
-            WHERE username = $1 AND deleted_at IS NULL
-            "#,
-            username
+    /// Spend a code. `false` when a concurrent request spent it first.
+    pub async fn mark_used(&self, id: i64) -> sqlx::Result<bool> {
+        let res = sqlx::query(
+            "UPDATE recovery_codes SET used_at = now() WHERE id = $1 AND used_at IS NULL",
         )
-        .fetch_optional(&self.pool)
+        .bind(id)
+        .execute(&self.pool)
         .await?;
+        Ok(res.rows_affected() == 1)
+    }
 
-        Ok(user)
+    pub async fn find_user_id_by_email(&self, email: &str) -> sqlx::Result<Option<i64>> {
+        sqlx::query_scalar("SELECT id FROM users WHERE email = $1 AND deleted_at IS NULL")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await
     }
-}
\ No newline at end of file
+}
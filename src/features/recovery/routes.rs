@@ -0,0 +1,91 @@
+use actix_web::{get, http::header, post, web, HttpRequest, HttpResponse, Responder};
+use validator::Validate;
+
+use crate::{
+    features::{
+        recovery::{
+            types::{RecoveryCodesResp, RecoveryCodesStatusResp, RecoveryLoginReq},
+            RecoveryService,
+        },
+        clients::EmailClient,
+        users::UserService,
+    },
+    infrastructure::middlewares::auth::AuthenticatedUser,
+};
+
+#[utoipa::path(
+    post,
+    path = "/auth/recovery-codes",
+    tag = "auth",
+    responses(
+        (status = 200, description = "New recovery codes, shown once; older codes stop working", body = RecoveryCodesResp),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Recovery codes are disabled"),
+    )
+)]
+#[post("/auth/recovery-codes")]
+pub async fn generate_recovery_codes(
+    user: AuthenticatedUser,
+    recovery_service: web::Data<RecoveryService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    let codes = recovery_service.generate(user.uid).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(RecoveryCodesResp { codes }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/recovery-codes",
+    tag = "auth",
+    responses(
+        (status = 200, description = "How many recovery codes are left", body = RecoveryCodesStatusResp),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Recovery codes are disabled"),
+    )
+)]
+#[get("/auth/recovery-codes")]
+pub async fn recovery_codes_status(
+    user: AuthenticatedUser,
+    recovery_service: web::Data<RecoveryService>,
+) -> actix_web::Result<impl Responder> {
+    let remaining = recovery_service.remaining(user.uid).await?;
+    Ok(HttpResponse::Ok().json(RecoveryCodesStatusResp { remaining }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/recovery-codes/login",
+    tag = "auth",
+    request_body = RecoveryLoginReq,
+    responses(
+        (status = 200, description = "Recovery code accepted (and spent), tokens issued"),
+        (status = 400, description = "Missing device cookie"),
+        (status = 401, description = "Unknown email or invalid code"),
+        (status = 403, description = "Recovery codes are disabled"),
+        (status = 423, description = "Account or IP locked out after failed logins"),
+        (status = 429, description = "Too many requests, or backing off after failed logins"),
+    )
+)]
+#[post("/auth/recovery-codes/login")]
+pub async fn login_with_recovery_code(
+    req: HttpRequest,
+    payload: web::Json<RecoveryLoginReq>,
+    user_service: web::Data<UserService>,
+    recovery_service: web::Data<RecoveryService>,
+    email_client: web::Data<EmailClient>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+
+    user_service
+        .finish_guarded_login(
+            &req,
+            &payload.email,
+            &email_client,
+            recovery_service.authenticate(&payload.email, &payload.recovery_code),
+        )
+        .await
+}
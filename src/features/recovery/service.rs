@@ -0,0 +1,159 @@
+use password_hash::rand_core::{OsRng, RngCore};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    features::{
+        audits::{AuditRepository, EventType, LogLevel},
+        recovery::RecoveryRepository,
+        system::ConfigService,
+        users::{PasswordCheck, PasswordHashing, VerifiedLogin},
+    },
+    utils::error::{Error, Result},
+};
+
+/// Codes per set.
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Characters per code, printed as two groups of five.
+const RECOVERY_CODE_LEN: usize = 10;
+/// No 0/o, 1/l/i: codes get copied from paper.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+#[derive(Clone)]
+pub struct RecoveryService {
+    repo: RecoveryRepository,
+    audit_repo: AuditRepository,
+    config_service: Arc<ConfigService>,
+    password_hashing: PasswordHashing,
+}
+
+impl RecoveryService {
+    pub fn new(
+        pool: PgPool,
+        config_service: Arc<ConfigService>,
+        password_hashing: PasswordHashing,
+    ) -> Self {
+        Self {
+            repo: RecoveryRepository::new(pool.clone()),
+            audit_repo: AuditRepository::new(pool),
+            config_service,
+            password_hashing,
+        }
+    }
+
+    /// New set of codes; any previous set stops working. The plain codes
+    /// are returned once and never stored.
+    pub async fn generate(&self, user_id: i64) -> Result<Vec<String>> {
+        self.ensure_allowed().await?;
+
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| new_code()).collect();
+        let hashes = codes
+            .iter()
+            .map(|code| self.password_hashing.hash(&normalize(code)))
+            .collect::<Result<Vec<_>>>()?;
+
+        self.repo
+            .replace(user_id, &hashes)
+            .await
+            .map_err(Error::from)?;
+        self.audit_repo
+            .create(
+                user_id,
+                EventType::RecoveryCodesGenerated,
+                LogLevel::Info,
+                None,
+            )
+            .await
+            .map_err(Error::from)?;
+
+        Ok(codes)
+    }
+
+    pub async fn remaining(&self, user_id: i64) -> Result<i64> {
+        self.ensure_allowed().await?;
+        self.repo.count_unused(user_id).await.map_err(Error::from)
+    }
+
+    /// Whether a code could stand in for the second factor of `user_id`.
+    pub async fn is_available(&self, user_id: i64) -> Result<bool> {
+        if !self.config_service.get().await?.allow_recovery_codes {
+            return Ok(false);
+        }
+        Ok(self.repo.count_unused(user_id).await.map_err(Error::from)? > 0)
+    }
+
+    /// Spend `code` if it is one of the user's unused codes.
+    pub async fn redeem(&self, user_id: i64, code: &str) -> Result<bool> {
+        self.ensure_allowed().await?;
+
+        let code = normalize(code);
+        let unused = self.repo.unused(user_id).await.map_err(Error::from)?;
+        let mut matched = None;
+        for entry in &unused {
+            if self.password_hashing.verify(&entry.code_hash, &code)? != PasswordCheck::Mismatch {
+                matched = Some(entry.id);
+                break;
+            }
+        }
+        let Some(id) = matched else {
+            return Ok(false);
+        };
+        if !self.repo.mark_used(id).await.map_err(Error::from)? {
+            return Ok(false);
+        }
+
+        self.audit_repo
+            .create(user_id, EventType::RecoveryCodeUsed, LogLevel::Warn, None)
+            .await
+            .map_err(Error::from)?;
+        Ok(true)
+    }
+
+    /// Sign-in with email and a recovery code, for users who lost their
+    /// password or second factor.
+    pub async fn authenticate(&self, email: &str, code: &str) -> Result<VerifiedLogin> {
+        self.ensure_allowed().await?;
+
+        let Some(user_id) = self
+            .repo
+            .find_user_id_by_email(email)
+            .await
+            .map_err(Error::from)?
+        else {
+            return Ok(VerifiedLogin::Rejected(None));
+        };
+        if self.redeem(user_id, code).await? {
+            Ok(VerifiedLogin::User(user_id))
+        } else {
+            Ok(VerifiedLogin::Rejected(Some(user_id)))
+        }
+    }
+
+    async fn ensure_allowed(&self) -> Result<()> {
+        if !self.config_service.get().await?.allow_recovery_codes {
+            return Err(Error::Forbidden);
+        }
+        Ok(())
+    }
+}
+
+/// `xxxxx-xxxxx` from `RECOVERY_CODE_ALPHABET`.
+fn new_code() -> String {
+    let mut code = String::with_capacity(RECOVERY_CODE_LEN + 1);
+    for i in 0..RECOVERY_CODE_LEN {
+        if i == RECOVERY_CODE_LEN / 2 {
+            code.push('-');
+        }
+        let idx = OsRng.next_u32() as usize % RECOVERY_CODE_ALPHABET.len();
+        code.push(RECOVERY_CODE_ALPHABET[idx] as char);
+    }
+    code
+}
+
+/// What gets hashed: lowercase, without the dash or stray spaces.
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// Fresh recovery codes. Shown this once; only hashes are kept.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesResp {
+    pub codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesStatusResp {
+    /// codes not used yet
+    pub remaining: i64,
+}
+
+/// Sign in with a recovery code when the usual factors are lost.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryLoginReq {
+    #[validate(email)]
+    pub email: String,
+    #[validate(length(min = 10, max = 32))]
+    pub recovery_code: String,
+}
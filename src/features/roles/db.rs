@@ -0,0 +1,8 @@
+use sqlx::prelude::FromRow;
+
+/// Row of `roles`.
+#[derive(FromRow, Clone)]
+pub struct Role {
+    pub id: i32,
+    pub name: String,
+}
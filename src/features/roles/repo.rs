@@ -0,0 +1,120 @@
+use sqlx::PgPool;
+
+use super::Role;
+
+#[derive(Clone)]
+pub struct RoleRepository {
+    pool: PgPool,
+}
+
+impl RoleRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn all(&self) -> sqlx::Result<Vec<Role>> {
+        sqlx::query_as::<_, Role>("SELECT id, name FROM roles ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn find_by_id(&self, role_id: i32) -> sqlx::Result<Option<Role>> {
+        sqlx::query_as::<_, Role>("SELECT id, name FROM roles WHERE id = $1")
+            .bind(role_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn find_by_name(&self, name: &str) -> sqlx::Result<Option<Role>> {
+        sqlx::query_as::<_, Role>("SELECT id, name FROM roles WHERE name = $1")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// `None` if the name is taken.
+    pub async fn create(&self, name: &str) -> sqlx::Result<Option<Role>> {
+        sqlx::query_as::<_, Role>(
+            r#"
+            INSERT INTO roles (name) VALUES ($1)
+            ON CONFLICT (name) DO NOTHING
+            RETURNING id, name
+            "#,
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn rename(&self, role_id: i32, name: &str) -> sqlx::Result<Option<Role>> {
+        sqlx::query_as::<_, Role>("UPDATE roles SET name = $2 WHERE id = $1 RETURNING id, name")
+            .bind(role_id)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Assignments go with it (`ON DELETE CASCADE`).
+    pub async fn delete(&self, role_id: i32) -> sqlx::Result<bool> {
+        let result = sqlx::query("DELETE FROM roles WHERE id = $1")
+            .bind(role_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Role names of a user, sorted.
+    pub async fn names_for_user(&self, user_id: i64) -> sqlx::Result<Vec<String>> {
+        sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT r.name
+            FROM users_roles ur
+            JOIN roles r ON r.id = ur.role_id
+            WHERE ur.user_id = $1
+            ORDER BY r.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// False if the user already had the role.
+    pub async fn assign(&self, user_id: i64, role_id: i32) -> sqlx::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO users_roles (user_id, role_id) VALUES ($1, $2)
+            ON CONFLICT (user_id, role_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(role_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// False if the user didn't have the role.
+    pub async fn unassign(&self, user_id: i64, role_id: i32) -> sqlx::Result<bool> {
+        let result = sqlx::query("DELETE FROM users_roles WHERE user_id = $1 AND role_id = $2")
+            .bind(user_id)
+            .bind(role_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn holder_count(&self, name: &str) -> sqlx::Result<i64> {
+        sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT count(*)
+            FROM users_roles ur
+            JOIN roles r ON r.id = ur.role_id
+            WHERE r.name = $1
+            "#,
+        )
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await
+    }
+}
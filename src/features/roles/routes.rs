@@ -0,0 +1,190 @@
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use validator::Validate;
+
+use crate::{
+    features::roles::{
+        types::{RoleReq, RoleResp, UserRolesResp},
+        RoleService,
+    },
+    infrastructure::middlewares::{admin_auth::require_role, auth::AuthenticatedUser},
+};
+
+#[utoipa::path(
+    get,
+    path = "/admin/roles",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Every role, by name", body = [RoleResp]),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+    )
+)]
+#[get("/admin/roles", wrap = "require_role(\"admin\")")]
+pub async fn list_roles(role_service: web::Data<RoleService>) -> actix_web::Result<impl Responder> {
+    Ok(HttpResponse::Ok().json(role_service.list().await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/roles",
+    tag = "admin",
+    request_body = RoleReq,
+    responses(
+        (status = 201, description = "Role created", body = RoleResp),
+        (status = 400, description = "Invalid role name"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 409, description = "Name already taken"),
+    )
+)]
+#[post("/admin/roles", wrap = "require_role(\"admin\")")]
+pub async fn create_role(
+    admin: AuthenticatedUser,
+    payload: web::Json<RoleReq>,
+    role_service: web::Data<RoleService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    tracing::info!(admin_id = admin.uid, name = %payload.name, "creating role");
+
+    let role = role_service.create(&payload.name).await?;
+    Ok(HttpResponse::Created().json(role))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/roles/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Role id")),
+    request_body = RoleReq,
+    responses(
+        (status = 200, description = "Role renamed", body = RoleResp),
+        (status = 400, description = "Invalid role name"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "No such role"),
+        (status = 409, description = "Name already taken, or the built-in admin role"),
+    )
+)]
+#[put("/admin/roles/{id}", wrap = "require_role(\"admin\")")]
+pub async fn rename_role(
+    admin: AuthenticatedUser,
+    path: web::Path<i32>,
+    payload: web::Json<RoleReq>,
+    role_service: web::Data<RoleService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    let role_id = path.into_inner();
+    tracing::info!(admin_id = admin.uid, role_id, name = %payload.name, "renaming role");
+
+    let role = role_service.rename(role_id, &payload.name).await?;
+    Ok(HttpResponse::Ok().json(role))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/roles/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Role id")),
+    responses(
+        (status = 204, description = "Role and its assignments deleted"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "No such role"),
+        (status = 409, description = "The built-in admin role"),
+    )
+)]
+#[delete("/admin/roles/{id}", wrap = "require_role(\"admin\")")]
+pub async fn delete_role(
+    admin: AuthenticatedUser,
+    path: web::Path<i32>,
+    role_service: web::Data<RoleService>,
+) -> actix_web::Result<impl Responder> {
+    let role_id = path.into_inner();
+    tracing::info!(admin_id = admin.uid, role_id, "deleting role");
+
+    role_service.delete(role_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/users/{id}/roles",
+    tag = "admin",
+    params(("id" = i64, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The user's roles", body = UserRolesResp),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "No such user"),
+    )
+)]
+#[get("/admin/users/{id}/roles", wrap = "require_role(\"admin\")")]
+pub async fn user_roles(
+    path: web::Path<i64>,
+    role_service: web::Data<RoleService>,
+) -> actix_web::Result<impl Responder> {
+    let user_id = path.into_inner();
+    let roles = role_service.user_roles(user_id).await?;
+    Ok(HttpResponse::Ok().json(UserRolesResp { user_id, roles }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/users/{id}/roles/{role_id}",
+    tag = "admin",
+    params(
+        ("id" = i64, Path, description = "User id"),
+        ("role_id" = i32, Path, description = "Role id"),
+    ),
+    responses(
+        (status = 204, description = "Role assigned, effective with the user's next token"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "No such user or role"),
+    )
+)]
+#[put("/admin/users/{id}/roles/{role_id}", wrap = "require_role(\"admin\")")]
+pub async fn assign_role(
+    admin: AuthenticatedUser,
+    path: web::Path<(i64, i32)>,
+    role_service: web::Data<RoleService>,
+) -> actix_web::Result<impl Responder> {
+    let (user_id, role_id) = path.into_inner();
+    tracing::info!(admin_id = admin.uid, user_id, role_id, "assigning role");
+
+    role_service.assign(user_id, role_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{id}/roles/{role_id}",
+    tag = "admin",
+    params(
+        ("id" = i64, Path, description = "User id"),
+        ("role_id" = i32, Path, description = "Role id"),
+    ),
+    responses(
+        (status = 204, description = "Role removed, effective with the user's next token"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "The user doesn't have the role"),
+        (status = 409, description = "The last admin"),
+    )
+)]
+#[delete("/admin/users/{id}/roles/{role_id}", wrap = "require_role(\"admin\")")]
+pub async fn unassign_role(
+    admin: AuthenticatedUser,
+    path: web::Path<(i64, i32)>,
+    role_service: web::Data<RoleService>,
+) -> actix_web::Result<impl Responder> {
+    let (user_id, role_id) = path.into_inner();
+    tracing::info!(admin_id = admin.uid, user_id, role_id, "removing role");
+
+    role_service.unassign(user_id, role_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
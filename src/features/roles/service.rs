@@ -0,0 +1,177 @@
+use sqlx::PgPool;
+
+use crate::{
+    features::{
+        audits::{AuditRepository, EventType, LogLevel},
+        roles::{types::RoleResp, RoleRepository},
+        users::UserRepository,
+    },
+    utils::error::{Error, Result},
+};
+
+/// Guards the admin endpoints; can be neither renamed nor deleted.
+pub const ADMIN_ROLE: &str = "admin";
+
+#[derive(Clone)]
+pub struct RoleService {
+    repo: RoleRepository,
+    user_repo: UserRepository,
+    audit_repo: AuditRepository,
+}
+
+impl RoleService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            repo: RoleRepository::new(pool.clone()),
+            user_repo: UserRepository::new(pool.clone()),
+            audit_repo: AuditRepository::new(pool),
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<RoleResp>> {
+        let roles = self.repo.all().await?;
+        Ok(roles.into_iter().map(RoleResp::from).collect())
+    }
+
+    pub async fn create(&self, name: &str) -> Result<RoleResp> {
+        let name = valid_name(name)?;
+        let role = self
+            .repo
+            .create(&name)
+            .await?
+            .ok_or_else(|| Error::Conflict(format!("role {name} already exists")))?;
+        Ok(role.into())
+    }
+
+    pub async fn rename(&self, role_id: i32, name: &str) -> Result<RoleResp> {
+        let name = valid_name(name)?;
+        self.editable(role_id).await?;
+        if self.repo.find_by_name(&name).await?.is_some() {
+            return Err(Error::Conflict(format!("role {name} already exists")));
+        }
+        let role = self
+            .repo
+            .rename(role_id, &name)
+            .await?
+            .ok_or(Error::NotFound)?;
+        Ok(role.into())
+    }
+
+    pub async fn delete(&self, role_id: i32) -> Result<()> {
+        self.editable(role_id).await?;
+        if !self.repo.delete(role_id).await? {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    pub async fn user_roles(&self, user_id: i64) -> Result<Vec<String>> {
+        self.user_repo
+            .find_by_id(user_id)
+            .await?
+            .ok_or(Error::NotFound)?;
+        Ok(self.repo.names_for_user(user_id).await?)
+    }
+
+    /// Takes effect with the user's next token: a login or a refresh.
+    pub async fn assign(&self, user_id: i64, role_id: i32) -> Result<()> {
+        self.user_repo
+            .find_by_id(user_id)
+            .await?
+            .ok_or(Error::NotFound)?;
+        self.repo
+            .find_by_id(role_id)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        if self.repo.assign(user_id, role_id).await? {
+            self.audit(user_id, EventType::RoleAssigned).await;
+        }
+        Ok(())
+    }
+
+    /// Access tokens already issued keep the role until they expire; revoke
+    /// them through `/admin/revocations` for an immediate cut-off.
+    pub async fn unassign(&self, user_id: i64, role_id: i32) -> Result<()> {
+        let role = self
+            .repo
+            .find_by_id(role_id)
+            .await?
+            .ok_or(Error::NotFound)?;
+        if role.name == ADMIN_ROLE && self.repo.holder_count(ADMIN_ROLE).await? <= 1 {
+            let roles = self.repo.names_for_user(user_id).await?;
+            if roles.iter().any(|r| r == ADMIN_ROLE) {
+                return Err(Error::Conflict("the last admin can't be removed".into()));
+            }
+        }
+
+        if !self.repo.unassign(user_id, role_id).await? {
+            return Err(Error::NotFound);
+        }
+        self.audit(user_id, EventType::RoleRevoked).await;
+        Ok(())
+    }
+
+    /// Makes the account with `email` the first admin, as long as nobody holds
+    /// the role yet; later runs are no-ops, so the variable can't hand out
+    /// admin rights once an admin exists.
+    pub async fn bootstrap_admin(&self, email: &str) -> Result<()> {
+        if self.repo.holder_count(ADMIN_ROLE).await? > 0 {
+            return Ok(());
+        }
+        let Some(user) = self
+            .user_repo
+            .find_by_email(&email.trim().to_lowercase())
+            .await?
+        else {
+            tracing::warn!(
+                email,
+                "no account to bootstrap as admin yet, sign up and restart"
+            );
+            return Ok(());
+        };
+        let role = self
+            .repo
+            .find_by_name(ADMIN_ROLE)
+            .await?
+            .ok_or_else(|| Error::Unexpected("the admin role is missing".into()))?;
+
+        self.repo.assign(user.id, role.id).await?;
+        self.audit(user.id, EventType::RoleAssigned).await;
+        tracing::info!(user_id = user.id, "bootstrapped the first admin");
+        Ok(())
+    }
+
+    async fn editable(&self, role_id: i32) -> Result<()> {
+        let role = self
+            .repo
+            .find_by_id(role_id)
+            .await?
+            .ok_or(Error::NotFound)?;
+        if role.name == ADMIN_ROLE {
+            return Err(Error::Conflict("the admin role is built in".into()));
+        }
+        Ok(())
+    }
+
+    async fn audit(&self, user_id: i64, event_type: EventType) {
+        if let Err(e) = self
+            .audit_repo
+            .create(user_id, event_type, LogLevel::Warn, None)
+            .await
+        {
+            tracing::error!(user_id, "audit {event_type:?} failed: {e}");
+        }
+    }
+}
+
+fn valid_name(name: &str) -> Result<String> {
+    let name = name.trim().to_lowercase();
+    let allowed = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-:".contains(c);
+    if name.is_empty() || !name.chars().all(allowed) {
+        return Err(Error::Validation(
+            "role names use lowercase letters, digits, `_`, `-` and `:`".into(),
+        ));
+    }
+    Ok(name)
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::Role;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleReq {
+    /// lowercase letters, digits, `_`, `-` and `:`; ends up in access tokens
+    #[validate(length(min = 1, max = 64))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleResp {
+    pub id: i32,
+    pub name: String,
+}
+
+impl From<Role> for RoleResp {
+    fn from(r: Role) -> Self {
+        Self {
+            id: r.id,
+            name: r.name,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UserRolesResp {
+    pub user_id: i64,
+    /// role names, sorted; tokens issued from now on carry them
+    pub roles: Vec<String>,
+}
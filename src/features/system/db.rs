@@ -7,4 +7,14 @@ pub struct ConfigEntity {
     pub refresh_token_validity_seconds: i32,
     pub ai_model: String,
     pub vector_similarity_threshold: i32,
+    pub password_min_length: i32,
+    pub password_min_char_classes: i32,
+    pub password_min_strength: i32,
+    pub password_reject_personal_info: bool,
+    pub password_check_breached: bool,
+    pub login_backoff_after: i32,
+    pub login_backoff_max_seconds: i32,
+    pub lockout_threshold: i32,
+    pub lockout_ip_threshold: i32,
+    pub lockout_duration_seconds: i32,
 }
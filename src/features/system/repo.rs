@@ -26,7 +26,17 @@ impl ConfigRepository {
                     token_validity_seconds = $3,
                     refresh_token_validity_seconds = $4,
                     ai_model = $5,
-                    vector_similarity_threshold = $6
+                    vector_similarity_threshold = $6,
+                    password_min_length = $7,
+                    password_min_char_classes = $8,
+                    password_min_strength = $9,
+                    password_reject_personal_info = $10,
+                    password_check_breached = $11,
+                    login_backoff_after = $12,
+                    login_backoff_max_seconds = $13,
+                    lockout_threshold = $14,
+                    lockout_ip_threshold = $15,
+                    lockout_duration_seconds = $16
             "#,
         )
         .bind(cfg.allow_recovery_codes)
@@ -35,6 +45,16 @@ impl ConfigRepository {
         .bind(cfg.refresh_token_validity_seconds)
         .bind(&cfg.ai_model)
         .bind(cfg.vector_similarity_threshold)
+        .bind(cfg.password_min_length)
+        .bind(cfg.password_min_char_classes)
+        .bind(cfg.password_min_strength)
+        .bind(cfg.password_reject_personal_info)
+        .bind(cfg.password_check_breached)
+        .bind(cfg.login_backoff_after)
+        .bind(cfg.login_backoff_max_seconds)
+        .bind(cfg.lockout_threshold)
+        .bind(cfg.lockout_ip_threshold)
+        .bind(cfg.lockout_duration_seconds)
         .execute(&self.pool)
         .await?;
 
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use crate::features::clients::EmailClient;
+use crate::infrastructure::middlewares::admin_auth::require_role;
 use actix_web::error::ErrorInternalServerError;
 use actix_web::{get, put, web, HttpResponse, Result};
 use chrono::Utc;
@@ -76,7 +77,8 @@ pub async fn version() -> Result<HttpResponse> {
     path = "/system/config",
     tag = "system",
     responses(
-        (status = 200, description = "Get system config", body = ConfigDto)
+        (status = 200, description = "Get system config", body = ConfigDto),
+        (status = 429, description = "Too many requests from this network")
     )
 )]
 #[get("/system/config")]
@@ -92,10 +94,12 @@ pub async fn config(service: web::Data<Arc<ConfigService>>) -> Result<HttpRespon
     request_body = ConfigDto,
     responses(
         (status = 200, description = "Update system config successfully"),
-        (status = 400, description = "Invalid config input")
+        (status = 400, description = "Invalid config input"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Caller is not an admin")
     )
 )]
-#[put("/system/config")]
+#[put("/system/config", wrap = "require_role(\"admin\")")]
 pub async fn update_config(
     service: web::Data<Arc<ConfigService>>,
     email_client: web::Data<EmailClient>,
@@ -117,13 +121,33 @@ pub async fn update_config(
              token_validity_seconds: {}\n\
              refresh_token_validity_seconds: {}\n\
              ai_model: {}\n\
-             vector_similarity_threshold: {}",
+             vector_similarity_threshold: {}\n\
+             password_min_length: {}\n\
+             password_min_char_classes: {}\n\
+             password_min_strength: {}\n\
+             password_reject_personal_info: {}\n\
+             password_check_breached: {}\n\
+             login_backoff_after: {}\n\
+             login_backoff_max_seconds: {}\n\
+             lockout_threshold: {}\n\
+             lockout_ip_threshold: {}\n\
+             lockout_duration_seconds: {}",
             dto.allow_recovery_codes,
             dto.allow_refresh_tokens,
             dto.token_validity_seconds,
             dto.refresh_token_validity_seconds,
             dto.ai_model,
-            dto.vector_similarity_threshold
+            dto.vector_similarity_threshold,
+            dto.password_min_length,
+            dto.password_min_char_classes,
+            dto.password_min_strength,
+            dto.password_reject_personal_info,
+            dto.password_check_breached,
+            dto.login_backoff_after,
+            dto.login_backoff_max_seconds,
+            dto.lockout_threshold,
+            dto.lockout_ip_threshold,
+            dto.lockout_duration_seconds
         );
 
         if let Err(e) = email_client
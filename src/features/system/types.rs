@@ -13,6 +13,25 @@ pub struct ConfigDto {
     pub refresh_token_validity_seconds: i32,
     pub ai_model: String,
     pub vector_similarity_threshold: i32,
+    pub password_min_length: i32,
+    /// how many of lowercase, uppercase, digits and symbols a password must mix (1-4)
+    pub password_min_char_classes: i32,
+    /// zxcvbn-style score (0-4) a password must reach
+    pub password_min_strength: i32,
+    /// reject passwords containing the email or username
+    pub password_reject_personal_info: bool,
+    /// reject passwords found in the local breached-password corpus
+    pub password_check_breached: bool,
+    /// failed logins allowed before each further attempt has to wait
+    pub login_backoff_after: i32,
+    /// cap of the doubling wait between failed logins
+    pub login_backoff_max_seconds: i32,
+    /// failed logins on one account that lock it
+    pub lockout_threshold: i32,
+    /// failed logins from one IP bucket (/24, /64) that lock the bucket
+    pub lockout_ip_threshold: i32,
+    /// how long a lock lasts; failures are counted over the same window
+    pub lockout_duration_seconds: i32,
 }
 
 impl ConfigDto {
@@ -32,6 +51,39 @@ impl ConfigDto {
                 "vector_similarity_threshold must be between 0 and 100".into(),
             ));
         }
+        if self.password_min_length < 1 || self.password_min_length > 128 {
+            return Err(Error::Validation(
+                "password_min_length must be between 1 and 128".into(),
+            ));
+        }
+        if self.password_min_char_classes < 1 || self.password_min_char_classes > 4 {
+            return Err(Error::Validation(
+                "password_min_char_classes must be between 1 and 4".into(),
+            ));
+        }
+        if self.password_min_strength < 0 || self.password_min_strength > 4 {
+            return Err(Error::Validation(
+                "password_min_strength must be between 0 and 4".into(),
+            ));
+        }
+        if self.login_backoff_after < 0 {
+            return Err(Error::Validation("login_backoff_after must be >= 0".into()));
+        }
+        if self.login_backoff_max_seconds < 0 {
+            return Err(Error::Validation(
+                "login_backoff_max_seconds must be >= 0".into(),
+            ));
+        }
+        if self.lockout_threshold < 1 || self.lockout_ip_threshold < 1 {
+            return Err(Error::Validation(
+                "lockout_threshold and lockout_ip_threshold must be > 0".into(),
+            ));
+        }
+        if self.lockout_duration_seconds <= 0 {
+            return Err(Error::Validation(
+                "lockout_duration_seconds must be > 0".into(),
+            ));
+        }
         Ok(())
     }
 }
@@ -46,6 +98,16 @@ impl From<ConfigEntity> for ConfigDto {
             refresh_token_validity_seconds: e.refresh_token_validity_seconds,
             ai_model: e.ai_model,
             vector_similarity_threshold: e.vector_similarity_threshold,
+            password_min_length: e.password_min_length,
+            password_min_char_classes: e.password_min_char_classes,
+            password_min_strength: e.password_min_strength,
+            password_reject_personal_info: e.password_reject_personal_info,
+            password_check_breached: e.password_check_breached,
+            login_backoff_after: e.login_backoff_after,
+            login_backoff_max_seconds: e.login_backoff_max_seconds,
+            lockout_threshold: e.lockout_threshold,
+            lockout_ip_threshold: e.lockout_ip_threshold,
+            lockout_duration_seconds: e.lockout_duration_seconds,
         }
     }
 }
@@ -60,6 +122,16 @@ impl From<&ConfigDto> for ConfigEntity {
             refresh_token_validity_seconds: d.refresh_token_validity_seconds,
             ai_model: d.ai_model.clone(),
             vector_similarity_threshold: d.vector_similarity_threshold,
+            password_min_length: d.password_min_length,
+            password_min_char_classes: d.password_min_char_classes,
+            password_min_strength: d.password_min_strength,
+            password_reject_personal_info: d.password_reject_personal_info,
+            password_check_breached: d.password_check_breached,
+            login_backoff_after: d.login_backoff_after,
+            login_backoff_max_seconds: d.login_backoff_max_seconds,
+            lockout_threshold: d.lockout_threshold,
+            lockout_ip_threshold: d.lockout_ip_threshold,
+            lockout_duration_seconds: d.lockout_duration_seconds,
         }
     }
 }
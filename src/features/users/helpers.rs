@@ -1,10 +1,13 @@
 use crate::utils::error::{Error, Result};
 use actix_web::cookie::{time::Duration as CookieDuration, Cookie, SameSite};
-use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use num_traits::FromPrimitive;
 
 pub const COOKIE_DEVICE_ID: &str = "__Host-device_id";
 pub const COOKIE_USER_ID: &str = "__Host-user_id";
+pub const COOKIE_ACCESS_TOKEN: &str = "__Host-access_token";
+pub const COOKIE_REFRESH_TOKEN: &str = "__Host-refresh_token";
+pub const COOKIE_LOGIN_WITH_EMAIL: &str = "__Host-login_with_email";
+pub const COOKIE_LOGIN_WITH_PHONE: &str = "__Host-login_with_phone";
 
 pub fn host_cookie(name: &str, value: String, max_age_seconds: i64, http_only: bool) -> Cookie<'_> {
     let mut c = Cookie::build(name.to_owned(), value)
@@ -21,13 +24,29 @@ pub fn host_cookie(name: &str, value: String, max_age_seconds: i64, http_only: b
     c
 }
 
-/// Verify Argon2 hash that you stored on create().
-pub fn verify_password(stored_hash: &str, plain: &str) -> Result<bool> {
-    let parsed = PasswordHash::new(stored_hash)
-        .map_err(|_| Error::Unexpected("invalid stored password hash".into()))?;
-    Ok(Argon2::default()
-        .verify_password(plain.as_bytes(), &parsed)
-        .is_ok())
+/// Normalize a user-typed phone number to E.164 (`+` and up to 15 digits).
+/// Spaces, dashes, dots and parentheses are dropped and a leading `00` becomes `+`.
+/// Numbers without a country code are rejected: there is no default region to assume.
+pub fn normalize_e164(raw: &str) -> Result<String> {
+    let compact: String = raw
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+        .collect();
+    let digits = compact
+        .strip_prefix('+')
+        .or_else(|| compact.strip_prefix("00"))
+        .ok_or_else(|| {
+            Error::Validation("phone number must start with + and a country code".into())
+        })?;
+
+    let valid = (7..=15).contains(&digits.len())
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && !digits.starts_with('0');
+    if !valid {
+        return Err(Error::Validation("invalid phone number".into()));
+    }
+    Ok(format!("+{digits}"))
 }
 
 // src/features/auth/ip_logging.rs
@@ -1,12 +1,16 @@
 mod db;
 mod helpers;
+mod password_hashing;
+mod password_policy;
 mod repo;
 mod routes;
 mod service;
 pub mod types;
 
 pub(super) use db::*;
-pub(super) use helpers::*;
+pub use helpers::*;
+pub use password_hashing::*;
+pub use password_policy::*;
 pub(super) use repo::*;
 pub use routes::*;
 pub use service::*;
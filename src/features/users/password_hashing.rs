@@ -0,0 +1,111 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordVerifier, Version};
+use pbkdf2::Pbkdf2;
+use scrypt::Scrypt;
+
+use crate::utils::error::{Error, Result};
+
+/// Hashes new passwords with Argon2id at the configured cost and verifies
+/// every format we accept: Argon2 at any cost plus hashes imported from the
+/// legacy system (bcrypt, and scrypt / PBKDF2 PHC strings).
+#[derive(Clone)]
+pub struct PasswordHashing {
+    params: Params,
+}
+
+/// Result of checking a password against a stored hash.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordCheck {
+    Mismatch,
+    Match,
+    /// right password, but the hash is weaker than what we issue today:
+    /// store a fresh `hash()` of it
+    MatchOutdated,
+}
+
+impl PasswordHashing {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| Error::Validation(format!("invalid argon2 params: {e}")))?;
+        Ok(Self { params })
+    }
+
+    /// `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS`, `ARGON2_PARALLELISM`; unset
+    /// ones keep the argon2 crate defaults (OWASP's 19 MiB, 2 passes, 1 lane).
+    pub fn from_env() -> Result<Self> {
+        fn var(name: &str, default: u32) -> Result<u32> {
+            match std::env::var(name) {
+                Ok(v) => v
+                    .parse()
+                    .map_err(|_| Error::Validation(format!("{name} must be a number"))),
+                Err(_) => Ok(default),
+            }
+        }
+        Self::new(
+            var("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST)?,
+            var("ARGON2_ITERATIONS", Params::DEFAULT_T_COST)?,
+            var("ARGON2_PARALLELISM", Params::DEFAULT_P_COST)?,
+        )
+    }
+
+    /// PHC string (`$argon2id$v=19$m=..,t=..,p=..$salt$hash`); the salt lives in it.
+    pub fn hash(&self, plain: &str) -> Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(self
+            .argon2()
+            .hash_password(plain.as_bytes(), &salt)
+            .map_err(|_| Error::Unexpected("failed to hash password".into()))?
+            .to_string())
+    }
+
+    pub fn verify(&self, stored_hash: &str, plain: &str) -> Result<PasswordCheck> {
+        let invalid = || Error::Unexpected("invalid stored password hash".into());
+
+        // bcrypt predates PHC: `$2b$12$<salt+hash>`
+        if stored_hash.starts_with("$2") {
+            return Ok(
+                match bcrypt::verify(plain, stored_hash).map_err(|_| invalid())? {
+                    true => PasswordCheck::MatchOutdated,
+                    false => PasswordCheck::Mismatch,
+                },
+            );
+        }
+
+        let parsed = PasswordHash::new(stored_hash).map_err(|_| invalid())?;
+        let algorithm = parsed.algorithm.as_str();
+        let matches = match algorithm {
+            "argon2id" | "argon2i" | "argon2d" => Argon2::default()
+                .verify_password(plain.as_bytes(), &parsed)
+                .is_ok(),
+            "scrypt" => Scrypt.verify_password(plain.as_bytes(), &parsed).is_ok(),
+            "pbkdf2" | "pbkdf2-sha256" | "pbkdf2-sha512" => {
+                Pbkdf2.verify_password(plain.as_bytes(), &parsed).is_ok()
+            }
+            other => {
+                return Err(Error::Unexpected(format!(
+                    "unsupported password hash algorithm: {other}"
+                )))
+            }
+        };
+        if !matches {
+            return Ok(PasswordCheck::Mismatch);
+        }
+
+        let current = algorithm == Algorithm::Argon2id.as_str()
+            && parsed.version == Some(Version::V0x13.into())
+            && Params::try_from(&parsed).is_ok_and(|p| {
+                p.m_cost() == self.params.m_cost()
+                    && p.t_cost() == self.params.t_cost()
+                    && p.p_cost() == self.params.p_cost()
+            });
+        Ok(if current {
+            PasswordCheck::Match
+        } else {
+            PasswordCheck::MatchOutdated
+        })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
@@ -0,0 +1,458 @@
+use std::sync::Arc;
+
+use crate::{
+    features::{clients::BreachedPasswordsClient, system::ConfigService},
+    utils::error::{Error, Result},
+};
+
+/// Passwords and words attackers try first, most common first (the rank is
+/// the guess count). Kept short: the breached corpus covers the long tail.
+const COMMON_PASSWORDS: &[&str] = &[
+    "password",
+    "123456",
+    "12345678",
+    "qwerty",
+    "123456789",
+    "12345",
+    "1234",
+    "111111",
+    "1234567",
+    "dragon",
+    "123123",
+    "baseball",
+    "abc123",
+    "football",
+    "monkey",
+    "letmein",
+    "shadow",
+    "master",
+    "666666",
+    "qwertyuiop",
+    "123321",
+    "mustang",
+    "1234567890",
+    "michael",
+    "654321",
+    "superman",
+    "1qaz2wsx",
+    "7777777",
+    "121212",
+    "000000",
+    "qazwsx",
+    "123qwe",
+    "killer",
+    "trustno1",
+    "jordan",
+    "jennifer",
+    "zxcvbnm",
+    "asdfgh",
+    "hunter",
+    "buster",
+    "soccer",
+    "harley",
+    "batman",
+    "andrew",
+    "tigger",
+    "sunshine",
+    "iloveyou",
+    "2000",
+    "charlie",
+    "robert",
+    "thomas",
+    "hockey",
+    "ranger",
+    "daniel",
+    "starwars",
+    "klaster",
+    "112233",
+    "george",
+    "computer",
+    "michelle",
+    "jessica",
+    "pepper",
+    "1111",
+    "zxcvbn",
+    "555555",
+    "11111111",
+    "131313",
+    "freedom",
+    "777777",
+    "pass",
+    "maggie",
+    "159753",
+    "aaaaaa",
+    "ginger",
+    "princess",
+    "joshua",
+    "cheese",
+    "amanda",
+    "summer",
+    "love",
+    "ashley",
+    "nicole",
+    "chelsea",
+    "biteme",
+    "matthew",
+    "access",
+    "yankees",
+    "987654321",
+    "dallas",
+    "austin",
+    "thunder",
+    "taylor",
+    "matrix",
+    "welcome",
+    "admin",
+    "login",
+    "passw0rd",
+    "secret",
+    "changeme",
+    "forest",
+    "gate",
+    "forestgate",
+];
+
+/// Runs that count as one pattern (and their reverses), zxcvbn's sequence
+/// and spatial matchers in miniature.
+const SEQUENCES: &[&str] = &[
+    "abcdefghijklmnopqrstuvwxyz",
+    "01234567890",
+    "qwertyuiop",
+    "asdfghjkl",
+    "zxcvbnm",
+    "1qaz2wsx3edc4rfv5tgb6yhn7ujm8ik9ol0p",
+    "!@#$%^&*()",
+];
+
+/// Policy knobs from the system config (`password_*` columns).
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub min_char_classes: usize,
+    pub min_strength: u8,
+    pub reject_personal_info: bool,
+    pub check_breached: bool,
+}
+
+/// Checks new passwords (sign-up, change, reset) against the configured policy.
+#[derive(Clone)]
+pub struct PasswordPolicyService {
+    config_service: Arc<ConfigService>,
+    breached: Option<BreachedPasswordsClient>,
+}
+
+impl PasswordPolicyService {
+    pub fn new(
+        config_service: Arc<ConfigService>,
+        breached: Option<BreachedPasswordsClient>,
+    ) -> Self {
+        Self {
+            config_service,
+            breached,
+        }
+    }
+
+    /// `Error::Validation` naming every rule the password breaks.
+    pub async fn check(&self, password: &str, email: &str, username: &str) -> Result<()> {
+        let cfg = self.config_service.get().await?;
+        let policy = PasswordPolicy {
+            min_length: cfg.password_min_length.max(0) as usize,
+            min_char_classes: cfg.password_min_char_classes.max(0) as usize,
+            min_strength: cfg.password_min_strength.clamp(0, 4) as u8,
+            reject_personal_info: cfg.password_reject_personal_info,
+            check_breached: cfg.password_check_breached,
+        };
+
+        let mut problems = policy.violations(password, &[email, username]);
+        if policy.check_breached {
+            if let Some(breached) = &self.breached {
+                if breached.contains(password).await? {
+                    problems.push("appears in a known data breach".to_string());
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(format!(
+                "password {}",
+                problems.join("; ")
+            )))
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Every rule `password` breaks, except the breach check (which needs I/O).
+    /// `personal` holds values the password must not contain (email, username).
+    pub fn violations(&self, password: &str, personal: &[&str]) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if password.chars().count() < self.min_length {
+            problems.push(format!(
+                "must be at least {} characters long",
+                self.min_length
+            ));
+        }
+        if char_classes(password) < self.min_char_classes {
+            problems.push(format!(
+                "must mix at least {} of: lowercase, uppercase, digits, symbols",
+                self.min_char_classes
+            ));
+        }
+        if strength_score(password) < self.min_strength {
+            problems.push("is too easy to guess".to_string());
+        }
+        if self.reject_personal_info && contains_personal_info(password, personal) {
+            problems.push("must not contain your email or username".to_string());
+        }
+        problems
+    }
+}
+
+/// zxcvbn-style score: 0 (< 10^3 guesses) .. 4 (>= 10^10 guesses).
+pub fn strength_score(password: &str) -> u8 {
+    match estimate_guesses(password).log10() {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+/// How many of lowercase, uppercase, digits and symbols appear.
+fn char_classes(password: &str) -> usize {
+    [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_ascii_digit()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ]
+    .iter()
+    .filter(|present| **present)
+    .count()
+}
+
+fn contains_personal_info(password: &str, personal: &[&str]) -> bool {
+    let password = password.to_lowercase();
+    personal
+        .iter()
+        .flat_map(|value| {
+            let value = value.to_lowercase();
+            let local = value.split('@').next().unwrap_or_default().to_string();
+            [value, local]
+        })
+        .any(|token| token.chars().count() >= 3 && password.contains(&token))
+}
+
+/// Cheapest way we know to guess `password`: brute force, a common word with
+/// digits/symbols around it, a repeated chunk, or a keyboard/alphabet run.
+fn estimate_guesses(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    if chars.is_empty() {
+        return 1.0;
+    }
+    let mut best = brute_force_guesses(password);
+
+    // repeated unit: "abcabcabc", "aaaaaa"
+    for period in 1..=chars.len() / 2 {
+        if chars.len().is_multiple_of(period) && chars.chunks(period).all(|c| c == &chars[..period])
+        {
+            let unit: String = chars[..period].iter().collect();
+            let repeats = (chars.len() / period) as f64;
+            best = best.min(estimate_guesses(&unit) * repeats);
+            break;
+        }
+    }
+
+    let lower = password.to_lowercase();
+    if is_sequence(&lower) {
+        best = best.min(chars.len() as f64 * 26.0);
+    }
+
+    // dictionary word, with l33t, capitals and affixes like "Password123!";
+    // digits are only stripped as a second try, words like "trustno1" keep them
+    let word = lower.trim_matches(|c: char| !c.is_alphanumeric());
+    let core = word
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .trim_start_matches(|c: char| c.is_ascii_digit());
+    for stem in [word, core] {
+        let unleeted = unleet(stem);
+        for candidate in [stem.to_string(), unleeted.clone()] {
+            if candidate.is_empty() {
+                continue;
+            }
+            let base = COMMON_PASSWORDS
+                .iter()
+                .position(|w| *w == candidate)
+                .map(|rank| (rank + 1) as f64)
+                .or_else(|| is_sequence(&candidate).then_some(candidate.len() as f64 * 26.0));
+            if let Some(base) = base {
+                let affix_len = chars.len().saturating_sub(stem.chars().count());
+                let mut guesses = base * 10f64.powi(affix_len as i32);
+                if password.chars().any(|c| c.is_uppercase()) {
+                    guesses *= 2.0;
+                }
+                if candidate == unleeted && unleeted != stem {
+                    guesses *= 2.0;
+                }
+                best = best.min(guesses);
+            }
+        }
+    }
+
+    best.max(1.0)
+}
+
+fn brute_force_guesses(password: &str) -> f64 {
+    let mut cardinality = 0.0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        cardinality += 26.0;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        cardinality += 26.0;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        cardinality += 10.0;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        cardinality += 33.0;
+    }
+    if !password.is_ascii() {
+        cardinality += 100.0;
+    }
+    f64::powi(cardinality, password.chars().count() as i32)
+}
+
+/// Whole string is a run (>= 3) of one of `SEQUENCES`, either direction.
+fn is_sequence(lower: &str) -> bool {
+    lower.chars().count() >= 3
+        && SEQUENCES.iter().any(|seq| {
+            let reversed: String = seq.chars().rev().collect();
+            seq.contains(lower) || reversed.contains(lower)
+        })
+}
+
+fn unleet(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | '!' => 'i',
+            '3' => 'e',
+            '4' | '@' => 'a',
+            '5' | '$' => 's',
+            '7' => 't',
+            other => other,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 10,
+            min_char_classes: 3,
+            min_strength: 3,
+            reject_personal_info: true,
+            check_breached: false,
+        }
+    }
+
+    #[test]
+    fn common_passwords_stay_weak_in_any_disguise() {
+        for password in [
+            "",
+            "password",
+            "Password",
+            "Password1",
+            "Password123!",
+            "P@ssw0rd!",
+            "letmein!",
+            "Trustno1",
+            "trustno1",
+            "abc123",
+        ] {
+            assert!(strength_score(password) <= 1, "{password:?}");
+        }
+        assert_eq!(strength_score("password"), 0);
+        assert_eq!(strength_score("P@ssw0rd!"), 0);
+    }
+
+    #[test]
+    fn runs_and_repeats_are_cheap() {
+        for password in [
+            "abcdefghij",
+            "jihgfedcba",
+            "0123456789",
+            "qwertyuiop",
+            "1qaz2wsx3edc",
+            "aaaaaaaaaaaa",
+            "abcabcabcabc",
+            "zxcvbnmzxcvbnm",
+        ] {
+            assert!(strength_score(password) <= 1, "{password:?}");
+        }
+    }
+
+    #[test]
+    fn random_strings_score_by_length() {
+        assert_eq!(strength_score("xk"), 0);
+        assert_eq!(strength_score("xkqv"), 1);
+        assert_eq!(strength_score("xkqvb"), 2);
+        assert_eq!(strength_score("xkqvbz"), 3);
+        assert_eq!(strength_score("x7#Qm9!vLp2@Rz"), 4);
+    }
+
+    #[test]
+    fn guesses_never_drop_below_one() {
+        assert_eq!(estimate_guesses(""), 1.0);
+        assert!(estimate_guesses("a") >= 1.0);
+    }
+
+    #[test]
+    fn counts_character_classes() {
+        assert_eq!(char_classes("abc"), 1);
+        assert_eq!(char_classes("abcABC"), 2);
+        assert_eq!(char_classes("abcABC123"), 3);
+        assert_eq!(char_classes("abcABC123!"), 4);
+    }
+
+    #[test]
+    fn finds_personal_info_case_insensitively() {
+        let personal = ["Jane.Doe@example.com", "jd"];
+        assert!(contains_personal_info("xJANE.DOE9!", &personal));
+        assert!(contains_personal_info("jane.doe@example.com1", &personal));
+        // tokens shorter than three characters are ignored
+        assert!(!contains_personal_info("jd-x7#Qm9!vLp2", &personal));
+    }
+
+    #[test]
+    fn reports_every_violation() {
+        let problems = policy().violations("jane", &["jane@example.com", "jane"]);
+        assert_eq!(problems.len(), 4, "{problems:?}");
+
+        assert!(policy()
+            .violations("x7#Qm9!vLp2@Rz", &["jane@example.com", "jane"])
+            .is_empty());
+    }
+
+    #[test]
+    fn personal_info_is_allowed_when_the_rule_is_off() {
+        let lenient = PasswordPolicy {
+            reject_personal_info: false,
+            ..policy()
+        };
+        assert!(lenient
+            .violations("jane-x7#Qm9!vLp2@Rz", &["jane@example.com", "jane"])
+            .is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 use super::User;
-use crate::features::users::{types::CreateUserDto, LoginMethod};
+use crate::features::{identities::IdentityKind, users::types::CreateUserDto};
 use chrono::Utc;
 use sqlx::{PgPool, Postgres, QueryBuilder};
 use time::Date;
@@ -18,7 +18,7 @@ impl UserRepository {
         &self,
         email_verified: Option<bool>,
         phone_number_verified: Option<bool>,
-        login_method: Option<&str>,
+        identity: Option<IdentityKind>,
         limit: i32,
         offset: i32,
     ) -> Result<(Vec<User>, i64), sqlx::Error> {
@@ -32,8 +32,6 @@ impl UserRepository {
                 is_email_verified,
                 is_phone_verified,
                 password_hash,
-                salt,
-                login_method,
                 created_at,
                 updated_at,
                 deleted_at
@@ -49,9 +47,10 @@ impl UserRepository {
         if let Some(v) = phone_number_verified {
             qb.push(" AND is_phone_verified = ").push_bind(v);
         }
-        if let Some(lm) = login_method {
-            // Compare against text (works well if column is a PG enum)
-            qb.push(" AND login_method = ").push_bind(lm);
+        if let Some(kind) = identity {
+            qb.push(" AND EXISTS (SELECT 1 FROM user_identities i WHERE i.user_id = users.id AND i.kind = ")
+                .push_bind(kind)
+                .push(")");
         }
         // if let Some(from) = created_from {
         //     // Compare date part only
@@ -84,8 +83,11 @@ impl UserRepository {
         if let Some(v) = phone_number_verified {
             count_qb.push(" AND is_phone_verified = ").push_bind(v);
         }
-        if let Some(ref lm) = login_method {
-            count_qb.push(" AND login_method = ").push_bind(lm);
+        if let Some(kind) = identity {
+            count_qb
+                .push(" AND EXISTS (SELECT 1 FROM user_identities i WHERE i.user_id = users.id AND i.kind = ")
+                .push_bind(kind)
+                .push(")");
         }
         // if let Some(from) = created_from {
         //     count_qb.push(" AND created_at::date >= ").push_bind(from);
@@ -112,7 +114,6 @@ impl UserRepository {
         &self,
         user_dto: CreateUserDto,
         password_hash: String,
-        salt: Vec<u8>,
     ) -> Result<User, sqlx::Error> {
         let user = sqlx::query_as!(
             User,
@@ -123,24 +124,20 @@ impl UserRepository {
                 email, 
                 phone_number, 
                 password_hash, 
-                salt, 
                 is_email_verified, 
                 is_phone_verified, 
-                login_method, 
                 created_at, 
                 updated_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             RETURNING 
                 id, 
                 username, 
                 email, 
                 phone_number, 
                 password_hash, 
-                salt, 
                 is_email_verified, 
                 is_phone_verified, 
-                login_method, 
                 created_at, 
                 updated_at, 
                 deleted_at
@@ -149,10 +146,8 @@ impl UserRepository {
             user_dto.email,
             user_dto.phone_number,
             password_hash,
-            salt,
             true,  // is_email_verified - every user is created after email verification
             false, // is_phone_verified
-            user_dto.login_method,
             Utc::now(),
             Utc::now()
         )
@@ -172,10 +167,8 @@ impl UserRepository {
                 email,
                 phone_number,
                 password_hash,
-                salt,
                 is_email_verified,
                 is_phone_verified,
-                login_method,
                 created_at,
                 updated_at,
                 deleted_at
@@ -200,10 +193,8 @@ impl UserRepository {
                 email,
                 phone_number,
                 password_hash,
-                salt,
                 is_email_verified,
                 is_phone_verified,
-                login_method,
                 created_at,
                 updated_at,
                 deleted_at
@@ -228,10 +219,8 @@ impl UserRepository {
                 email,
                 phone_number,
                 password_hash,
-                salt,
                 is_email_verified,
                 is_phone_verified,
-                login_method,
                 created_at,
                 updated_at,
                 deleted_at
@@ -245,4 +234,70 @@ impl UserRepository {
 
         Ok(user)
     }
+
+    /// Owner of a verified number; unverified numbers never identify anyone.
+    pub async fn find_by_verified_phone(&self, phone: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            r#"
+            SELECT *
+            FROM users
+            WHERE phone_number = $1 AND is_phone_verified AND deleted_at IS NULL
+            "#,
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn set_password(&self, user_id: i64, password_hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET password_hash = $2, updated_at = now()
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(password_hash)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Swap `old_hash` for `new_hash`; false if the password changed meanwhile.
+    pub async fn replace_password_hash(
+        &self,
+        user_id: i64,
+        old_hash: &str,
+        new_hash: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET password_hash = $3
+            WHERE id = $1 AND password_hash = $2 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(old_hash)
+        .bind(new_hash)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    pub async fn set_verified_phone(&self, user_id: i64, phone: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET phone_number = $2, is_phone_verified = TRUE, updated_at = now()
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
 }
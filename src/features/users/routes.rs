@@ -1,16 +1,36 @@
-use actix_web::{post, web, HttpRequest, Responder};
+use actix_web::{
+    cookie::{Cookie, SameSite},
+    get, post, put, web, HttpRequest, HttpResponse, Responder,
+};
+use time::Duration;
 use validator::Validate;
 
-use crate::features::users::{types::UserLoginReq, UserService};
+use crate::{
+    features::{
+        clients::EmailClient,
+        mfa::types::MfaChallengeResp,
+        users::{
+            types::{
+                ChangePasswordReq, ForgotPasswordReq, LoginWithEmailReq, LoginWithEmailResp,
+                LoginWithEmailVerifyReq, PhoneCodeReq, PhoneNumberReq, ResetPasswordReq, UserDto,
+                UserLoginReq,
+            },
+            UserService, COOKIE_LOGIN_WITH_EMAIL, COOKIE_LOGIN_WITH_PHONE, LOGIN_EMAIL_TTL_SECONDS,
+            PHONE_OTP_TTL_SECONDS,
+        },
+    },
+    infrastructure::middlewares::auth::AuthenticatedUser,
+};
 
 #[utoipa::path(
     post,
     path="/users/login",
     tag="users",
     responses(
-        (status = 200, description = "Prepare user for authentication"),
+        (status = 200, description = "Tokens, or a challenge for `/auth/mfa/verify` when 2FA is enabled", body = MfaChallengeResp),
         (status = 403, description = "Forbidden"),
-        (status = 429, description = "Too many requests"),
+        (status = 423, description = "Locked out after too many failed logins; see `Retry-After` and the emailed unlock link"),
+        (status = 429, description = "Backing off after failed logins; see `Retry-After`"),
     )
 )]
 #[post("/users/login")]
@@ -18,10 +38,299 @@ pub async fn login(
     req: HttpRequest,
     payload: web::Json<UserLoginReq>,
     user_service: web::Data<UserService>,
+    email_client: web::Data<EmailClient>,
 ) -> actix_web::Result<impl Responder> {
     println!("{:?}", payload);
     if let Err(errors) = payload.validate() {
         return Ok(actix_web::HttpResponse::BadRequest().json(errors));
     }
-    user_service.login(&req, &payload).await
+    user_service.login(&req, &payload, &email_client).await
+}
+
+#[utoipa::path(
+    post,
+    path="/users/login/with-email",
+    tag="users",
+    request_body = LoginWithEmailReq,
+    responses(
+        (status = 200, description = "If the account exists, a code and a magic link are emailed; the cookie binds them to this browser", body = LoginWithEmailResp),
+        (status = 429, description = "Too many requests"),
+    )
+)]
+#[post("/users/login/with-email")]
+pub async fn login_with_email(
+    payload: web::Json<LoginWithEmailReq>,
+    user_service: web::Data<UserService>,
+    email_client: web::Data<EmailClient>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+
+    let cookie_value = user_service.send_login_email(&payload.email, &email_client);
+    let cookie = Cookie::build(COOKIE_LOGIN_WITH_EMAIL, cookie_value)
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(LOGIN_EMAIL_TTL_SECONDS)) // same as the code TTL
+        .path("/") // required for __Host-*
+        .finish();
+
+    let mut resp = HttpResponse::Ok();
+    resp.cookie(cookie);
+    Ok(resp.json(LoginWithEmailResp { ok: true }))
+}
+
+#[utoipa::path(
+    post,
+    path="/users/login/with-email/verify",
+    tag="users",
+    request_body = LoginWithEmailVerifyReq,
+    responses(
+        (status = 200, description = "Code or magic link accepted, tokens issued"),
+        (status = 400, description = "Missing device cookie"),
+        (status = 409, description = "Invalid or expired code"),
+        (status = 429, description = "Too many requests"),
+    )
+)]
+#[post("/users/login/with-email/verify")]
+pub async fn verify_login_with_email(
+    req: HttpRequest,
+    payload: web::Json<LoginWithEmailVerifyReq>,
+    user_service: web::Data<UserService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    user_service
+        .verify_login_email(&req, payload.code.as_deref(), payload.token.as_deref())
+        .await
+}
+
+#[utoipa::path(
+    get,
+    path="/users/me",
+    tag="users",
+    responses(
+        (status = 200, description = "Profile of the authenticated user", body = UserDto),
+        (status = 401, description = "Missing or invalid access token"),
+    )
+)]
+#[get("/users/me")]
+pub async fn me(
+    user: AuthenticatedUser,
+    user_service: web::Data<UserService>,
+) -> actix_web::Result<impl Responder> {
+    let profile = user_service.profile(user.uid).await?;
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+#[utoipa::path(
+    post,
+    path="/users/me/phone",
+    tag="users",
+    request_body = PhoneNumberReq,
+    responses(
+        (status = 202, description = "Code sent by SMS to the normalized number"),
+        (status = 400, description = "Not a valid international number"),
+        (status = 409, description = "Number verified by another account"),
+        (status = 429, description = "Too many requests"),
+    )
+)]
+#[post("/users/me/phone")]
+pub async fn add_phone(
+    user: AuthenticatedUser,
+    payload: web::Json<PhoneNumberReq>,
+    user_service: web::Data<UserService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+
+    user_service
+        .start_phone_verification(user.uid, &payload.phone_number)
+        .await?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[utoipa::path(
+    post,
+    path="/users/me/phone/verify",
+    tag="users",
+    request_body = PhoneCodeReq,
+    responses(
+        (status = 200, description = "Phone number verified", body = UserDto),
+        (status = 409, description = "Invalid or expired code, or number verified by another account meanwhile"),
+    )
+)]
+#[post("/users/me/phone/verify")]
+pub async fn verify_phone(
+    user: AuthenticatedUser,
+    payload: web::Json<PhoneCodeReq>,
+    user_service: web::Data<UserService>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    let profile = user_service
+        .confirm_phone_verification(user.uid, &payload.code)
+        .await?;
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+#[utoipa::path(
+    post,
+    path="/users/login/with-phone",
+    tag="users",
+    request_body = PhoneNumberReq,
+    responses(
+        (status = 200, description = "If the number is verified on an account, a code is sent by SMS; the cookie binds it to this browser", body = LoginWithEmailResp),
+        (status = 400, description = "Not a valid international number"),
+        (status = 429, description = "Too many requests"),
+    )
+)]
+#[post("/users/login/with-phone")]
+pub async fn login_with_phone(
+    payload: web::Json<PhoneNumberReq>,
+    user_service: web::Data<UserService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+
+    let cookie_value = user_service.send_login_sms(&payload.phone_number)?;
+    let cookie = Cookie::build(COOKIE_LOGIN_WITH_PHONE, cookie_value)
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(PHONE_OTP_TTL_SECONDS)) // same as the code TTL
+        .path("/") // required for __Host-*
+        .finish();
+
+    let mut resp = HttpResponse::Ok();
+    resp.cookie(cookie);
+    Ok(resp.json(LoginWithEmailResp { ok: true }))
+}
+
+#[utoipa::path(
+    post,
+    path="/users/login/with-phone/verify",
+    tag="users",
+    request_body = PhoneCodeReq,
+    responses(
+        (status = 200, description = "Code accepted, tokens issued"),
+        (status = 400, description = "Missing device cookie"),
+        (status = 409, description = "Invalid or expired code"),
+        (status = 429, description = "Too many requests"),
+    )
+)]
+#[post("/users/login/with-phone/verify")]
+pub async fn verify_login_with_phone(
+    req: HttpRequest,
+    payload: web::Json<PhoneCodeReq>,
+    user_service: web::Data<UserService>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    user_service.verify_login_sms(&req, &payload.code).await
+}
+
+#[utoipa::path(
+    put,
+    path="/users/me/password",
+    tag="users",
+    request_body = ChangePasswordReq,
+    responses(
+        (status = 204, description = "Password changed, every other session and API key signed out"),
+        (status = 400, description = "New password rejected by the password policy"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Current password is wrong"),
+        (status = 423, description = "Locked out after failed attempts"),
+        (status = 429, description = "Backing off after failed attempts"),
+    )
+)]
+#[put("/users/me/password")]
+pub async fn change_password(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    payload: web::Json<ChangePasswordReq>,
+    user_service: web::Data<UserService>,
+    email_client: web::Data<EmailClient>,
+) -> actix_web::Result<impl Responder> {
+    user.require_session()?;
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    user_service
+        .change_password(
+            &req,
+            user.uid,
+            user.sid,
+            &payload.current_password,
+            &payload.new_password,
+            &email_client,
+        )
+        .await
+}
+
+#[utoipa::path(
+    post,
+    path="/users/password/forgot",
+    tag="users",
+    request_body = ForgotPasswordReq,
+    responses(
+        (status = 202, description = "If the account exists, a reset link is emailed"),
+        (status = 429, description = "Too many requests"),
+    )
+)]
+#[post("/users/password/forgot")]
+pub async fn forgot_password(
+    payload: web::Json<ForgotPasswordReq>,
+    user_service: web::Data<UserService>,
+    email_client: web::Data<EmailClient>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+
+    // answer before looking the account up: same status and timing either way
+    let email = payload.into_inner().email;
+    actix_web::rt::spawn(async move {
+        if let Err(e) = user_service
+            .send_password_reset(&email, &email_client)
+            .await
+        {
+            tracing::error!("password reset email: {e}");
+        }
+    });
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[utoipa::path(
+    post,
+    path="/users/password/reset",
+    tag="users",
+    request_body = ResetPasswordReq,
+    responses(
+        (status = 204, description = "Password changed, every session signed out"),
+        (status = 400, description = "New password rejected by the password policy"),
+        (status = 409, description = "Invalid, used or expired reset token"),
+    )
+)]
+#[post("/users/password/reset")]
+pub async fn reset_password(
+    payload: web::Json<ResetPasswordReq>,
+    user_service: web::Data<UserService>,
+    email_client: web::Data<EmailClient>,
+) -> actix_web::Result<impl Responder> {
+    if let Err(errors) = payload.validate() {
+        return Ok(HttpResponse::BadRequest().json(errors));
+    }
+    user_service
+        .reset_password(&payload.token, &payload.new_password, &email_client)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
 }
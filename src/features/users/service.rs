@@ -1,55 +1,134 @@
 use actix_web::ResponseError;
 // src/features/users/user_service.rs
-use actix_web::{http::header, HttpRequest, HttpResponse};
+use actix_web::{
+    cookie::Cookie,
+    http::{header, StatusCode},
+    HttpRequest, HttpResponse,
+};
+use chrono::Datelike;
+use deadpool_redis::{redis, Pool};
+use password_hash::rand_core::{OsRng, RngCore};
 use sqlx::PgPool;
+use std::collections::HashMap;
+use std::future::Future;
 use std::net::IpAddr;
 use std::sync::Arc;
+use uuid::Uuid;
 
-use super::types::UserLoginReq;
-use crate::features::clients::MaxMindClient;
-use crate::features::system::ConfigService;
+use super::db::User;
+use super::types::{UserDto, UserLoginReq};
+use crate::features::auth::{AuthService, DpopRequest};
+use crate::features::clients::{EmailClient, MaxMindClient, SmsProvider};
+use crate::features::identities::{IdentityKind, IdentityRepository, UserIdentity};
+use crate::features::lockout::{LockoutService, LoginGate};
+use crate::features::mfa::{types::MfaChallengeResp, ChallengeOutcome, MfaProof, MfaService};
+use crate::features::onboarding::{get_client_ip, sha256_hex};
 use crate::features::users::helpers::{
-    host_cookie, log_login_attempt, verify_password, COOKIE_DEVICE_ID, COOKIE_USER_ID,
+    log_login_attempt, normalize_e164, COOKIE_DEVICE_ID, COOKIE_LOGIN_WITH_EMAIL,
+    COOKIE_LOGIN_WITH_PHONE,
 };
+use crate::features::users::password_hashing::{PasswordCheck, PasswordHashing};
+use crate::features::users::password_policy::PasswordPolicyService;
 use crate::features::users::repo::UserRepository;
+use crate::utils::crypto::{constant_time_eq, ClientHMAC};
 use crate::utils::error::{Error, Result};
-use crate::utils::token_service::TokenService;
+use crate::utils::token_service::TokenGrant;
+
+/// Frontend page that receives `?token=` and posts it to `/users/login/with-email/verify`.
+const DEFAULT_MAGIC_LINK_URL: &str = "/login/with-email";
+/// Lifetime of a login code / magic link (and of the cookie they are bound to).
+pub const LOGIN_EMAIL_TTL_SECONDS: i64 = 10 * 60;
+/// Lifetime of an SMS code (and of the phone login cookie).
+pub const PHONE_OTP_TTL_SECONDS: i64 = 5 * 60;
+/// Wrong codes allowed before a pending code is dropped.
+const OTP_MAX_ATTEMPTS: i64 = 5;
+/// Frontend page that receives `?token=` and posts it with the new password to `/users/password/reset`.
+const DEFAULT_PASSWORD_RESET_URL: &str = "/reset-password";
+/// Lifetime of a password reset link.
+const PASSWORD_RESET_TTL_SECONDS: i64 = 30 * 60;
+
+/// region Redis prefixes
+/// nonce -> hash { uid, code, attempts } of a pending passwordless login
+pub const LOGIN_OTP_PREFIX: &str = "otp:login:with_email:v1:";
+/// nonce -> hash { uid, code, attempts } of a pending phone login
+pub const LOGIN_PHONE_PREFIX: &str = "otp:login:with_phone:v1:";
+/// user id -> hash { phone, code, attempts } of a number being verified
+pub const PHONE_VERIFY_PREFIX: &str = "otp:phone:v1:verify:";
+/// sha256(reset token) -> user id
+pub const PASSWORD_RESET_PREFIX: &str = "auth:pwreset:v1:token:";
+/// user id -> sha256 of the newest reset token; older links stop working
+pub const PASSWORD_RESET_USER_PREFIX: &str = "auth:pwreset:v1:user:";
+/// endregion Redis prefixes
 
 #[derive(Clone)]
 pub struct UserService {
     pool: PgPool,
+    redis_pool: Pool,
     user_repo: UserRepository,
-    token_service: Arc<TokenService>,
-    config_service: Arc<ConfigService>,
+    identity_repo: IdentityRepository,
+    auth_service: AuthService,
     maxmind: Arc<MaxMindClient>,
+    sms_provider: Arc<dyn SmsProvider>,
+    mfa_service: MfaService,
+    hmac_client: ClientHMAC,
+    magic_link_url: String,
+    password_reset_url: String,
+    password_policy: PasswordPolicyService,
+    password_hashing: PasswordHashing,
+    lockout_service: LockoutService,
 }
 
 impl UserService {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pool: PgPool,
-        token_service: Arc<TokenService>,
-        config_service: Arc<ConfigService>,
+        redis_pool: Pool,
+        auth_service: AuthService,
         maxmind: Arc<MaxMindClient>,
+        sms_provider: Arc<dyn SmsProvider>,
+        mfa_service: MfaService,
+        hmac_client: ClientHMAC,
+        password_policy: PasswordPolicyService,
+        password_hashing: PasswordHashing,
+        lockout_service: LockoutService,
     ) -> Self {
         Self {
             pool: pool.clone(),
+            redis_pool,
             user_repo: UserRepository::new(pool.clone()),
-            token_service,
-            config_service,
+            identity_repo: IdentityRepository::new(pool.clone()),
+            auth_service,
             maxmind,
+            sms_provider,
+            mfa_service,
+            hmac_client,
+            magic_link_url: std::env::var("LOGIN_MAGIC_LINK_URL")
+                .unwrap_or_else(|_| DEFAULT_MAGIC_LINK_URL.into()),
+            password_reset_url: std::env::var("PASSWORD_RESET_URL")
+                .unwrap_or_else(|_| DEFAULT_PASSWORD_RESET_URL.into()),
+            password_policy,
+            password_hashing,
+            lockout_service,
         }
     }
 
+    pub async fn profile(&self, user_id: i64) -> Result<UserDto> {
+        self.user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(Error::from)?
+            .map(UserDto::from)
+            .ok_or(Error::NotFound)
+    }
+
     pub async fn login(
         &self,
         req: &HttpRequest,
         payload: &UserLoginReq,
+        email_client: &EmailClient,
     ) -> actix_web::Result<HttpResponse> {
         // 1) client IP
-        let client_ip: Option<IpAddr> = req
-            .connection_info()
-            .realip_remote_addr()
-            .and_then(|s| s.parse().ok());
+        let client_ip = get_client_ip(req);
 
         println!("IP: {:?}", client_ip);
 
@@ -78,6 +157,12 @@ impl UserService {
             Some(u) => u,
             None => {
                 println!("USER: {:?}", user_opt);
+                if let Some(refused) = self.login_gate(None, client_ip).await? {
+                    return Ok(refused);
+                }
+                self.lockout_service
+                    .record_failure(None, client_ip, email_client)
+                    .await?;
                 let _ = log_login_attempt(&self.pool, &self.maxmind, None, client_ip, false).await;
                 return Ok(Error::Unauthorized.error_response());
             }
@@ -85,24 +170,784 @@ impl UserService {
 
         println!("USER 2: {:?}", user);
 
-        // 3) password
-        let ok = verify_password(&user.password_hash, &payload.password)
+        // backoff / lockout: refused before the password is even looked at
+        if let Some(refused) = self.login_gate(Some(user.id), client_ip).await? {
+            return Ok(refused);
+        }
+
+        // 3) password, if the account signs in with one at all
+        let identity = self
+            .identity_repo
+            .find(IdentityKind::Password, None, &user.id.to_string())
+            .await
+            .map_err(Error::from)?;
+        let check = self
+            .password_hashing
+            .verify(&user.password_hash, &payload.password)
             .map_err(|e| Error::Unexpected(format!("password verify error: {e}")))?;
-        if !ok {
+        let Some(identity) = identity.filter(|_| check != PasswordCheck::Mismatch) else {
+            self.lockout_service
+                .record_failure(Some((user.id, &user.email)), client_ip, email_client)
+                .await?;
             let _ =
                 log_login_attempt(&self.pool, &self.maxmind, Some(user.id), client_ip, false).await;
             return Ok(Error::Unauthorized.error_response());
+        };
+        self.identity_repo
+            .touch(identity.id, None)
+            .await
+            .map_err(Error::from)?;
+
+        // legacy algorithm or old Argon2 costs: only now do we hold the plain password
+        if check == PasswordCheck::MatchOutdated {
+            self.upgrade_password_hash(user.id, &user.password_hash, &payload.password)
+                .await;
+        }
+
+        self.complete_login(req, user.id, client_ip, SecondFactor::Pending)
+            .await
+    }
+
+    /// Second half of a 2FA password login (`/auth/mfa/verify`). Behind the
+    /// same backoff and lockout as the password: a wrong code counts as a
+    /// failed login, so fresh challenges from repeated password logins do not
+    /// buy more guesses.
+    pub async fn complete_mfa_login(
+        &self,
+        req: &HttpRequest,
+        challenge_token: &str,
+        proof: MfaProof<'_>,
+        email_client: &EmailClient,
+    ) -> actix_web::Result<HttpResponse> {
+        let client_ip = get_client_ip(req);
+        let user_id = self.mfa_service.challenge_user(challenge_token).await?;
+        if let Some(refused) = self.login_gate(user_id, client_ip).await? {
+            return Ok(refused);
+        }
+
+        match self
+            .mfa_service
+            .verify_challenge(challenge_token, proof)
+            .await?
+        {
+            ChallengeOutcome::Passed(user_id) => {
+                self.complete_login(req, user_id, client_ip, SecondFactor::Proven)
+                    .await
+            }
+            ChallengeOutcome::Failed(user_id) => {
+                let user = match user_id {
+                    Some(id) => self.user_repo.find_by_id(id).await.map_err(Error::from)?,
+                    None => None,
+                };
+                self.lockout_service
+                    .record_failure(
+                        user.as_ref().map(|u| (u.id, u.email.as_str())),
+                        client_ip,
+                        email_client,
+                    )
+                    .await?;
+                let _ =
+                    log_login_attempt(&self.pool, &self.maxmind, user_id, client_ip, false).await;
+                Ok(Error::InvalidOtp("invalid or expired code".into()).error_response())
+            }
+        }
+    }
+
+    /// Tail of a login whose credential another service already checked
+    /// (passkeys, upstream providers, recovery codes): tokens or an MFA
+    /// challenge on success, a logged failure otherwise.
+    pub async fn finish_verified_login(
+        &self,
+        req: &HttpRequest,
+        outcome: VerifiedLogin,
+    ) -> actix_web::Result<HttpResponse> {
+        let client_ip = get_client_ip(req);
+        match outcome {
+            VerifiedLogin::User(user_id) => {
+                self.complete_login(req, user_id, client_ip, SecondFactor::Pending)
+                    .await
+            }
+            VerifiedLogin::MultiFactor(user_id) => {
+                self.complete_login(req, user_id, client_ip, SecondFactor::Proven)
+                    .await
+            }
+            VerifiedLogin::Rejected(user_id) => {
+                let _ =
+                    log_login_attempt(&self.pool, &self.maxmind, user_id, client_ip, false).await;
+                Ok(Error::Unauthorized.error_response())
+            }
+        }
+    }
+
+    /// `finish_verified_login` for credentials that can be guessed (recovery
+    /// codes): behind the same backoff and lockout as passwords, so `check`
+    /// does not even run while they hold, and a rejection counts as a failed
+    /// login of the account `email` names.
+    pub async fn finish_guarded_login(
+        &self,
+        req: &HttpRequest,
+        email: &str,
+        email_client: &EmailClient,
+        check: impl Future<Output = Result<VerifiedLogin>>,
+    ) -> actix_web::Result<HttpResponse> {
+        let client_ip = get_client_ip(req);
+        let user = self
+            .user_repo
+            .find_by_email(email)
+            .await
+            .map_err(Error::from)?;
+        if let Some(refused) = self.login_gate(user.as_ref().map(|u| u.id), client_ip).await? {
+            return Ok(refused);
+        }
+
+        // a pass is cleared by `complete_login`, once a second factor, if due, is in
+        let outcome = check.await?;
+        if let VerifiedLogin::Rejected(_) = outcome {
+            let account = user.as_ref().map(|u| (u.id, u.email.as_str()));
+            self.lockout_service
+                .record_failure(account, client_ip, email_client)
+                .await?;
+        }
+        self.finish_verified_login(req, outcome).await
+    }
+
+    /// Passwordless login (`IdentityKind::EmailOtp`), step 1: email a one-time
+    /// code and a magic link, both bound to a nonce. Returns the signed nonce
+    /// for the cookie. Unknown emails get a cookie too (and no email), and the
+    /// lookup and send run after the answer, so neither the response nor its
+    /// timing tells whether an account exists.
+    pub(super) fn send_login_email(&self, email: &str, email_client: &EmailClient) -> String {
+        // 1) nonce (32 bytes -> hex), signed into the cookie value
+        let nonce = new_nonce();
+        let cookie_value = self.hmac_client.encode_cookie_value(&nonce);
+
+        let service = self.clone();
+        let email = email.to_lowercase();
+        let email_client = email_client.clone();
+        actix_web::rt::spawn(async move {
+            if let Err(e) = service
+                .email_login_code(&nonce, &email, &email_client)
+                .await
+            {
+                tracing::error!("login email: {e}");
+            }
+        });
+        cookie_value
+    }
+
+    async fn email_login_code(
+        &self,
+        nonce: &str,
+        email: &str,
+        email_client: &EmailClient,
+    ) -> Result<()> {
+        let Some(identity) = self
+            .identity_repo
+            .find(IdentityKind::EmailOtp, None, email)
+            .await?
+        else {
+            return Ok(());
+        };
+        let Some(user) = self
+            .user_repo
+            .find_by_id(identity.user_id)
+            .await
+            .map_err(Error::from)?
+        else {
+            return Ok(());
+        };
+
+        // 2) 6-digit code, stored with the user under the nonce
+        let code = new_code();
+        self.put_pending_code(
+            &format!("{}{}", LOGIN_OTP_PREFIX, nonce),
+            &pending_login_fields(&identity, &code),
+            LOGIN_EMAIL_TTL_SECONDS,
+        )
+        .await?;
+
+        // 3) magic link: an HMAC over the nonce, only usable next to the cookie
+        let separator = if self.magic_link_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        let link = format!(
+            "{}{}token={}",
+            self.magic_link_url,
+            separator,
+            self.magic_link_token(nonce)
+        );
+
+        let text_body = format!(
+            "Your sign-in code is: {code}\n\nOr open this link in the same browser:\n{link}\n\nBoth expire in 10 minutes.\nIf you did not try to sign in, you can ignore this email."
+        );
+        let html_body = format!(
+            r#"
+<!doctype html>
+<html>
+  <body style="background:#f6f8fb;margin:0;padding:24px;font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',Roboto,Helvetica,Arial,sans-serif;color:#0f172a;">
+    <div style="text-align:center;margin:20px 0;">
+      <div style="display:inline-block;letter-spacing:6px;font-weight:700;font-size:28px;color:#111827;background:#f3f4f6;border-radius:12px;padding:12px 18px;">
+        {code}
+      </div>
+    </div>
+    <div style="text-align:center;margin:20px 0;">
+      <a href="{link}" style="color:#2563eb;">Sign in to Forest Gate</a>
+    </div>
+    © {year} Forest Gate
+  </body>
+</html>
+"#,
+            year = chrono::Utc::now().year()
+        );
+
+        email_client
+            .send_text_and_html(
+                &user.email,
+                "Your sign-in code",
+                Some(text_body.as_str()),
+                Some(html_body.as_str()),
+            )
+            .await
+    }
+
+    /// Passwordless login, step 2: check the code or magic-link token against
+    /// the nonce in the cookie, then finish exactly like a password login.
+    pub(super) async fn verify_login_email(
+        &self,
+        req: &HttpRequest,
+        code: Option<&str>,
+        token: Option<&str>,
+    ) -> actix_web::Result<HttpResponse> {
+        self.verify_pending_login(
+            req,
+            COOKIE_LOGIN_WITH_EMAIL,
+            LOGIN_OTP_PREFIX,
+            |nonce, fields| match (code, token) {
+                (Some(code), _) => code_matches(fields, code),
+                (None, Some(token)) => self.hmac_client.verify(&magic_link_input(nonce), token),
+                (None, None) => false,
+            },
+        )
+        .await
+    }
+
+    /// Forgot password, step 1: email a single-use reset link. Unknown emails
+    /// get the same (empty) answer, so the caller cannot probe for accounts.
+    pub(super) async fn send_password_reset(
+        &self,
+        email: &str,
+        email_client: &EmailClient,
+    ) -> Result<()> {
+        let Some(user) = self
+            .user_repo
+            .find_by_email(email)
+            .await
+            .map_err(Error::from)?
+        else {
+            return Ok(());
+        };
+        self.email_password_link(&user, PasswordLink::Reset, email_client)
+            .await
+    }
+
+    /// `POST /users/me/identities/password`: accounts without a password
+    /// (signed up through a provider, or whose password was unlinked) get a
+    /// link to choose one. The password is only set once the link is opened,
+    /// so a stolen access token cannot plant a permanent way in.
+    pub async fn send_password_setup(
+        &self,
+        user_id: i64,
+        email_client: &EmailClient,
+    ) -> Result<()> {
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(Error::from)?
+            .ok_or(Error::NotFound)?;
+        self.email_password_link(&user, PasswordLink::Setup, email_client)
+            .await
+    }
+
+    /// Stores a single-use token and emails the link that spends it with
+    /// `reset_password`; a newer link replaces the older one.
+    async fn email_password_link(
+        &self,
+        user: &User,
+        kind: PasswordLink,
+        email_client: &EmailClient,
+    ) -> Result<()> {
+        // only the hash is stored, a Redis dump holds no usable links
+        let token = new_nonce();
+        let token_hash = sha256_hex(&token);
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let _: () = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(format!("{}{}", PASSWORD_RESET_PREFIX, token_hash))
+            .arg(user.id)
+            .arg("EX")
+            .arg(PASSWORD_RESET_TTL_SECONDS)
+            .ignore()
+            .cmd("SET")
+            .arg(format!("{}{}", PASSWORD_RESET_USER_PREFIX, user.id))
+            .arg(&token_hash)
+            .arg("EX")
+            .arg(PASSWORD_RESET_TTL_SECONDS)
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+
+        let separator = if self.password_reset_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        let link = format!("{}{}token={}", self.password_reset_url, separator, token);
+
+        let (subject, action, request) = match kind {
+            PasswordLink::Reset => (
+                "Reset your password",
+                "Choose a new password",
+                "a password reset",
+            ),
+            PasswordLink::Setup => (
+                "Set a password",
+                "Choose a password",
+                "a password for your account",
+            ),
+        };
+        let text_body = format!(
+            "{action} with this link:\n{link}\n\nIt expires in 30 minutes and works once.\nIf you did not ask for {request}, you can ignore this email."
+        );
+        let html_body = format!(
+            r#"
+<!doctype html>
+<html>
+  <body style="background:#f6f8fb;margin:0;padding:24px;font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',Roboto,Helvetica,Arial,sans-serif;color:#0f172a;">
+    <div style="text-align:center;margin:20px 0;">
+      <a href="{link}" style="color:#2563eb;">{action}</a>
+    </div>
+    <p style="text-align:center;color:#6b7280;">The link expires in 30 minutes. If you did not ask for it, ignore this email.</p>
+    © {year} Forest Gate
+  </body>
+</html>
+"#,
+            year = chrono::Utc::now().year()
+        );
+
+        email_client
+            .send_text_and_html(
+                &user.email,
+                subject,
+                Some(text_body.as_str()),
+                Some(html_body.as_str()),
+            )
+            .await
+    }
+
+    /// Forgot password, step 2: spend the token, store the new password and
+    /// sign the user out everywhere (sessions and refresh tokens). A password
+    /// the policy rejects leaves the token unspent, so the link can be retried.
+    pub(super) async fn reset_password(
+        &self,
+        token: &str,
+        new_password: &str,
+        email_client: &EmailClient,
+    ) -> Result<()> {
+        let token_hash = sha256_hex(token);
+        let token_key = format!("{}{}", PASSWORD_RESET_PREFIX, token_hash);
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let invalid = || Error::InvalidOtp("invalid or expired reset token".into());
+        let user_id: Option<i64> = redis::cmd("GET")
+            .arg(&token_key)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        let user_id = user_id.ok_or_else(invalid)?;
+
+        // a newer reset email supersedes this one
+        let user_key = format!("{}{}", PASSWORD_RESET_USER_PREFIX, user_id);
+        let newest: Option<String> = redis::cmd("GET")
+            .arg(&user_key)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        if newest.as_deref() != Some(token_hash.as_str()) {
+            return Err(invalid());
         }
 
-        println!("PASSWORD: {:?}", ok);
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(Error::from)?
+            .ok_or_else(invalid)?;
+        self.password_policy
+            .check(new_password, &user.email, &user.username)
+            .await?;
+
+        // only now spend the token; whoever deletes it wins
+        let (spent,): (i64,) = redis::pipe()
+            .atomic()
+            .cmd("DEL")
+            .arg(&token_key)
+            .cmd("DEL")
+            .arg(&user_key)
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        if spent == 0 {
+            return Err(invalid());
+        }
+        let password_hash = self.password_hashing.hash(new_password)?;
+        self.user_repo
+            .set_password(user.id, &password_hash)
+            .await
+            .map_err(Error::from)?;
+        // the emailed link proves the account, so it may sign in with a password from now on
+        self.identity_repo
+            .link(user.id, IdentityKind::Password, None, &user.id.to_string(), None)
+            .await?;
+        self.auth_service.revoke_user(user.id).await?;
 
-        // 4) device cookie
+        // the password is changed either way; a lost notice is only logged
+        if let Err(e) = self
+            .notify_password_changed(&user.email, email_client)
+            .await
+        {
+            tracing::warn!(user_id = user.id, "password changed notice not sent: {e}");
+        }
+        Ok(())
+    }
+
+    /// `PUT /users/me/password`: the current password proves it is the owner
+    /// at the keyboard, the new one has to pass the password policy. Wrong
+    /// current passwords count like failed logins, so a stolen session cannot
+    /// guess it without limit. Every other session (and API key) is signed
+    /// out and the owner is told by email, as after a reset.
+    pub async fn change_password(
+        &self,
+        req: &HttpRequest,
+        user_id: i64,
+        session_id: Uuid,
+        current_password: &str,
+        new_password: &str,
+        email_client: &EmailClient,
+    ) -> actix_web::Result<HttpResponse> {
+        let client_ip = get_client_ip(req);
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(Error::from)?
+            .ok_or(Error::NotFound)?;
+        if let Some(refused) = self.login_gate(Some(user.id), client_ip).await? {
+            return Ok(refused);
+        }
+        if self
+            .password_hashing
+            .verify(&user.password_hash, current_password)?
+            == PasswordCheck::Mismatch
+        {
+            self.lockout_service
+                .record_failure(Some((user.id, &user.email)), client_ip, email_client)
+                .await?;
+            return Err(Error::Forbidden.into());
+        }
+        self.lockout_service.record_success(user.id).await?;
+        if current_password == new_password {
+            return Err(Error::Validation(
+                "new password must differ from the current one".into(),
+            )
+            .into());
+        }
+        self.password_policy
+            .check(new_password, &user.email, &user.username)
+            .await?;
+
+        let password_hash = self.password_hashing.hash(new_password)?;
+        self.user_repo
+            .set_password(user.id, &password_hash)
+            .await
+            .map_err(Error::from)?;
+        self.auth_service
+            .revoke_other_sessions(user.id, session_id)
+            .await?;
+
+        // the password is changed either way; a lost notice is only logged
+        if let Err(e) = self
+            .notify_password_changed(&user.email, email_client)
+            .await
+        {
+            tracing::warn!(user_id = user.id, "password changed notice not sent: {e}");
+        }
+        Ok(HttpResponse::NoContent().finish())
+    }
+
+    /// 429 while backing off after failed logins, 423 while locked out; both
+    /// with `Retry-After`. `None` lets the attempt through.
+    async fn login_gate(
+        &self,
+        user_id: Option<i64>,
+        client_ip: Option<IpAddr>,
+    ) -> Result<Option<HttpResponse>> {
+        let (status, code, retry_after, message) =
+            match self.lockout_service.check(user_id, client_ip).await? {
+                LoginGate::Open => return Ok(None),
+                LoginGate::Wait(seconds) => (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "TOO_MANY_REQUESTS",
+                    seconds,
+                    "too many failed logins, wait before trying again",
+                ),
+                LoginGate::Locked(seconds) => (
+                    StatusCode::LOCKED,
+                    "ACCOUNT_LOCKED",
+                    seconds,
+                    "too many failed logins, password sign-in is locked for now",
+                ),
+            };
+        Ok(Some(
+            HttpResponse::build(status)
+                .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                .json(serde_json::json!({ "code": code, "message": message })),
+        ))
+    }
+
+    /// Re-hash a verified password with the current settings. Only replaces
+    /// `old_hash`, so a concurrent password change wins; failures are logged
+    /// and the login goes on.
+    async fn upgrade_password_hash(&self, user_id: i64, old_hash: &str, plain: &str) {
+        let upgraded = match self.password_hashing.hash(plain) {
+            Ok(new_hash) => self
+                .user_repo
+                .replace_password_hash(user_id, old_hash, &new_hash)
+                .await
+                .map_err(Error::from),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = upgraded {
+            tracing::warn!(user_id, "password rehash failed: {e}");
+        }
+    }
+
+    /// Step 1 of adding a phone number: normalize it to E.164 and text a code
+    /// to it. Nothing is written to `users` until the code comes back.
+    pub async fn start_phone_verification(&self, user_id: i64, raw_phone: &str) -> Result<()> {
+        let phone = normalize_e164(raw_phone)?;
+        if let Some(owner) = self
+            .user_repo
+            .find_by_verified_phone(&phone)
+            .await
+            .map_err(Error::from)?
+        {
+            if owner.id != user_id {
+                return Err(Error::Conflict("phone number already in use".into()));
+            }
+        }
+
+        let code = new_code();
+        self.put_pending_code(
+            &format!("{}{}", PHONE_VERIFY_PREFIX, user_id),
+            &[("phone", phone.clone()), ("code", code.clone())],
+            PHONE_OTP_TTL_SECONDS,
+        )
+        .await?;
+
+        self.sms_provider
+            .send(
+                &phone,
+                &format!("Your Forest Gate verification code is {code}. It expires in 5 minutes."),
+            )
+            .await
+    }
+
+    /// Step 2: a matching code stores the number as the user's verified phone.
+    pub async fn confirm_phone_verification(&self, user_id: i64, code: &str) -> Result<UserDto> {
+        let fields = match self
+            .take_pending_code(&format!("{}{}", PHONE_VERIFY_PREFIX, user_id), |f| {
+                code_matches(f, code)
+            })
+            .await?
+        {
+            PendingCode::Accepted(fields) => fields,
+            _ => return Err(Error::InvalidOtp("invalid or expired code".into())),
+        };
+        let phone = fields
+            .get("phone")
+            .ok_or_else(|| Error::InvalidOtp("invalid or expired code".into()))?;
+
+        self.user_repo
+            .set_verified_phone(user_id, phone)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(db) if db.is_unique_violation() => {
+                    Error::Conflict("phone number already in use".into())
+                }
+                other => Error::from(other),
+            })?;
+        self.identity_repo
+            .replace(user_id, IdentityKind::Phone, phone)
+            .await?;
+
+        self.profile(user_id).await
+    }
+
+    /// Phone login (`IdentityKind::Phone`), step 1: text a code to a verified
+    /// number. Like the email flow, unknown numbers still get a cookie and the
+    /// lookup and send run after the answer.
+    pub(super) fn send_login_sms(&self, raw_phone: &str) -> Result<String> {
+        let phone = normalize_e164(raw_phone)?;
+        let nonce = new_nonce();
+        let cookie_value = self.hmac_client.encode_cookie_value(&nonce);
+
+        let service = self.clone();
+        actix_web::rt::spawn(async move {
+            if let Err(e) = service.text_login_code(&nonce, &phone).await {
+                tracing::error!("login sms: {e}");
+            }
+        });
+        Ok(cookie_value)
+    }
+
+    async fn text_login_code(&self, nonce: &str, phone: &str) -> Result<()> {
+        let Some(identity) = self
+            .identity_repo
+            .find(IdentityKind::Phone, None, phone)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        let code = new_code();
+        self.put_pending_code(
+            &format!("{}{}", LOGIN_PHONE_PREFIX, nonce),
+            &pending_login_fields(&identity, &code),
+            PHONE_OTP_TTL_SECONDS,
+        )
+        .await?;
+
+        self.sms_provider
+            .send(
+                phone,
+                &format!("Your Forest Gate sign-in code is {code}. It expires in 5 minutes."),
+            )
+            .await
+    }
+
+    /// Phone login, step 2.
+    pub(super) async fn verify_login_sms(
+        &self,
+        req: &HttpRequest,
+        code: &str,
+    ) -> actix_web::Result<HttpResponse> {
+        self.verify_pending_login(
+            req,
+            COOKIE_LOGIN_WITH_PHONE,
+            LOGIN_PHONE_PREFIX,
+            |_, fields| code_matches(fields, code),
+        )
+        .await
+    }
+
+    /// Second step shared by the passwordless flows: the nonce comes from the
+    /// signed `cookie`, the pending login from `prefix` + nonce, and `accept`
+    /// judges the answer. Every outcome lands in `login_attempts`.
+    async fn verify_pending_login(
+        &self,
+        req: &HttpRequest,
+        cookie: &str,
+        prefix: &str,
+        accept: impl FnOnce(&str, &HashMap<String, String>) -> bool,
+    ) -> actix_web::Result<HttpResponse> {
+        let client_ip = get_client_ip(req);
+        let invalid = || Error::InvalidOtp("invalid or expired code".into()).error_response();
+        let uid = |fields: &HashMap<String, String>| {
+            fields.get("uid").and_then(|v| v.parse::<i64>().ok())
+        };
+
+        // 1) nonce from the signed cookie
+        let Some(nonce) = req
+            .cookie(cookie)
+            .and_then(|c| self.hmac_client.decode_cookie_value(c.value()))
+        else {
+            return Ok(invalid());
+        };
+
+        // 2) pending login
+        let check = self
+            .take_pending_code(&format!("{}{}", prefix, nonce), |fields| {
+                accept(&nonce, fields)
+            })
+            .await?;
+        let user_id = match check {
+            PendingCode::Accepted(fields) => {
+                if let Some(identity_id) = fields.get("iid").and_then(|v| v.parse().ok()) {
+                    self.identity_repo
+                        .touch(identity_id, None)
+                        .await
+                        .map_err(Error::from)?;
+                }
+                uid(&fields)
+            }
+            PendingCode::Rejected(fields) => {
+                let _ =
+                    log_login_attempt(&self.pool, &self.maxmind, uid(&fields), client_ip, false)
+                        .await;
+                return Ok(invalid());
+            }
+            PendingCode::Missing => None,
+        };
+        let Some(user_id) = user_id else {
+            let _ = log_login_attempt(&self.pool, &self.maxmind, None, client_ip, false).await;
+            return Ok(invalid());
+        };
+
+        // 3) tokens (or the MFA challenge), then drop the cookie
+        let mut resp = self
+            .complete_login(req, user_id, client_ip, SecondFactor::Pending)
+            .await?;
+        if resp.status().is_success() {
+            resp.add_removal_cookie(&Cookie::build(cookie, "").path("/").finish())
+                .map_err(|e| Error::Unexpected(format!("removal cookie: {e}")))?;
+        }
+        Ok(resp)
+    }
+
+    /// Steps shared by every login method once the user is known: the 2FA
+    /// gate, device link, optional DPoP binding, tokens, `login_attempts` row,
+    /// cookies. With 2FA on and `second_factor` still pending, the login is
+    /// parked and the caller gets a challenge instead of tokens.
+    async fn complete_login(
+        &self,
+        req: &HttpRequest,
+        user_id: i64,
+        client_ip: Option<IpAddr>,
+        second_factor: SecondFactor,
+    ) -> actix_web::Result<HttpResponse> {
+        // 0) second factor: park the login and hand out a challenge instead of tokens
+        if second_factor == SecondFactor::Pending {
+            if let Some(challenge) = self.mfa_challenge(user_id).await? {
+                return Ok(HttpResponse::Ok()
+                    .insert_header((header::CACHE_CONTROL, "no-store"))
+                    .json(challenge));
+            }
+        }
+        // every factor is in: failed attempts before this one no longer count
+        self.lockout_service.record_success(user_id).await?;
+
+        // 1) device cookie
         let device_id_cookie = req.cookie(COOKIE_DEVICE_ID);
         let device_id: i64 = match device_id_cookie.and_then(|c| c.value().parse::<i64>().ok()) {
             Some(d) => d,
             None => {
                 let _ =
-                    log_login_attempt(&self.pool, &self.maxmind, Some(user.id), client_ip, false)
+                    log_login_attempt(&self.pool, &self.maxmind, Some(user_id), client_ip, false)
                         .await;
                 return Ok(
                     Error::Validation("missing or invalid device cookie".into()).error_response()
@@ -110,80 +955,231 @@ impl UserService {
             }
         };
 
-        // 5) ensure user_devices link
+        // 2) ensure user_devices link
         sqlx::query!(
             r#"
             INSERT INTO user_devices (user_id, device_id)
             VALUES ($1, $2)
             ON CONFLICT (user_id, device_id) DO NOTHING
             "#,
-            user.id,
+            user_id,
             device_id
         )
         .execute(&self.pool)
         .await
         .map_err(Error::from)?;
 
-        // 6) tokens
+        // 3) optional DPoP proof binds the tokens to the device's key
+        let dpop = DpopRequest::from_request(req);
+        let jkt = match dpop.proof {
+            Some(_) => Some(
+                self.auth_service
+                    .bind_device_key(user_id, device_id, &dpop)
+                    .await?,
+            ),
+            None => None,
+        };
+
+        // 4) tokens (starts a new refresh rotation family)
+        let grant = TokenGrant {
+            jkt,
+            ..Default::default()
+        };
         let tokens = self
-            .token_service
-            .mint_tokens(user.id, device_id)
+            .auth_service
+            .issue(user_id, device_id, client_ip, &grant)
             .await
             .map_err(|e| Error::Unexpected(format!("mint tokens: {e}")))?;
 
-        // 7) log success
-        let _ = log_login_attempt(&self.pool, &self.maxmind, Some(user.id), client_ip, true).await;
+        // 5) log success
+        let _ = log_login_attempt(&self.pool, &self.maxmind, Some(user_id), client_ip, true).await;
 
-        // 8) cookies + JSON
-        let cfg = self.config_service.get().await.map_err(Error::from)?;
-        let mut resp = HttpResponse::Ok();
+        // 6) cookies + JSON
+        Ok(self
+            .auth_service
+            .tokens_response(user_id, device_id, tokens)
+            .await?)
+    }
 
-        let user_id_cookie = host_cookie(
-            COOKIE_USER_ID,
-            user.id.to_string(),
-            cfg.refresh_token_validity_seconds as i64,
-            true,
-        );
-        resp.cookie(user_id_cookie);
+    /// A challenge for a login that proved one factor, `None` without 2FA.
+    pub async fn mfa_challenge(&self, user_id: i64) -> Result<Option<MfaChallengeResp>> {
+        if !self.mfa_service.is_enabled(user_id).await? {
+            return Ok(None);
+        }
+        self.mfa_service.create_challenge(user_id).await.map(Some)
+    }
 
-        let access_cookie = host_cookie(
-            "__Host-access_token",
-            tokens.access_token.clone(),
-            cfg.token_validity_seconds as i64,
-            true,
+    async fn notify_password_changed(&self, email: &str, email_client: &EmailClient) -> Result<()> {
+        let text_body = "Your Forest Gate password was just changed and every device was signed out.\n\nIf this was not you, reset your password right away and contact support.";
+        let html_body = format!(
+            r#"
+<!doctype html>
+<html>
+  <body style="background:#f6f8fb;margin:0;padding:24px;font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',Roboto,Helvetica,Arial,sans-serif;color:#0f172a;">
+    <p style="text-align:center;">Your password was just changed and every device was signed out.</p>
+    <p style="text-align:center;color:#6b7280;">If this was not you, reset your password right away and contact support.</p>
+    © {year} Forest Gate
+  </body>
+</html>
+"#,
+            year = chrono::Utc::now().year()
         );
-        resp.cookie(access_cookie);
-
-        // If you want refresh cookie, just uncomment:
-        if let Some(ref rt) = tokens.refresh_token {
-            let refresh_cookie = host_cookie(
-                "__Host-refresh_token",
-                rt.clone(),
-                cfg.refresh_token_validity_seconds as i64,
-                true,
-            );
-            resp.cookie(refresh_cookie);
-        }
-
-        #[derive(serde::Serialize)]
-        struct LoginResponse {
-            user_id: i64,
-            device_id: i64,
-            access_token: String,
-            access_expires_at: i64,
-            refresh_token: Option<String>,
-            refresh_expires_at: Option<i64>,
-        }
-
-        Ok(resp
-            .insert_header((header::CONTENT_TYPE, "application/json"))
-            .json(LoginResponse {
-                user_id: user.id,
-                device_id,
-                access_token: tokens.access_token,
-                access_expires_at: tokens.access_expires_at,
-                refresh_token: tokens.refresh_token,
-                refresh_expires_at: tokens.refresh_expires_at,
-            }))
+
+        email_client
+            .send_text_and_html(
+                email,
+                "Your password was changed",
+                Some(text_body),
+                Some(html_body.as_str()),
+            )
+            .await
     }
+
+    fn magic_link_token(&self, nonce: &str) -> String {
+        self.hmac_client.sign(&magic_link_input(nonce))
+    }
+
+    async fn put_pending_code(
+        &self,
+        key: &str,
+        fields: &[(&str, String)],
+        ttl_seconds: i64,
+    ) -> Result<()> {
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let _: () = redis::pipe()
+            .atomic()
+            .cmd("DEL")
+            .arg(key)
+            .ignore()
+            .cmd("HSET")
+            .arg(key)
+            .arg(fields)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(key)
+            .arg(ttl_seconds)
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Check an answer against the code stored under `key`. Wrong answers count
+    /// towards `OTP_MAX_ATTEMPTS`; a right one consumes the entry (single use).
+    async fn take_pending_code(
+        &self,
+        key: &str,
+        accept: impl FnOnce(&HashMap<String, String>) -> bool,
+    ) -> Result<PendingCode> {
+        let mut conn = self.redis_pool.get().await.map_err(Error::from)?;
+        let fields: HashMap<String, String> = redis::cmd("HGETALL")
+            .arg(key)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        if !fields.contains_key("code") {
+            return Ok(PendingCode::Missing);
+        }
+
+        if !accept(&fields) {
+            let attempts: i64 = redis::cmd("HINCRBY")
+                .arg(key)
+                .arg("attempts")
+                .arg(1)
+                .query_async(&mut *conn)
+                .await
+                .map_err(Error::from)?;
+            if attempts >= OTP_MAX_ATTEMPTS {
+                let _: () = redis::cmd("DEL")
+                    .arg(key)
+                    .query_async(&mut *conn)
+                    .await
+                    .map_err(Error::from)?;
+            }
+            return Ok(PendingCode::Rejected(fields));
+        }
+
+        // whoever deletes the entry wins
+        let deleted: i64 = redis::cmd("DEL")
+            .arg(key)
+            .query_async(&mut *conn)
+            .await
+            .map_err(Error::from)?;
+        if deleted == 0 {
+            return Ok(PendingCode::Missing);
+        }
+        Ok(PendingCode::Accepted(fields))
+    }
+}
+
+/// Result of checking an answer against a pending one-time code.
+enum PendingCode {
+    /// nothing stored: expired, already used or never issued
+    Missing,
+    /// wrong answer; the stored fields are kept for logging
+    Rejected(HashMap<String, String>),
+    /// right answer; the entry is gone
+    Accepted(HashMap<String, String>),
+}
+
+/// What an emailed password link is for; only the wording differs.
+#[derive(Clone, Copy)]
+enum PasswordLink {
+    Reset,
+    Setup,
+}
+
+/// Whether a login has already proven a second factor, see `complete_login`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SecondFactor {
+    /// one factor so far: password, emailed or texted code, upstream
+    /// provider or recovery code
+    Pending,
+    /// the MFA challenge passed, or a passkey with user verification
+    Proven,
+}
+
+/// Credential check done outside this service, see `finish_verified_login`.
+pub enum VerifiedLogin {
+    /// one factor; users with 2FA on still get a challenge
+    User(i64),
+    /// counts as two factors on its own: a passkey whose authenticator
+    /// verified the user (possession of the key plus its PIN or biometric)
+    MultiFactor(i64),
+    /// the user id is known when the credential was, even if the proof failed
+    Rejected(Option<i64>),
+}
+
+/// 32 random bytes, hex.
+fn new_nonce() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// 6-digit code (000000..999999), zero-padded.
+fn new_code() -> String {
+    format!("{:06}", OsRng.next_u32() % 1_000_000)
+}
+
+/// Redis fields of a pending passwordless login through `identity`.
+fn pending_login_fields(identity: &UserIdentity, code: &str) -> [(&'static str, String); 3] {
+    [
+        ("uid", identity.user_id.to_string()),
+        ("iid", identity.id.to_string()),
+        ("code", code.to_string()),
+    ]
+}
+
+/// Whether `answer` is the pending code in `fields`, compared in constant time.
+fn code_matches(fields: &HashMap<String, String>, answer: &str) -> bool {
+    fields
+        .get("code")
+        .is_some_and(|code| constant_time_eq(code.as_bytes(), answer.as_bytes()))
+}
+
+/// Domain-separated so a link token can never double as a cookie signature.
+fn magic_link_input(nonce: &str) -> String {
+    format!("login-link:{nonce}")
 }
@@ -10,7 +10,6 @@ pub struct CreateUserDto {
     pub username: String,
     pub email: String,
     pub phone_number: Option<String>,
-    pub login_method: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,7 +20,6 @@ pub struct UserResponse {
     pub phone_number: Option<String>,
     pub is_email_verified: bool,
     pub is_phone_verified: bool,
-    pub login_method: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -35,7 +33,6 @@ impl From<User> for UserResponse {
             phone_number: user.phone_number,
             is_email_verified: user.is_email_verified,
             is_phone_verified: user.is_phone_verified,
-            login_method: user.login_method.clone(),
             created_at: user.created_at,
             updated_at: user.updated_at,
         }
@@ -64,6 +61,72 @@ pub struct UserLoginReq {
     pub password: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginWithEmailReq {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginWithEmailResp {
+    pub ok: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePasswordReq {
+    #[validate(length(min = 1))]
+    pub current_password: String,
+    /// checked against the password policy in the system config
+    #[validate(length(min = 1))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgotPasswordReq {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetPasswordReq {
+    /// from the link in the reset email
+    #[validate(length(min = 1, max = 128))]
+    pub token: String,
+    /// checked against the password policy in the system config
+    #[validate(length(min = 1))]
+    pub new_password: String,
+}
+
+/// Either the code from the email or the `token` of its magic link.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginWithEmailVerifyReq {
+    #[validate(length(min = 6, max = 6))]
+    pub code: Option<String>,
+    #[validate(length(min = 1, max = 128))]
+    pub token: Option<String>,
+}
+
+/// Any common formatting is accepted; it is normalized to E.164.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct PhoneNumberReq {
+    #[validate(length(min = 7, max = 32))]
+    pub phone_number: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct PhoneCodeReq {
+    #[validate(length(min = 6, max = 6))]
+    pub code: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct UserDevice {
     pub user_id: i64,
@@ -81,7 +144,6 @@ pub struct UserDto {
     pub phone_number: Option<String>,
     pub is_email_verified: bool,
     pub is_phone_verified: bool,
-    pub login_method: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
@@ -96,7 +158,6 @@ impl From<User> for UserDto {
             phone_number: user.phone_number,
             is_email_verified: user.is_email_verified,
             is_phone_verified: user.is_phone_verified,
-            login_method: user.login_method,
             created_at: user.created_at,
             updated_at: user.updated_at,
             deleted_at: user.deleted_at,
@@ -0,0 +1,224 @@
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{ready, LocalBoxFuture};
+use uuid::Uuid;
+
+use crate::{
+    features::{
+        api_keys::{ApiKeyService, API_KEY_PREFIX},
+        auth::{AuthService, DpopRequest},
+        onboarding::get_client_ip,
+        users::COOKIE_ACCESS_TOKEN,
+    },
+    utils::{error::Error, token_service::TokenClaims},
+};
+
+/// The verified caller of a request.
+///
+/// Add it as a handler argument to require a valid access token, sent either as
+/// `Authorization: Bearer <token>` (`DPoP <token>` for bound tokens) or in the
+/// `__Host-access_token` cookie. Bound tokens also need a `DPoP` proof header.
+/// Requests without one are rejected with 401 before the handler runs.
+///
+/// A personal API key (`fgk_...`) works in place of the token as a bearer
+/// credential. Its scopes are checked against the request method, it carries
+/// no roles, and `did`/`sid` are 0 / nil since a key has no device or session.
+///
+/// Tokens issued to OAuth clients are refused with 403: a client the user
+/// consented to must not reach first-party endpoints. Resources meant for
+/// clients take [`OAuthUser`] instead.
+///
+/// The caller is verified once per request: the first extraction (a guard,
+/// the rate limiter or the handler) keeps it in the request extensions and
+/// later ones reuse it, so a single-use DPoP proof is only spent once.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub uid: i64,
+    pub did: i64,
+    pub sid: Uuid,
+    /// names from `users_roles` when the token was issued
+    pub roles: Vec<String>,
+    /// set when the caller used an API key instead of an access token
+    pub api_key_id: Option<i64>,
+}
+
+impl AuthenticatedUser {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    /// 403 for API keys: managing credentials needs a real sign-in, so a leaked
+    /// key cannot mint more keys or take over the account.
+    pub fn require_session(&self) -> Result<(), Error> {
+        match self.api_key_id {
+            Some(_) => Err(Error::Forbidden),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Some(user) = req.extensions().get::<AuthenticatedUser>() {
+            return Box::pin(ready(Ok(user.clone())));
+        }
+
+        let verified = verified_user(req);
+        let req = req.clone();
+        Box::pin(async move {
+            let user = verified.await?;
+            req.extensions_mut().insert(user.clone());
+            Ok(user)
+        })
+    }
+}
+
+fn verified_user(req: &HttpRequest) -> LocalBoxFuture<'static, Result<AuthenticatedUser, Error>> {
+    let token = access_token(req);
+    if let Some(key) = token.as_deref().filter(|t| t.starts_with(API_KEY_PREFIX)) {
+        return api_key_user(req, key.to_owned());
+    }
+    let claims = verified_claims(req, token);
+
+    Box::pin(async move {
+        let claims = claims.await?;
+        if claims.cid.is_some() {
+            return Err(Error::Forbidden);
+        }
+
+        Ok(AuthenticatedUser {
+            uid: claims.uid,
+            did: claims.did,
+            sid: claims.sid,
+            roles: claims.roles,
+            api_key_id: None,
+        })
+    })
+}
+
+/// A user acting through an OAuth client, e.g. for `/oauth/userinfo`.
+///
+/// Only access tokens issued to a client are accepted (no first-party tokens,
+/// no API keys), and the handler decides from `scopes` what the client may see.
+#[derive(Debug, Clone)]
+pub struct OAuthUser {
+    pub uid: i64,
+    pub scopes: Vec<String>,
+}
+
+impl OAuthUser {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+impl FromRequest for OAuthUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = access_token(req).filter(|t| !t.starts_with(API_KEY_PREFIX));
+        let claims = verified_claims(req, token);
+
+        Box::pin(async move {
+            let claims = claims.await?;
+            if claims.cid.is_none() {
+                return Err(Error::Forbidden);
+            }
+
+            Ok(OAuthUser {
+                uid: claims.uid,
+                scopes: claims.scope.split_whitespace().map(str::to_owned).collect(),
+            })
+        })
+    }
+}
+
+fn verified_claims(
+    req: &HttpRequest,
+    token: Option<String>,
+) -> LocalBoxFuture<'static, Result<TokenClaims, Error>> {
+    let dpop = DpopRequest::from_request(req);
+    let auth_service = req.app_data::<web::Data<AuthService>>().cloned();
+
+    Box::pin(async move {
+        let auth_service = auth_service
+            .ok_or_else(|| Error::Unexpected("AuthService is not registered".into()))?;
+        let token = token.ok_or(Error::Unauthorized)?;
+
+        auth_service.authenticate(&token, &dpop).await
+    })
+}
+
+fn api_key_user(
+    req: &HttpRequest,
+    key: String,
+) -> LocalBoxFuture<'static, Result<AuthenticatedUser, Error>> {
+    let method = req.method().clone();
+    let ip = get_client_ip(req);
+    let api_key_service = req.app_data::<web::Data<ApiKeyService>>().cloned();
+
+    Box::pin(async move {
+        let api_key_service = api_key_service
+            .ok_or_else(|| Error::Unexpected("ApiKeyService is not registered".into()))?;
+        let api_key = api_key_service.authenticate(&key, &method, ip).await?;
+
+        Ok(AuthenticatedUser {
+            uid: api_key.user_id,
+            did: 0,
+            sid: Uuid::nil(),
+            roles: Vec::new(),
+            api_key_id: Some(api_key.id),
+        })
+    })
+}
+
+/// Authorization header wins over the cookie so API clients are never shadowed by a stale browser cookie.
+fn access_token(req: &HttpRequest) -> Option<String> {
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|v| {
+            v.strip_prefix("Bearer ")
+                .or_else(|| v.strip_prefix("DPoP "))
+        })
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+
+    bearer.or_else(|| {
+        req.cookie(COOKIE_ACCESS_TOKEN)
+            .map(|c| c.value().to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test::TestRequest, FromRequest, HttpMessage};
+    use uuid::Uuid;
+
+    use super::AuthenticatedUser;
+
+    /// Guards, the rate limiter and the handler all extract the caller; only
+    /// the first one may verify the token and spend its DPoP proof.
+    #[actix_web::test]
+    async fn a_verified_caller_is_not_verified_again() {
+        // no AuthService registered and no token: verifying would fail
+        let req = TestRequest::get()
+            .uri("/admin/audit-events")
+            .to_http_request();
+        assert!(AuthenticatedUser::extract(&req).await.is_err());
+
+        req.extensions_mut().insert(AuthenticatedUser {
+            uid: 7,
+            did: 1,
+            sid: Uuid::new_v4(),
+            roles: vec!["auditor".into()],
+            api_key_id: None,
+        });
+        let user = AuthenticatedUser::extract(&req).await.unwrap();
+        assert_eq!((user.uid, user.roles), (7, vec!["auditor".to_string()]));
+    }
+}
@@ -1,3 +1,7 @@
+pub mod admin_auth;
 pub mod auth;
 pub mod cors;
-pub mod rate_limit;
\ No newline at end of file
+pub mod policy_auth;
+pub mod rate_limit;
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -0,0 +1,99 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    web, FromRequest, HttpRequest, ResponseError,
+};
+use futures::future::LocalBoxFuture;
+
+use crate::{
+    features::{onboarding::get_client_ip, policies::PolicyService},
+    infrastructure::middlewares::auth::AuthenticatedUser,
+    utils::error::{self, Error},
+};
+
+/// Only lets a request through when the access policies allow `action` for
+/// the caller: 401 without a valid token, 403 when denied. A `{user_id}`
+/// path segment is taken as the owner of the resource. Put it on a route
+/// with `#[get("/admin/...", wrap = "require_policy(\"audit_events:read\")")]`.
+pub fn require_policy(action: &'static str) -> RequirePolicy {
+    RequirePolicy { action }
+}
+
+#[derive(Clone)]
+pub struct RequirePolicy {
+    action: &'static str,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePolicy
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = RequirePolicyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequirePolicyMiddleware {
+            service: Rc::new(service),
+            action: self.action,
+        }))
+    }
+}
+
+pub struct RequirePolicyMiddleware<S> {
+    service: Rc<S>,
+    action: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for RequirePolicyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let action = self.action;
+
+        Box::pin(async move {
+            if let Err(e) = authorize(req.request(), action).await {
+                return Ok(req.into_response(e.error_response()));
+            }
+            Ok(service.call(req).await?.map_into_boxed_body())
+        })
+    }
+}
+
+/// The caller comes from the request extensions once anything before has
+/// verified it, and is kept there for the handler: a DPoP proof is spent once.
+async fn authorize(req: &HttpRequest, action: &str) -> error::Result<()> {
+    let user = AuthenticatedUser::extract(req).await?;
+    let policies = req
+        .app_data::<web::Data<PolicyService>>()
+        .ok_or_else(|| Error::Unexpected("PolicyService is not registered".into()))?;
+    let owner = req
+        .match_info()
+        .get("user_id")
+        .and_then(|id| id.parse().ok());
+
+    let context = policies.context(&user, get_client_ip(req), owner).await?;
+    let decision = policies.authorize(action, context).await?;
+    if !decision.allowed {
+        tracing::warn!(user_id = user.uid, action, reason = %decision.reason, "access denied");
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
@@ -1 +1,324 @@
-// TODO:
\ No newline at end of file
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+    rc::Rc,
+    sync::Arc,
+};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue, RETRY_AFTER},
+    web, FromRequest, HttpResponse,
+};
+use futures::future::LocalBoxFuture;
+use serde::Deserialize;
+
+use crate::{
+    features::onboarding::{
+        get_client_ip, ip_to_bucket, sha256_hex, OnboardingService, RateLimitAlgorithm,
+        RateLimitStatus, RateLimiter, COOKIE_VISITOR,
+    },
+    infrastructure::middlewares::auth::AuthenticatedUser,
+};
+
+/// region Redis prefixes
+/// `{algorithm}:{group or route}:{key}:{sha256(value)}` -> counter of one policy
+/// (v2: GCRA counts from `GCRA_EPOCH_MS`, so v1 TATs are on another scale)
+pub const RATE_LIMIT_PREFIX: &str = "rl:route:v2:";
+/// endregion Redis prefixes
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitPolicy {
+    /// `METHOD /pattern` as registered, e.g. `POST /users/login` or `GET /admin/users/{id}/lockouts`
+    pub route: String,
+    pub key: RateLimitKey,
+    /// JSON pointer into the request body for `key = "field"`, e.g. `/extraData/installId`
+    #[serde(default)]
+    pub field: Option<String>,
+    pub limit: u32,
+    pub window_seconds: u64,
+    /// policies naming the same group share one counter (e.g. every route that sends email)
+    #[serde(default)]
+    pub group: Option<String>,
+    /// unset: the `algorithm` at the top of the policy file
+    #[serde(default)]
+    pub algorithm: Option<RateLimitAlgorithm>,
+}
+
+/// What a policy counts requests by. Requests without a value for the key
+/// (no cookie, not signed in, field missing) are not counted by that policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    /// the client's /24 (IPv4) or /64 (IPv6)
+    Ip,
+    /// the signed visitor cookie from `/onboarding/preparation`
+    Visitor,
+    /// the user of a valid access token
+    User,
+    /// `email` of the JSON body, case-insensitive
+    Email,
+    /// any JSON body value, see `RateLimitPolicy::field`
+    Field,
+}
+
+impl RateLimitPolicy {
+    fn needs_body(&self) -> bool {
+        matches!(self.key, RateLimitKey::Email | RateLimitKey::Field)
+    }
+
+    fn counter_key(&self, value: &str) -> String {
+        let key = match self.key {
+            RateLimitKey::Ip => "ip",
+            RateLimitKey::Visitor => "visitor",
+            RateLimitKey::User => "user",
+            RateLimitKey::Email => "email",
+            RateLimitKey::Field => self.field.as_deref().unwrap_or_default(),
+        };
+        format!(
+            "{}{}:{}:{}:{}",
+            RATE_LIMIT_PREFIX,
+            self.algorithm.unwrap_or_default().as_str(),
+            self.group.as_deref().unwrap_or(&self.route),
+            key,
+            sha256_hex(value)
+        )
+    }
+}
+
+/// Declarative per-route rate limiting. Every policy of the matched route is
+/// hit; the first one over its limit answers 429. Responses carry
+/// `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` of the
+/// tightest policy, and 429s a `Retry-After`.
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: RateLimiter,
+    policies: Arc<HashMap<String, Vec<RateLimitPolicy>>>,
+}
+
+impl RateLimit {
+    pub fn new(limiter: RateLimiter, policies: Vec<RateLimitPolicy>) -> Self {
+        let mut by_route: HashMap<String, Vec<RateLimitPolicy>> = HashMap::new();
+        for policy in policies {
+            by_route
+                .entry(normalize_route(&policy.route))
+                .or_default()
+                .push(policy);
+        }
+        Self {
+            limiter,
+            policies: Arc::new(by_route),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            limiter: self.limiter.clone(),
+            policies: self.policies.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limiter: RateLimiter,
+    policies: Arc<HashMap<String, Vec<RateLimitPolicy>>>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let route = req
+            .match_pattern()
+            .map(|pattern| format!("{} {}", req.method(), pattern));
+        let Some(policies) = route.and_then(|r| self.policies.get(&r).cloned()) else {
+            return Box::pin(async move { Ok(service.call(req).await?.map_into_boxed_body()) });
+        };
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            // body keys read the JSON first, then hand the same bytes to the handler
+            let body = if policies.iter().any(RateLimitPolicy::needs_body) {
+                let bytes = req.extract::<web::Bytes>().await?;
+                req.set_payload(Payload::from(bytes.clone()));
+                serde_json::from_slice::<serde_json::Value>(&bytes).ok()
+            } else {
+                None
+            };
+
+            let mut tightest: Option<RateLimitStatus> = None;
+            for policy in &policies {
+                let Some(value) = key_value(policy, &req, body.as_ref()).await else {
+                    continue;
+                };
+                let status = limiter
+                    .hit(
+                        policy.algorithm.unwrap_or_default(),
+                        &policy.counter_key(&value),
+                        policy.limit,
+                        policy.window_seconds * 1000,
+                    )
+                    .await;
+                if !status.allowed {
+                    let mut resp = HttpResponse::TooManyRequests();
+                    resp.insert_header((RETRY_AFTER, seconds(status.reset_ms)));
+                    let mut resp = resp.finish();
+                    set_headers(resp.headers_mut(), &status);
+                    return Ok(req.into_response(resp));
+                }
+                if tightest.is_none_or(|t| status.remaining < t.remaining) {
+                    tightest = Some(status);
+                }
+            }
+
+            let mut res = service.call(req).await?.map_into_boxed_body();
+            if let Some(status) = tightest {
+                set_headers(res.headers_mut(), &status);
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// The value `policy` counts this request by, if the request has one.
+async fn key_value(
+    policy: &RateLimitPolicy,
+    req: &ServiceRequest,
+    body: Option<&serde_json::Value>,
+) -> Option<String> {
+    match policy.key {
+        RateLimitKey::Ip => get_client_ip(req.request()).map(|ip| ip_to_bucket(&ip)),
+        RateLimitKey::Visitor => {
+            let onboarding = req.app_data::<web::Data<OnboardingService>>()?;
+            onboarding.has_valid_cookie(req.cookie(COOKIE_VISITOR))
+        }
+        RateLimitKey::User => {
+            // kept in the request extensions, so the handler does not verify
+            // the token (and spend its DPoP proof) a second time
+            let user = AuthenticatedUser::extract(req.request()).await.ok()?;
+            Some(user.uid.to_string())
+        }
+        RateLimitKey::Email => body?
+            .get("email")?
+            .as_str()
+            .map(|email| email.trim().to_lowercase()),
+        RateLimitKey::Field => {
+            let value = body?.pointer(policy.field.as_deref()?)?;
+            match value {
+                serde_json::Value::String(s) => Some(s.clone()),
+                serde_json::Value::Null => None,
+                other => Some(other.to_string()),
+            }
+        }
+    }
+}
+
+fn set_headers(headers: &mut actix_web::http::header::HeaderMap, status: &RateLimitStatus) {
+    for (name, value) in [
+        ("ratelimit-limit", status.limit.to_string()),
+        ("ratelimit-remaining", status.remaining.to_string()),
+        ("ratelimit-reset", seconds(status.reset_ms)),
+    ] {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    }
+}
+
+fn seconds(ms: u64) -> String {
+    ms.div_ceil(1000).to_string()
+}
+
+/// `post  /users/login` -> `POST /users/login`
+fn normalize_route(route: &str) -> String {
+    let mut parts = route.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => format!("{} {}", method.to_uppercase(), path),
+        _ => route.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        get,
+        test::{call_service, init_service, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    use super::{RateLimit, RateLimitKey, RateLimitPolicy};
+    use crate::{
+        features::onboarding::{RateLimitFallback, RateLimiter},
+        infrastructure::middlewares::{
+            auth::AuthenticatedUser,
+            test_support::{fake_redis, DpopCaller},
+        },
+    };
+
+    #[get("/users/me/ping")]
+    async fn ping(user: AuthenticatedUser) -> HttpResponse {
+        HttpResponse::Ok().body(user.uid.to_string())
+    }
+
+    /// A `key = "user"` policy needs the caller before the handler does; one
+    /// DPoP proof has to serve both.
+    #[actix_web::test]
+    async fn user_keyed_policy_keeps_the_dpop_proof_usable() {
+        let redis_pool = fake_redis();
+        let (auth_service, caller) = DpopCaller::new(redis_pool.clone(), 7, &[]);
+        let policy = RateLimitPolicy {
+            route: "GET /users/me/ping".into(),
+            key: RateLimitKey::User,
+            field: None,
+            limit: 10,
+            window_seconds: 60,
+            group: None,
+            algorithm: None,
+        };
+        let limiter = RateLimiter::new(redis_pool, RateLimitFallback::Closed);
+        let app = init_service(
+            App::new()
+                .wrap(RateLimit::new(limiter, vec![policy]))
+                .app_data(web::Data::new(auth_service))
+                .service(ping),
+        )
+        .await;
+
+        let request = TestRequest::get()
+            .uri("/users/me/ping")
+            .insert_header(("host", "localhost"))
+            .insert_header(("authorization", format!("DPoP {}", caller.access_token)))
+            .insert_header((
+                "dpop",
+                caller.proof("GET", "http://localhost/users/me/ping"),
+            ))
+            .to_request();
+        let resp = call_service(&app, request).await;
+        assert_eq!(resp.status(), 200);
+        assert!(resp.headers().contains_key("ratelimit-limit"));
+    }
+}
@@ -0,0 +1,175 @@
+//! An `AuthService` without Postgres or Redis, and a DPoP-bound caller to
+//! send requests through it, for middleware tests.
+
+use std::{
+    collections::HashSet,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use deadpool_redis::Pool;
+use jsonwebtoken::{encode, Algorithm, Header};
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    features::{
+        auth::AuthService, keys::KeyRingService, roles::RoleRepository, system::ConfigService,
+    },
+    infrastructure::persistence::redis::create_pool,
+    utils::{
+        crypto::p256_jwk_thumbprint,
+        token_service::{Confirmation, TokenClaims, TokenKind, TokenService},
+    },
+};
+
+const ISSUER: &str = "test-issuer";
+const AUDIENCE: &str = "test-audience";
+
+/// Just enough Redis for `AuthService::authenticate` and `RateLimiter::hit`:
+/// `SET ... NX` keeps the keys it has seen (the DPoP replay guard), `MGET`
+/// finds nothing revoked, `EVAL` allows, anything else is `+OK`.
+pub fn fake_redis() -> Pool {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("redis://{}", listener.local_addr().unwrap());
+    let seen = Arc::new(Mutex::new(HashSet::new()));
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let seen = seen.clone();
+            std::thread::spawn(move || {
+                let mut writer = stream.try_clone().unwrap();
+                let mut lines = BufReader::new(stream).lines().map_while(|l| l.ok());
+                while let Some(header) = lines.next() {
+                    let argc: usize = header.trim_start_matches('*').parse().unwrap_or(0);
+                    let args: Vec<String> = (0..argc).filter_map(|_| lines.nth(1)).collect();
+                    let reply = match args.first().map(|c| c.to_uppercase()).as_deref() {
+                        Some("SET") if args.iter().any(|a| a == "NX") => {
+                            match seen.lock().unwrap().insert(args[1].clone()) {
+                                true => "+OK\r\n".to_owned(),
+                                false => "$-1\r\n".to_owned(),
+                            }
+                        }
+                        Some("MGET") => {
+                            format!("*{}\r\n{}", argc - 1, "$-1\r\n".repeat(argc - 1))
+                        }
+                        Some("EVAL") => "*3\r\n:1\r\n:0\r\n:1000\r\n".to_owned(),
+                        _ => "+OK\r\n".to_owned(),
+                    };
+                    if writer.write_all(reply.as_bytes()).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    create_pool(&url).unwrap()
+}
+
+/// A user signed in on a device whose tokens are bound to its DPoP key.
+pub struct DpopCaller {
+    pub access_token: String,
+    device_key: EcdsaKeyPair,
+    x: String,
+    y: String,
+}
+
+impl DpopCaller {
+    /// An `AuthService` over `redis_pool` with a fresh key ring, and an access
+    /// token for user `uid` with `roles` signed by it.
+    pub fn new(redis_pool: Pool, uid: i64, roles: &[&str]) -> (AuthService, Self) {
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let config_service = Arc::new(ConfigService::new(pool.clone(), redis_pool.clone()));
+        let key_ring = Arc::new(KeyRingService::ephemeral(
+            pool.clone(),
+            config_service.clone(),
+        ));
+        let token_service = TokenService::new(
+            config_service.clone(),
+            key_ring.clone(),
+            RoleRepository::new(pool.clone()),
+            ISSUER,
+            AUDIENCE,
+        );
+        let auth_service =
+            AuthService::new(pool, redis_pool, Arc::new(token_service), config_service);
+
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let device_key =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+        let point = device_key.public_key().as_ref();
+        let (x, y) = (
+            URL_SAFE_NO_PAD.encode(&point[1..33]),
+            URL_SAFE_NO_PAD.encode(&point[33..65]),
+        );
+
+        let now = Utc::now();
+        let claims = TokenClaims {
+            sub: uid.to_string(),
+            uid,
+            did: 1,
+            sid: Uuid::new_v4(),
+            jti: Uuid::new_v4().to_string(),
+            iat: now.timestamp(),
+            iat_ms: now.timestamp_millis(),
+            exp: now.timestamp() + 300,
+            iss: ISSUER.into(),
+            aud: AUDIENCE.into(),
+            typ: TokenKind::Access,
+            fam: Uuid::new_v4().to_string(),
+            scope: String::new(),
+            cid: None,
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+            cnf: Some(Confirmation {
+                jkt: p256_jwk_thumbprint(&x, &y),
+            }),
+        };
+        let (kid, signing_key) = key_ring.signing_key();
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(kid);
+        let access_token = encode(&header, &claims, &signing_key).unwrap();
+
+        let caller = Self {
+            access_token,
+            device_key,
+            x,
+            y,
+        };
+        (auth_service, caller)
+    }
+
+    /// A fresh single-use proof for `method` on `htu`, carrying the token hash.
+    pub fn proof(&self, method: &str, htu: &str) -> String {
+        let header = serde_json::json!({
+            "typ": "dpop+jwt",
+            "alg": "ES256",
+            "jwk": { "kty": "EC", "crv": "P-256", "x": self.x, "y": self.y },
+        });
+        let claims = serde_json::json!({
+            "jti": Uuid::new_v4().to_string(),
+            "htm": method,
+            "htu": htu,
+            "iat": Utc::now().timestamp(),
+            "ath": URL_SAFE_NO_PAD.encode(Sha256::digest(self.access_token.as_bytes())),
+        });
+        let input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = self
+            .device_key
+            .sign(&SystemRandom::new(), input.as_bytes())
+            .unwrap();
+        format!("{input}.{}", URL_SAFE_NO_PAD.encode(signature.as_ref()))
+    }
+}
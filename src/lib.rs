@@ -1,4 +1,5 @@
 pub mod features;
+pub mod infrastructure;
 pub mod utils;
 pub mod seeding;
 // forest_gate::utils_controller
@@ -7,18 +7,31 @@ mod utils;
 use std::env;
 use std::sync::Arc;
 
+use crate::features::api_keys::ApiKeyService;
 use crate::features::audits::AuditService;
-use crate::features::users::UserService;
+use crate::features::auth::AuthService;
+use crate::features::federation::FederationService;
+use crate::features::identities::IdentityService;
+use crate::features::keys::KeyRingService;
+use crate::features::lockout::LockoutService;
+use crate::features::mfa::MfaService;
+use crate::features::oauth::OAuthService;
+use crate::features::passkeys::{PasskeyService, RelyingParty};
+use crate::features::policies::PolicyService;
+use crate::features::recovery::RecoveryService;
+use crate::features::roles::{RoleRepository, RoleService};
+use crate::features::users::{PasswordHashing, PasswordPolicyService, UserService};
 use crate::utils::error::Error;
 use crate::utils::token_service::TokenService;
 use actix_cors::Cors;
 use actix_web::{middleware::Logger, web, App, HttpServer};
 use config::traits::Env;
 use features::admin::AdminService;
-use features::clients::EmailClient;
+use features::clients::{sms_provider_from_env, BreachedPasswordsClient, EmailClient};
 use features::onboarding::OnboardingService;
 use features::system::ConfigService;
 // use forest_gate::seeding;
+use infrastructure::middlewares::rate_limit::RateLimit;
 use infrastructure::persistence::{db, redis};
 use swagger::ApiDoc;
 use utoipa::OpenApi;
@@ -26,11 +39,10 @@ use utoipa_swagger_ui::SwaggerUi;
 
 use crate::features::clients::{MaxMindClient, OpenRouterClient, OrMessage};
 use crate::features::onboarding::types::AppState;
-use crate::features::onboarding::utils::RateLimiter;
+use crate::features::onboarding::RateLimiter;
 
 // use crate::features::ws::ws_upgrade;
-use crate::utils::crypto::ClientHMAC;
-use tokio::sync::Mutex;
+use crate::utils::crypto::{ClientHMAC, SecretCipher};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -60,8 +72,15 @@ async fn main() -> std::io::Result<()> {
 
     // region settings
     let email_client = EmailClient::from_env().expect("email client config");
+    let sms_provider = sms_provider_from_env().expect("sms provider config");
+    let breached_passwords =
+        BreachedPasswordsClient::from_env().expect("breached passwords corpus");
     let pg_settings = config::DbSettings::from_env().expect("Failed to load settings");
     let redis_settings = config::RedisSettings::from_env().expect("Failed to load settings");
+    let rate_limit_settings =
+        config::RateLimitSettings::from_env().expect("Failed to load rate limit policies");
+    let oidc_settings =
+        config::OidcSettings::from_env().expect("Failed to load identity providers");
     // endregion settings
 
     // region persistense
@@ -76,42 +95,126 @@ async fn main() -> std::io::Result<()> {
 
     // region services
     let hmac_client = make_hmac_from_env();
-    let onboarding_service =
-        OnboardingService::new(hmac_client, db_pool.clone(), redis_pool.clone());
+    let config_service = Arc::new(ConfigService::new(db_pool.clone(), redis_pool.clone()));
+    let password_policy = PasswordPolicyService::new(config_service.clone(), breached_passwords);
+    let password_hashing = PasswordHashing::from_env().expect("argon2 settings");
+    let onboarding_service = OnboardingService::new(
+        hmac_client.clone(),
+        db_pool.clone(),
+        redis_pool.clone(),
+        password_policy.clone(),
+        password_hashing.clone(),
+    );
     // ATTENTION!!!
     // CONFIG SET notify-keyspace-events Ex
     // CONFIG GET notify-keyspace-events
     // THIS MUST BE EXECUTED BEFORE MAKING SURE YOU COULD FLUSH THE EVENTS FROM REDIS
     // OR JUST ADD TO redis.conf:
     // notify-keyspace-events Ex
-    let audit_service = AuditService::new(redis_pool.clone());
+    let audit_service = AuditService::new(db_pool.clone(), redis_pool.clone());
     audit_service.spawn_inactivity_flusher(
         &redis_settings.redis_url,
         &pg_settings.database_url,
         openrouter_client.clone(),
     );
-    let config_service = Arc::new(ConfigService::new(db_pool.clone(), redis_pool.clone()));
     let issuer = env::var("AUTH_ISSUER").unwrap_or_else(|_| "my-issuer".into());
     let audience = env::var("AUTH_AUDIENCE").unwrap_or_else(|_| "my-audience".into());
-    let token_service = Arc::new(
-        TokenService::new(config_service.clone(), issuer, audience).expect("init TokenService"),
+    let key_ring = Arc::new(
+        KeyRingService::load(
+            db_pool.clone(),
+            config_service.clone(),
+            make_secret_cipher_from_env(),
+        )
+        .await
+        .expect("load signing keys"),
     );
+    key_ring.spawn_reloader(std::time::Duration::from_secs(60));
+    let token_service = Arc::new(TokenService::new(
+        config_service.clone(),
+        key_ring.clone(),
+        RoleRepository::new(db_pool.clone()),
+        issuer.clone(),
+        audience,
+    ));
 
     let maxmind_client = Arc::new(MaxMindClient::from_env_or_default().expect("load maxmind dbs"));
 
-    let user_service = UserService::new(
+    let auth_service = AuthService::new(
         db_pool.clone(),
+        redis_pool.clone(),
         token_service.clone(),
         config_service.clone(),
+    );
+    auth_service.spawn_session_expirer(std::time::Duration::from_secs(60));
+    let recovery_service = RecoveryService::new(
+        db_pool.clone(),
+        config_service.clone(),
+        password_hashing.clone(),
+    );
+    let mfa_service = MfaService::new(
+        db_pool.clone(),
+        redis_pool.clone(),
+        make_secret_cipher_from_env(),
+        env::var("MFA_TOTP_ISSUER").unwrap_or_else(|_| "Forest Gate".into()),
+        recovery_service.clone(),
+    );
+    let passkey_service = PasskeyService::new(
+        db_pool.clone(),
+        redis_pool.clone(),
+        RelyingParty {
+            id: env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| "localhost".into()),
+            name: env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "Forest Gate".into()),
+            origin: env::var("WEBAUTHN_ORIGIN").unwrap_or_else(|_| "http://localhost:8080".into()),
+        },
+    );
+    let lockout_service =
+        LockoutService::new(db_pool.clone(), redis_pool.clone(), config_service.clone());
+    let identity_service = IdentityService::new(db_pool.clone());
+    let user_service = UserService::new(
+        db_pool.clone(),
+        redis_pool.clone(),
+        auth_service.clone(),
         maxmind_client.clone(),
+        sms_provider,
+        mfa_service.clone(),
+        hmac_client,
+        password_policy,
+        password_hashing.clone(),
+        lockout_service.clone(),
     );
     let admin_service = AdminService::new(db_pool.clone());
+    let api_key_service = ApiKeyService::new(db_pool.clone());
+    let role_service = RoleService::new(db_pool.clone());
+    if let Ok(email) = env::var("BOOTSTRAP_ADMIN_EMAIL") {
+        role_service
+            .bootstrap_admin(&email)
+            .await
+            .expect("Failed to bootstrap the first admin");
+    }
+    let federation_service = FederationService::new(
+        db_pool.clone(),
+        make_hmac_from_env(),
+        password_hashing.clone(),
+        oidc_settings.providers,
+        &issuer,
+        &[&env::var("OAUTH_LOGIN_URL").unwrap_or_default()],
+    );
+    let policy_service =
+        PolicyService::new(db_pool.clone(), redis_pool.clone(), maxmind_client.clone());
+    let oauth_service = OAuthService::new(
+        db_pool.clone(),
+        redis_pool.clone(),
+        auth_service.clone(),
+        token_service.clone(),
+        password_hashing,
+        env::var("OAUTH_LOGIN_URL").unwrap_or_else(|_| "/login".into()),
+    );
     // endregion services
 
     // region rate Limiting
-    let limiter = RateLimiter::new(redis_pool.clone());
+    let limiter = RateLimiter::new(redis_pool.clone(), rate_limit_settings.fallback);
+    let rate_limit = RateLimit::new(limiter, rate_limit_settings.policies);
     let app_state = web::Data::new(AppState {
-        limiter: Mutex::new(limiter),
         redis: redis_pool.clone(),
     });
     // endregion rate Limiting
@@ -122,6 +225,12 @@ async fn main() -> std::io::Result<()> {
         ClientHMAC::from_hex_key(&hex_key).expect("invalid VISITOR_HMAC_KEY hex")
     }
 
+    fn make_secret_cipher_from_env() -> SecretCipher {
+        let hex_key = env::var("MFA_ENCRYPTION_KEY")
+            .expect("MFA_ENCRYPTION_KEY must be set (hex, e.g. `openssl rand -hex 32`)");
+        SecretCipher::from_hex_key(&hex_key).expect("invalid MFA_ENCRYPTION_KEY")
+    }
+
     let openapi = ApiDoc::openapi();
 
     HttpServer::new(move || {
@@ -132,15 +241,29 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(db_pool.clone()))
             .app_data(web::Data::new(redis_pool.clone()))
             .app_data(web::Data::new(config_service.clone()))
+            .app_data(web::Data::new(token_service.clone()))
+            .app_data(web::Data::from(key_ring.clone()))
             .app_data(web::Data::new(onboarding_service.clone()))
             .app_data(web::Data::new(audit_service.clone()))
+            .app_data(web::Data::new(auth_service.clone()))
             .app_data(web::Data::new(user_service.clone()))
+            .app_data(web::Data::new(mfa_service.clone()))
+            .app_data(web::Data::new(passkey_service.clone()))
+            .app_data(web::Data::new(federation_service.clone()))
+            .app_data(web::Data::new(identity_service.clone()))
+            .app_data(web::Data::new(api_key_service.clone()))
+            .app_data(web::Data::new(recovery_service.clone()))
+            .app_data(web::Data::new(lockout_service.clone()))
             .app_data(web::Data::new(admin_service.clone()))
+            .app_data(web::Data::new(role_service.clone()))
+            .app_data(web::Data::new(policy_service.clone()))
+            .app_data(web::Data::new(oauth_service.clone()))
+            .wrap(rate_limit.clone())
             .wrap(Logger::default())
             .wrap(
                 Cors::default()
                     .allow_any_origin()
-                    .allowed_methods(vec!["GET", "POST", "PUT", "OPTIONS"])
+                    .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
                     .allow_any_header()
                     .supports_credentials(),
             ) // should be changed for production!!!
@@ -158,9 +281,71 @@ async fn main() -> std::io::Result<()> {
                     .service(features::onboarding::otp_verification)
                     .service(features::onboarding::user_details)
                     .service(features::users::login)
+                    .service(features::users::login_with_email)
+                    .service(features::users::verify_login_with_email)
+                    .service(features::users::login_with_phone)
+                    .service(features::users::verify_login_with_phone)
+                    .service(features::users::me)
+                    .service(features::users::add_phone)
+                    .service(features::users::verify_phone)
+                    .service(features::identities::list_identities)
+                    .service(features::identities::add_password_identity)
+                    .service(features::identities::add_email_identity)
+                    .service(features::identities::unlink_identity)
+                    .service(features::api_keys::list_api_keys)
+                    .service(features::api_keys::create_api_key)
+                    .service(features::api_keys::revoke_api_key)
+                    .service(features::users::change_password)
+                    .service(features::users::forgot_password)
+                    .service(features::users::reset_password)
+                    .service(features::lockout::unlock_account)
+                    .service(features::auth::refresh)
+                    .service(features::auth::logout)
+                    .service(features::mfa::enroll_totp)
+                    .service(features::mfa::confirm_totp)
+                    .service(features::mfa::disable_totp)
+                    .service(features::mfa::verify_mfa)
+                    .service(features::mfa::verify_mfa_recovery)
+                    .service(features::passkeys::passkey_register_options)
+                    .service(features::passkeys::passkey_register)
+                    .service(features::passkeys::passkey_login_options)
+                    .service(features::passkeys::passkey_login)
+                    .service(features::federation::federated_providers)
+                    .service(features::federation::federated_start)
+                    .service(features::federation::federated_callback)
+                    .service(features::recovery::generate_recovery_codes)
+                    .service(features::recovery::recovery_codes_status)
+                    .service(features::recovery::login_with_recovery_code)
                     .service(features::admin::users)
+                    .service(features::admin::revoke)
+                    .service(features::lockout::admin_unlock_account)
+                    .service(features::lockout::account_lockouts)
+                    .service(features::api_keys::all_api_keys)
+                    .service(features::api_keys::admin_revoke_api_key)
+                    .service(features::roles::list_roles)
+                    .service(features::roles::create_role)
+                    .service(features::roles::rename_role)
+                    .service(features::roles::delete_role)
+                    .service(features::roles::user_roles)
+                    .service(features::roles::assign_role)
+                    .service(features::roles::unassign_role)
+                    .service(features::policies::list_policies)
+                    .service(features::policies::create_policy)
+                    .service(features::policies::explain_policy)
+                    .service(features::policies::update_policy)
+                    .service(features::policies::delete_policy)
+                    .service(features::keys::jwks)
+                    .service(features::keys::rotate_key)
+                    .service(features::oauth::openid_configuration)
+                    .service(features::oauth::authorize)
+                    .service(features::oauth::token)
+                    .service(features::oauth::introspect)
+                    .service(features::oauth::userinfo)
+                    .service(features::oauth::create_client)
+                    .service(features::oauth::create_machine_client)
                     .service(features::audits::audit_init)
-                    .service(features::audits::audit_batch),
+                    .service(features::audits::audit_batch)
+                    .service(features::audits::audit_events),
             )
         // .service(
         //     web::scope("/ws")
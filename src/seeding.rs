@@ -181,8 +181,6 @@ async fn insert_users(
             email: String,
             phone: Option<String>,
             password_hash: String,
-            salt: Vec<u8>,
-            login_method: String,
             home_idx: usize,
         }
         let mut rows_local: Vec<RowData> = Vec::with_capacity(chunk_end - chunk_start);
@@ -214,13 +212,8 @@ async fn insert_users(
                 None
             };
 
-            let salt: Vec<u8> = (0..16).map(|_| rng.gen()).collect();
             let rand_str = Alphanumeric.sample_string(&mut rng, 32);
             let password_hash = format!("$argon2id$v=19$m=65536,t=3,p=1${}", rand_str);
-            let login_method = *["password", "oauth_google", "oauth_facebook", "magic_link"]
-                .choose(&mut rng)
-                .unwrap();
-
             let home_idx = rng.gen_range(0..profs.len());
 
             rows_local.push(RowData {
@@ -228,23 +221,19 @@ async fn insert_users(
                 email,
                 phone,
                 password_hash,
-                salt,
-                login_method: login_method.to_string(),
                 home_idx,
             });
         }
 
         // Use QueryBuilder to bind all values
         let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
-            "INSERT INTO users (username, email, phone_number, password_hash, salt, login_method) ",
+            "INSERT INTO users (username, email, phone_number, password_hash) ",
         );
         qb.push_values(&rows_local, |mut b, r| {
             b.push_bind(&r.username)
                 .push_bind(&r.email)
                 .push_bind(&r.phone)
-                .push_bind(&r.password_hash)
-                .push_bind(&r.salt)
-                .push_bind(&r.login_method);
+                .push_bind(&r.password_hash);
         });
         qb.push(" RETURNING id");
         let rows = qb.build().fetch_all(tx.as_mut()).await?;
@@ -255,6 +244,16 @@ async fn insert_users(
         }
     }
 
+    // seeded users sign in with their (random) password
+    let ids: Vec<i64> = result.iter().map(|(id, _)| *id).collect();
+    sqlx::query(
+        "INSERT INTO user_identities (user_id, kind, identifier) \
+         SELECT id, 'password', id::text FROM users WHERE id = ANY($1)",
+    )
+    .bind(&ids)
+    .execute(tx.as_mut())
+    .await?;
+
     Ok(result)
 }
 
@@ -1,11 +1,51 @@
 use forest_gate::features::{
-    admin::__path_users,
+    admin::{__path_revoke, __path_users},
+    api_keys::{
+        __path_admin_revoke_api_key, __path_all_api_keys, __path_create_api_key,
+        __path_list_api_keys, __path_revoke_api_key,
+    },
+    auth::{__path_logout, __path_refresh},
+    federation::{__path_federated_callback, __path_federated_providers, __path_federated_start},
+    identities::{
+        __path_add_email_identity, __path_add_password_identity, __path_list_identities,
+        __path_unlink_identity,
+    },
+    keys::{__path_jwks, __path_rotate_key},
+    lockout::{__path_account_lockouts, __path_admin_unlock_account, __path_unlock_account},
+    mfa::{
+        __path_confirm_totp, __path_disable_totp, __path_enroll_totp, __path_verify_mfa,
+        __path_verify_mfa_recovery,
+    },
+    oauth::{
+        __path_authorize, __path_create_client, __path_create_machine_client, __path_introspect,
+        __path_openid_configuration, __path_token, __path_userinfo,
+    },
     onboarding::{
         __path_otp_verification, __path_preparation, __path_user_details, __path_with_email,
     },
+    passkeys::{
+        __path_passkey_login, __path_passkey_login_options, __path_passkey_register,
+        __path_passkey_register_options,
+    },
+    policies::{
+        __path_create_policy, __path_delete_policy, __path_explain_policy,
+        __path_list_policies, __path_update_policy,
+    },
+    recovery::{
+        __path_generate_recovery_codes, __path_login_with_recovery_code,
+        __path_recovery_codes_status,
+    },
+    roles::{
+        __path_assign_role, __path_create_role, __path_delete_role, __path_list_roles,
+        __path_rename_role, __path_unassign_role, __path_user_roles,
+    },
     system::{__path_config, __path_health, __path_update_config, __path_version},
-    users::__path_login,
-    audits::{__path_audit_batch, __path_audit_init}
+    users::{
+        __path_add_phone, __path_change_password, __path_forgot_password, __path_login,
+        __path_login_with_email, __path_login_with_phone, __path_me, __path_reset_password,
+        __path_verify_login_with_email, __path_verify_login_with_phone, __path_verify_phone,
+    },
+    audits::{__path_audit_batch, __path_audit_events, __path_audit_init}
 };
 
 use utoipa::OpenApi;
@@ -25,9 +65,71 @@ use utoipa::OpenApi;
         user_details,
         with_email,
         login,
+        login_with_email,
+        verify_login_with_email,
+        login_with_phone,
+        verify_login_with_phone,
+        me,
+        add_phone,
+        verify_phone,
+        list_identities,
+        add_password_identity,
+        add_email_identity,
+        unlink_identity,
+        list_api_keys,
+        create_api_key,
+        revoke_api_key,
+        change_password,
+        forgot_password,
+        reset_password,
+        unlock_account,
+        refresh,
+        logout,
+        enroll_totp,
+        confirm_totp,
+        disable_totp,
+        verify_mfa,
+        verify_mfa_recovery,
+        passkey_register_options,
+        passkey_register,
+        passkey_login_options,
+        passkey_login,
+        federated_providers,
+        federated_start,
+        federated_callback,
+        generate_recovery_codes,
+        recovery_codes_status,
+        login_with_recovery_code,
         users,
+        revoke,
+        admin_unlock_account,
+        account_lockouts,
+        all_api_keys,
+        admin_revoke_api_key,
+        list_roles,
+        create_role,
+        rename_role,
+        delete_role,
+        user_roles,
+        assign_role,
+        unassign_role,
+        list_policies,
+        create_policy,
+        update_policy,
+        delete_policy,
+        explain_policy,
+        jwks,
+        rotate_key,
+        openid_configuration,
+        authorize,
+        token,
+        introspect,
+        userinfo,
+        create_client,
+        create_machine_client,
         audit_init,
-        audit_batch
+        audit_batch,
+        audit_events
     )
 )]
 pub struct ApiDoc;
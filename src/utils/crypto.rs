@@ -1,7 +1,11 @@
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
 use hmac::{Hmac, Mac};
-use sha2::Sha256;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use sha2::{Digest, Sha256};
 
 type HmacSha256 = Hmac<Sha256>;
 
@@ -62,3 +66,69 @@ impl ClientHMAC {
         None
     }
 }
+
+/// Byte comparison whose time does not depend on where the inputs differ,
+/// for codes and secrets typed in by the caller.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// RFC 7638 thumbprint of a P-256 public JWK (`x`, `y` base64url).
+/// Members in lexicographic order, no whitespace, SHA-256, base64url.
+pub fn p256_jwk_thumbprint(x: &str, y: &str) -> String {
+    let canonical = format!(r#"{{"crv":"P-256","kty":"EC","x":"{x}","y":"{y}"}}"#);
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+/// AES-256-GCM for small secrets kept in the DB (e.g. TOTP seeds).
+/// - `seal()` returns `nonce || ciphertext || tag` with a random nonce.
+/// - `aad` binds the ciphertext to its owner (e.g. the user id), so a sealed
+///   value copied onto another row does not open.
+#[derive(Clone)]
+pub struct SecretCipher {
+    key: LessSafeKey,
+}
+
+impl SecretCipher {
+    /// Create from a hex-encoded 32-byte key (e.g. `openssl rand -hex 32`).
+    pub fn from_hex_key(hex_key: &str) -> Result<Self, String> {
+        let key = hex::decode(hex_key).map_err(|e| e.to_string())?;
+        let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| "key must be 32 bytes")?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+        })
+    }
+
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce).ok()?;
+
+        let mut in_out = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad),
+                &mut in_out,
+            )
+            .ok()?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(in_out);
+        Some(sealed)
+    }
+
+    pub fn open(&self, sealed: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(aad), &mut in_out)
+            .ok()?;
+        Some(plaintext.to_vec())
+    }
+}
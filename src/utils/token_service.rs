@@ -1,32 +1,89 @@
 // src/features/auth/token_service.rs
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, Header, Validation};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
 use super::error::Error;
-use crate::{features::system::ConfigService, utils::error::Result};
+use crate::{
+    features::{keys::KeyRingService, roles::RoleRepository, system::ConfigService},
+    utils::error::Result,
+};
 
 #[derive(Clone)]
 pub struct TokenService {
     cfg: Arc<ConfigService>,
-    enc_key: EncodingKey,
-    dec_key: DecodingKey,
+    keys: Arc<KeyRingService>,
+    roles: RoleRepository,
     issuer: String,
     audience: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    Access,
+    Refresh,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
-    pub sub: String, // subject = user id
-    pub uid: i64,    // user id (numeric)
-    pub did: i64,    // device id
-    pub jti: String, // unique id for token
-    pub iat: i64,    // issued at (unix)
-    pub exp: i64,    // expires at (unix)
-    pub iss: String, // issuer
-    pub aud: String, // audience
+    pub sub: String,    // subject = user id
+    pub uid: i64,       // user id (numeric)
+    pub did: i64,       // device id
+    pub sid: Uuid,      // session id (row in `sessions`)
+    pub jti: String,    // unique id for token
+    pub iat: i64,       // issued at (unix)
+    #[serde(default)]
+    pub iat_ms: i64,    // issued at (unix ms), for revocation watermarks
+    pub exp: i64,       // expires at (unix)
+    pub iss: String,    // issuer
+    pub aud: String,    // audience
+    pub typ: TokenKind, // access or refresh
+    pub fam: String,    // refresh rotation family (jti of the first refresh token)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub scope: String, // space separated scopes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>, // OAuth client the token was issued to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>, // role names, first-party tokens only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>, // DPoP key binding (RFC 9449)
+}
+
+/// Access token of a machine client (client-credentials grant). There is no
+/// user, device or session behind it, so no `uid`/`did`/`sid`; it can never
+/// pass for a user token and vice versa.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientTokenClaims {
+    pub sub: String,       // subject = client id
+    pub client_id: String, // RFC 9068 §2.2
+    pub jti: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub iss: String,
+    pub aud: String,
+    pub typ: TokenKind, // always access
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub scope: String,
+}
+
+/// `cnf` claim: the token is only usable together with a proof signed by this key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Confirmation {
+    /// RFC 7638 thumbprint of the device's public key
+    pub jkt: String,
+}
+
+/// What a token pair is good for beyond "this user on this device".
+/// The default (no scope, no client) is a first-party login.
+#[derive(Debug, Clone, Default)]
+pub struct TokenGrant {
+    pub scope: String,
+    pub client_id: Option<String>,
+    /// thumbprint of the DPoP key the tokens get bound to
+    pub jkt: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -35,31 +92,56 @@ pub struct IssuedTokens {
     pub access_expires_at: i64,
     pub refresh_token: Option<String>,
     pub refresh_expires_at: Option<i64>,
+    #[serde(skip)]
+    pub refresh_jti: Option<String>,
+    #[serde(skip)]
+    pub family: String,
+    #[serde(skip)]
+    pub session_id: Uuid,
+    #[serde(skip)]
+    pub jkt: Option<String>,
 }
 
 impl TokenService {
-    /// `ec_private_pem` must be a PKCS#8 or SEC1 EC private key (P-256 recommended).
-    /// `ec_public_pem` must be the matching public key.
+    /// Tokens are signed with the active key of `keys` and carry its `kid`.
     pub fn new(
         cfg: Arc<ConfigService>,
+        keys: Arc<KeyRingService>,
+        roles: RoleRepository,
         issuer: impl Into<String>,
         audience: impl Into<String>,
-    ) -> Result<Self> {
-        let enc_key = EncodingKey::from_ec_pem(&std::fs::read("scripts/ec_private_pkcs8.pem")?)?;
-        let dec_key = DecodingKey::from_ec_pem(&std::fs::read("scripts/ec_public.pem")?)?;
-        Ok(Self {
+    ) -> Self {
+        Self {
             cfg,
-            enc_key,
-            dec_key,
+            keys,
+            roles,
             issuer: issuer.into(),
             audience: audience.into(),
-        })
+        }
     }
 
     /// Create access + optional refresh token based on ConfigDto flags and durations.
-    pub async fn mint_tokens(&self, user_id: i64, device_id: i64) -> Result<IssuedTokens> {
+    /// `family` continues an existing refresh rotation family; `None` starts a new one.
+    /// Roles are read fresh on every mint, so a refresh picks up role changes;
+    /// tokens for OAuth clients never carry them.
+    pub async fn mint_tokens(
+        &self,
+        user_id: i64,
+        device_id: i64,
+        session_id: Uuid,
+        family: Option<&str>,
+        grant: &TokenGrant,
+    ) -> Result<IssuedTokens> {
         let cfg = self.cfg.get().await?; // hot config (Redis → DB)
         let now = Utc::now();
+        let refresh_jti = Uuid::new_v4().to_string();
+        let family = family
+            .map(str::to_owned)
+            .unwrap_or_else(|| refresh_jti.clone());
+        let roles = match grant.client_id {
+            Some(_) => Vec::new(),
+            None => self.roles.names_for_user(user_id).await?,
+        };
 
         // ----- Access token -----
         let access_exp = now + Duration::seconds(cfg.token_validity_seconds as i64);
@@ -67,16 +149,25 @@ impl TokenService {
             sub: user_id.to_string(),
             uid: user_id,
             did: device_id,
+            sid: session_id,
             jti: Uuid::new_v4().to_string(),
             iat: now.timestamp(),
+            iat_ms: now.timestamp_millis(),
             exp: access_exp.timestamp(),
             iss: self.issuer.clone(),
             aud: self.audience.clone(),
+            typ: TokenKind::Access,
+            fam: family.clone(),
+            scope: grant.scope.clone(),
+            cid: grant.client_id.clone(),
+            roles,
+            cnf: grant.jkt.clone().map(|jkt| Confirmation { jkt }),
         };
 
+        let (kid, enc_key) = self.keys.signing_key();
         let mut header = Header::new(Algorithm::ES256);
-        header.kid = None; // set KID if you rotate keys
-        let access_token = encode(&header, &access_claims, &self.enc_key)
+        header.kid = Some(kid);
+        let access_token = encode(&header, &access_claims, &enc_key)
             .map_err(|e| Error::Unexpected(format!("encode access token: {e}")))?;
 
         // ----- Refresh token (conditional) -----
@@ -86,14 +177,22 @@ impl TokenService {
                 sub: user_id.to_string(),
                 uid: user_id,
                 did: device_id,
-                jti: Uuid::new_v4().to_string(),
+                sid: session_id,
+                jti: refresh_jti.clone(),
                 iat: now.timestamp(),
+                iat_ms: now.timestamp_millis(),
                 exp: refresh_exp.timestamp(),
                 iss: self.issuer.clone(),
                 aud: self.audience.clone(),
+                typ: TokenKind::Refresh,
+                fam: family.clone(),
+                scope: grant.scope.clone(),
+                cid: grant.client_id.clone(),
+                roles: Vec::new(), // re-read when the refresh mints new tokens
+                cnf: grant.jkt.clone().map(|jkt| Confirmation { jkt }),
             };
 
-            let refresh = encode(&header, &refresh_claims, &self.enc_key)
+            let refresh = encode(&header, &refresh_claims, &enc_key)
                 .map_err(|e| Error::Unexpected(format!("encode refresh token: {e}")))?;
             (Some(refresh), Some(refresh_exp.timestamp()))
         } else {
@@ -103,18 +202,76 @@ impl TokenService {
         Ok(IssuedTokens {
             access_token,
             access_expires_at: access_exp.timestamp(),
+            refresh_jti: refresh_token.as_ref().map(|_| refresh_jti),
             refresh_token,
             refresh_expires_at,
+            family,
+            session_id,
+            jkt: grant.jkt.clone(),
         })
     }
 
-    /// Optional: verifying (useful for tests or refresh endpoints).
-    pub fn verify(&self, token: &str) -> Result<TokenClaims> {
+    /// Access token for a machine client, valid as long as a user's access token.
+    /// Returns the token and its `exp`.
+    pub async fn mint_client_token(&self, client_id: &str, scope: &str) -> Result<(String, i64)> {
+        let cfg = self.cfg.get().await?;
+        let now = Utc::now();
+        let exp = (now + Duration::seconds(cfg.token_validity_seconds as i64)).timestamp();
+
+        let token = self.sign(&ClientTokenClaims {
+            sub: client_id.to_string(),
+            client_id: client_id.to_string(),
+            jti: Uuid::new_v4().to_string(),
+            iat: now.timestamp(),
+            exp,
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            typ: TokenKind::Access,
+            scope: scope.to_string(),
+        })?;
+        Ok((token, exp))
+    }
+
+    /// Sign arbitrary claims (e.g. OIDC ID tokens) with the active key.
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String> {
+        let (kid, enc_key) = self.keys.signing_key();
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(kid);
+        encode(&header, claims, &enc_key)
+            .map_err(|e| Error::Unexpected(format!("encode token: {e}")))
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Verify signature (key picked by `kid`), issuer, audience and expiry.
+    /// Callers check `typ` themselves.
+    pub async fn verify(&self, token: &str) -> Result<TokenClaims> {
+        self.verify_as(token).await
+    }
+
+    /// [`Self::verify`] for machine client tokens.
+    pub async fn verify_client(&self, token: &str) -> Result<ClientTokenClaims> {
+        self.verify_as(token).await
+    }
+
+    async fn verify_as<T: DeserializeOwned>(&self, token: &str) -> Result<T> {
+        let header = decode_header(token).map_err(|_| Error::Unauthorized)?;
+        if header.alg != Algorithm::ES256 {
+            return Err(Error::Unauthorized);
+        }
+
         let mut val = Validation::new(Algorithm::ES256);
         val.set_audience(&[self.audience.clone()]);
         val.set_issuer(&[self.issuer.clone()]);
-        let data =
-            decode::<TokenClaims>(token, &self.dec_key, &val).map_err(|e| Error::Unauthorized)?;
-        Ok(data.claims)
+
+        self.keys
+            .decoding_keys(header.kid.as_deref())
+            .await?
+            .iter()
+            .find_map(|key| decode::<T>(token, key, &val).ok())
+            .map(|data| data.claims)
+            .ok_or(Error::Unauthorized)
     }
 }